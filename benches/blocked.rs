@@ -0,0 +1,47 @@
+//! Compare rendering a large image a row at a time with rendering it in
+//! square blocks. Run with `cargo bench --bench blocked`.
+//!
+//! The view is far from the set and the limit low, so most pixels escape
+//! within a few iterations and the time goes mostly on memory traffic,
+//! which is what blocking changes.
+
+extern crate mandelbrot;
+extern crate num;
+
+use num::Complex;
+use std::time::{Duration, Instant};
+
+use mandelbrot::render::{Coloring, LIMIT, Shading, Supersampling, render_blocked, render_rows};
+use mandelbrot::view::{ComplexView, PixelBounds};
+
+/// Render a wide RGB image a few times, in blocks if 'blocked' and row
+/// by row otherwise, and return the fastest time.
+fn time_render(blocked: bool) -> Duration {
+    let bounds = PixelBounds::new(16000, 1000);
+    let view = ComplexView::new(Complex { re: 1.0, im: 1.5 },
+                                Complex { re: 4.0, im: -1.5 });
+    let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+    let coloring = Coloring::Palette { colors: &colors, repeat: 1 };
+    let mut pixels = vec![0; bounds.pixel_count() * coloring.channels()];
+
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            if blocked {
+                render_blocked(&mut pixels, bounds, view, Shading::EscapeTime, LIMIT, coloring);
+            } else {
+                render_rows(&mut pixels, bounds, view, 0, Shading::EscapeTime, LIMIT, coloring,
+                            Supersampling::none(), None);
+            }
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    for &(label, blocked) in &[("row by row", false), ("in 64x64 blocks", true)] {
+        let elapsed = time_render(blocked);
+        println!("{:>16}: {:>8.1} ms", label, elapsed.as_secs_f64() * 1000.0);
+    }
+}
@@ -0,0 +1,48 @@
+//! Compare how the chunk size used by `render_parallel` balances work
+//! between threads. Run with `cargo bench --bench chunk_rows`.
+//!
+//! The view is chosen so the set fills the middle rows: those iterate to
+//! the limit while the top and bottom rows escape quickly, so one band per
+//! thread leaves the threads with the outer bands idle for most of the
+//! render.
+
+extern crate mandelbrot;
+extern crate num;
+
+use num::Complex;
+use std::time::{Duration, Instant};
+
+use mandelbrot::render::{Coloring, LIMIT, Shading, Supersampling, Threading, render_parallel};
+use mandelbrot::view::{ComplexView, PixelBounds};
+
+/// Render the benchmark view with 'threading' a few times and return the
+/// fastest time.
+fn time_render(threading: Threading) -> Duration {
+    let bounds = PixelBounds::new(1200, 900);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.5 },
+                                Complex { re: 1.0, im: -1.5 });
+    let mut pixels = vec![0; bounds.pixel_count()];
+
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            render_parallel(&mut pixels, bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                            Supersampling::none(), None, threading);
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let threads = 4;
+    for &chunk_rows in &[None, Some(225), Some(64), Some(16), Some(4), Some(1)] {
+        let elapsed = time_render(Threading { threads, chunk_rows });
+        let label = match chunk_rows {
+            None => "one band per thread".to_string(),
+            Some(rows) => format!("{} rows per chunk", rows),
+        };
+        println!("{} threads, {:>20}: {:>8.1} ms", threads, label,
+                 elapsed.as_secs_f64() * 1000.0);
+    }
+}
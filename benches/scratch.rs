@@ -0,0 +1,63 @@
+//! Count the allocations `render_parallel` makes when it hands the image
+//! out a row at a time. Run with `cargo bench --bench scratch`.
+//!
+//! Every thread keeps one scratch buffer for the points of a row, so the
+//! count should grow with the number of threads but not with the number
+//! of chunks.
+
+extern crate mandelbrot;
+extern crate num;
+
+use num::Complex;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use mandelbrot::render::{Coloring, LIMIT, Shading, Supersampling, Threading, render_parallel};
+use mandelbrot::view::{ComplexView, PixelBounds};
+
+/// The system allocator, counting how many times it allocates.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn main() {
+    let bounds = PixelBounds::new(2000, 1500);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.5 },
+                                Complex { re: 1.0, im: -1.5 });
+    let mut pixels = vec![0; bounds.pixel_count()];
+
+    for &chunk_rows in &[None, Some(64), Some(1)] {
+        let threading = Threading { threads: 4, chunk_rows };
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        render_parallel(&mut pixels, bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                        Supersampling::none(), None, threading);
+        let elapsed = start.elapsed();
+        let label = match chunk_rows {
+            None => "one band per thread".to_string(),
+            Some(rows) => format!("{} rows per chunk", rows),
+        };
+        println!("{:>20}: {:>6} allocations, {:>8.1} ms", label,
+                 ALLOCATIONS.load(Ordering::Relaxed) - before, elapsed.as_secs_f64() * 1000.0);
+    }
+}
@@ -0,0 +1,274 @@
+//! Zoom animations: a sequence of frames, each a little narrower than
+//! the last, about the middle of a starting view.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::RenderError;
+use crate::render::{Coloring, Shading, Supersampling, Threading, render_image};
+use crate::view::{ComplexView, PixelBounds};
+
+/// How many times narrower each frame is than the last unless
+/// `--frame-zoom` says otherwise.
+pub const DEFAULT_FRAME_ZOOM: f64 = 1.1;
+
+/// A zoom into the middle of 'start', 'factor' times narrower every
+/// frame (so a 'factor' below one zooms out), iterating 'limit_growth'
+/// times as far every frame, since deeper frames need more iterations to
+/// show the edge of the set sharply.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Zoom {
+    pub start: ComplexView,
+    pub factor: f64,
+    pub limit_growth: f64,
+}
+
+impl Zoom {
+    /// The view at time 't', in frames from the start. 't' needn't be a
+    /// whole number: between frames the zoom carries on smoothly.
+    pub fn view_at(&self, t: f64) -> ComplexView {
+        let center = (self.start.upper_left + self.start.lower_right) / 2.0;
+        let half = (self.start.lower_right - self.start.upper_left) / (2.0 * self.factor.powf(t));
+        ComplexView { upper_left: center - half, lower_right: center + half, ..self.start }
+    }
+
+    /// The iteration limit of frame 'frame' when the first frame's is
+    /// 'limit', rounded to the nearest count and at least 1.
+    pub fn limit_at(&self, frame: u32, limit: u32) -> u32 {
+        let limit = limit as f64 * self.limit_growth.powi(frame as i32);
+        limit.round().clamp(1.0, u32::MAX as f64) as u32
+    }
+}
+
+/// The times, in frames from the start, of the 'subframes' sub-frames
+/// averaged into frame 'frame': evenly spread from the frame itself
+/// towards the next, as if the shutter were open all the while. They
+/// depend only on the frame index, so every run blurs alike.
+pub fn subframe_times(frame: u32, subframes: u32) -> Vec<f64> {
+    assert!(subframes >= 1, "a frame needs at least one sub-frame");
+    (0..subframes).map(|i| frame as f64 + i as f64 / subframes as f64).collect()
+}
+
+/// Render frame 'frame' of 'zoom' as `render_image` would render its
+/// view, but averaging 'subframes' renders at the `subframe_times`, for
+/// motion blur. With one sub-frame that is just the frame's own view.
+///
+/// Each frame iterates to its own `Zoom::limit_at`, but is colored as if
+/// its limit were the first frame's, 'limit': a color is tied to an
+/// escape count, not to a fraction of the limit, so the same structure
+/// keeps its color from frame to frame instead of the colors crawling as
+/// the limit grows.
+#[allow(clippy::too_many_arguments)]
+pub fn render_frame(zoom: Zoom,
+        frame: u32,
+        subframes: u32,
+        bounds: PixelBounds,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        threading: Threading) -> Result<Vec<u8>, RenderError>
+{
+    let coloring = Coloring::Anchored { base: &coloring, limit };
+    let limit = zoom.limit_at(frame, limit);
+    let times = subframe_times(frame, subframes);
+    if let [t] = times[..] {
+        return render_image(bounds, zoom.view_at(t), shading, limit, coloring, sampling, None,
+                            threading);
+    }
+
+    let mut sums: Vec<u32> = Vec::new();
+    for &t in &times {
+        let pixels = render_image(bounds, zoom.view_at(t), shading, limit, coloring, sampling,
+                                  None, threading)?;
+        sums.resize(pixels.len(), 0);
+        for (sum, &pixel) in sums.iter_mut().zip(&pixels) {
+            *sum += pixel as u32;
+        }
+    }
+
+    // Round to nearest, as the supersampling average does.
+    let count = times.len() as u32;
+    Ok(sums.into_iter().map(|sum| ((sum + count / 2) / count) as u8).collect())
+}
+
+/// Where to save frame 'frame' of 'frames' when asked for 'path': the
+/// frame number, padded to the same width for every frame so the files
+/// sort in order, goes before the extension. `out.png` becomes
+/// `out-07.png` for frame 7 of 100, numbered from 0.
+pub fn frame_path(path: &Path, frame: u32, frames: u32) -> PathBuf {
+    let width = frames.saturating_sub(1).to_string().len();
+    let stem = path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{:0width$}.{}", stem, frame, extension.to_string_lossy(),
+                                   width = width),
+        None => format!("{}-{:0width$}", stem, frame, width = width),
+    };
+    path.with_file_name(name)
+}
+
+/// A JSON manifest of the 'frames' frames of 'zoom' saved, as
+/// `frame_path` numbers them, for 'path', for video encoders, web players
+/// and other tools that take the sequence in: an object whose `frames`
+/// array has, for each frame in order, its `file`, the `upper_left` and
+/// `lower_right` corners of its view as `[re, im]`, its `zoom` (how many
+/// times narrower it is than the first frame) and the `iterations` limit
+/// it was rendered with, `Zoom::limit_at` of 'limit'. Numbers are
+/// written with every digit needed to read back exactly.
+pub fn manifest(zoom: Zoom, frames: u32, path: &Path, limit: u32) -> String {
+    let corner = |point: num::Complex<f64>| format!("[{:e}, {:e}]", point.re, point.im);
+    let entries: Vec<String> = (0..frames).map(|frame| {
+        let view = zoom.view_at(frame as f64);
+        format!("    {{\"file\": {}, \"upper_left\": {}, \"lower_right\": {}, \"zoom\": {:e}, \
+                 \"iterations\": {}}}",
+                json_string(&frame_path(path, frame, frames).to_string_lossy()),
+                corner(view.upper_left), corner(view.lower_right), zoom.factor.powf(frame as f64),
+                zoom.limit_at(frame, limit))
+    }).collect();
+    format!("{{\n  \"frames\": [\n{}\n  ]\n}}\n", entries.join(",\n"))
+}
+
+/// 's' as a JSON string literal, quoted and escaped.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// render_frame test
+#[test]
+fn test_render_frame() {
+    use num::Complex;
+    use crate::render::LIMIT;
+
+    let zoom = Zoom {
+        start: ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 }),
+        factor: 1.5,
+        limit_growth: 1.0,
+    };
+    let bounds = PixelBounds::new(60, 40);
+    let none = Supersampling::none();
+    let frame = |frame, subframes| {
+        render_frame(zoom, frame, subframes, bounds, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                     none, Threading::new(2)).unwrap()
+    };
+
+    // One sub-frame is the plain frame.
+    for index in 0..3 {
+        let plain = render_image(bounds, zoom.view_at(index as f64), Shading::EscapeTime, LIMIT,
+                                 Coloring::Gray, none, None, Threading::new(2)).unwrap();
+        assert_eq!(frame(index, 1), plain);
+    }
+
+    // More sub-frames blur the frame, the same way every time.
+    let blurred = frame(2, 4);
+    assert_eq!(blurred, frame(2, 4));
+    assert!(blurred != frame(2, 1));
+    assert_eq!(blurred.len(), bounds.pixel_count());
+}
+
+/// Zoom::view_at, subframe_times and frame_path test
+#[test]
+fn test_zoom() {
+    use num::Complex;
+
+    let zoom = Zoom {
+        start: ComplexView::new(Complex { re: -2.0, im: 2.0 }, Complex { re: 2.0, im: -2.0 }),
+        factor: 2.0,
+        limit_growth: 1.0,
+    };
+    assert_eq!(zoom.view_at(0.0), zoom.start);
+    assert_eq!(zoom.view_at(2.0),
+               ComplexView::new(Complex { re: -0.5, im: 0.5 }, Complex { re: 0.5, im: -0.5 }));
+
+    assert_eq!(zoom.limit_at(5, 300), 300);
+    let growing = Zoom { limit_growth: 1.5, ..zoom };
+    assert_eq!((growing.limit_at(0, 300), growing.limit_at(1, 300), growing.limit_at(2, 300)),
+               (300, 450, 675));
+    assert_eq!(Zoom { limit_growth: 0.0, ..zoom }.limit_at(1, 300), 1);
+
+    assert_eq!(subframe_times(3, 1), vec![3.0]);
+    assert_eq!(subframe_times(3, 4), vec![3.0, 3.25, 3.5, 3.75]);
+
+    assert_eq!(frame_path(Path::new("dir/out.png"), 7, 100), PathBuf::from("dir/out-07.png"));
+    assert_eq!(frame_path(Path::new("out.png"), 7, 101), PathBuf::from("out-007.png"));
+    assert_eq!(frame_path(Path::new("out"), 0, 1), PathBuf::from("out-0"));
+}
+
+/// manifest test: one entry per frame, with that frame's view.
+#[test]
+fn test_manifest() {
+    use num::Complex;
+
+    let zoom = Zoom {
+        start: ComplexView::new(Complex { re: -2.0, im: 2.0 }, Complex { re: 2.0, im: -2.0 }),
+        factor: 2.0,
+        limit_growth: 2.0,
+    };
+    let manifest = manifest(zoom, 3, Path::new("out/zoom.png"), 500);
+    assert_eq!(manifest.matches("\"file\"").count(), 3);
+    assert!(manifest.starts_with("{\n  \"frames\": [\n"));
+    assert!(manifest.ends_with("\n  ]\n}\n"));
+    assert!(manifest.contains("    {\"file\": \"out/zoom-0.png\", \"upper_left\": [-2e0, 2e0], \
+                               \"lower_right\": [2e0, -2e0], \"zoom\": 1e0, \"iterations\": 500},\n"));
+    assert!(manifest.contains("    {\"file\": \"out/zoom-1.png\", \"upper_left\": [-1e0, 1e0], \
+                               \"lower_right\": [1e0, -1e0], \"zoom\": 2e0, \"iterations\": 1000},\n"));
+    assert!(manifest.contains("    {\"file\": \"out/zoom-2.png\", \"upper_left\": [-5e-1, 5e-1], \
+                               \"lower_right\": [5e-1, -5e-1], \"zoom\": 4e0, \"iterations\": 2000}\n"));
+
+    assert_eq!(json_string("a \"b\"\\c\n"), "\"a \\\"b\\\"\\\\c\\u000a\"");
+}
+
+/// render_frame test: with the limit growing frame to frame, a point
+/// keeps its color from one frame to the next.
+#[test]
+fn test_frame_colors_anchored() {
+    use num::Complex;
+    use crate::render::LIMIT;
+
+    // Each frame is the middle half of the last, at 64×64 pixels, so
+    // pixel (x, y) of frame 1 is at the point of pixel (16 + x/2, 16 + y/2)
+    // of frame 0, exactly.
+    let zoom = Zoom {
+        start: ComplexView::new(Complex { re: -2.0, im: 2.0 }, Complex { re: 2.0, im: -2.0 }),
+        factor: 2.0,
+        limit_growth: 4.0,
+    };
+    let bounds = PixelBounds::new(64, 64);
+    let palette = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+    let coloring = Coloring::Palette { colors: &palette, repeat: 1 };
+    let none = Supersampling::none();
+    let frame = |frame| {
+        render_frame(zoom, frame, 1, bounds, Shading::EscapeTime, LIMIT, coloring, none,
+                     Threading::new(2)).unwrap()
+    };
+    let (first, second) = (frame(0), frame(1));
+    let unanchored = render_image(bounds, zoom.view_at(1.0), Shading::EscapeTime, 4 * LIMIT,
+                                  coloring, none, None, Threading::new(2)).unwrap();
+
+    let (mut compared, mut crawled) = (0, 0);
+    for y in (0..64).step_by(2) {
+        for x in (0..64).step_by(2) {
+            let before = &first[3 * (64 * (16 + y / 2) + 16 + x / 2)..][..3];
+            let after = &second[3 * (64 * y + x)..][..3];
+            // Points that escape only past the first frame's limit were
+            // black in it.
+            if before != [0, 0, 0] {
+                assert_eq!(before, after, "pixel {},{} of the second frame", x, y);
+                compared += 1;
+                crawled += (before != &unanchored[3 * (64 * y + x)..][..3]) as usize;
+            }
+        }
+    }
+    assert!(compared > 500, "{} pixels compared", compared);
+    // Colored by the fraction of its own limit, the second frame would
+    // have shifted nearly every color.
+    assert!(crawled * 10 > compared * 9, "{} of {} crawled", crawled, compared);
+}
@@ -0,0 +1,59 @@
+//! Classifying a list of points as in or out of the set, for labeling
+//! datasets without rendering anything.
+
+use std::io::{BufRead, Write};
+
+use crate::escape::escape_time;
+use crate::parse::parse_complex;
+
+/// The first line `classify` writes, naming its columns.
+pub const HEADER: &str = "re,im,class,escape_count";
+
+/// Read one point per line of 'input', written `RE,IM` as for the
+/// corners on the command line, and write a CSV line for each to
+/// 'output' under `HEADER`: the point as given, `interior` or `exterior`
+/// by `escape_time` with at most 'limit' iterations, and the escape count
+/// of exterior points (empty for interior ones). Blank lines are skipped.
+/// Returns the number of points classified, or an error naming the
+/// first line that isn't a point, counting from 1.
+pub fn classify<R: BufRead, W: Write>(input: R, mut output: W, limit: u32) -> Result<usize, String> {
+    let write_error = |error| format!("error writing the classifications: {}", error);
+    writeln!(output, "{}", HEADER).map_err(write_error)?;
+
+    let mut points = 0;
+    for (i, line) in input.lines().enumerate() {
+        let line = line.map_err(|error| format!("error reading line {}: {}", i + 1, error))?;
+        let point = line.trim();
+        if point.is_empty() {
+            continue;
+        }
+        let c = parse_complex(point).ok_or_else(|| {
+            format!("line {}: invalid point `{}`, expected RE,IM", i + 1, point)
+        })?;
+        match escape_time(c, limit) {
+            Some(count) => writeln!(output, "{},exterior,{}", point, count),
+            None => writeln!(output, "{},interior,", point),
+        }.map_err(write_error)?;
+        points += 1;
+    }
+
+    Ok(points)
+}
+
+/// classify test
+#[test]
+fn test_classify() {
+    let input = "0,0\n3,0\n\n  0.5,0  \n-1,0\n";
+    let mut output = Vec::new();
+    assert_eq!(classify(input.as_bytes(), &mut output, 255), Ok(4));
+    assert_eq!(String::from_utf8(output).unwrap(),
+               "re,im,class,escape_count\n\
+                0,0,interior,\n\
+                3,0,exterior,0\n\
+                0.5,0,exterior,4\n\
+                -1,0,interior,\n");
+
+    let error = classify("0,0\n1,1\nnonsense\n2,2\n".as_bytes(), Vec::new(), 255).unwrap_err();
+    assert!(error.starts_with("line 3:"), "{}", error);
+    assert!(error.contains("`nonsense`"), "{}", error);
+}
@@ -0,0 +1,83 @@
+//! Pixel-by-pixel comparison of two images, for checking that a change
+//! to the renderer left its output alone, or seeing where it didn't.
+
+use image::{DynamicImage, GenericImage};
+
+use crate::view::PixelBounds;
+
+/// How far apart two images are, over every channel of every pixel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difference {
+    /// The largest absolute difference between two samples.
+    pub max: u8,
+    /// The mean absolute difference between samples.
+    pub mean: f64,
+    /// A grayscale image of the largest difference among each pixel's
+    /// channels, black where the images agree.
+    pub pixels: Vec<u8>,
+    pub bounds: PixelBounds,
+}
+
+/// Compare 'a' with 'b', both taken as RGBA so that images of different
+/// color types can still be compared. Images of different sizes can't,
+/// and are an error saying so.
+pub fn compare(a: &DynamicImage, b: &DynamicImage) -> Result<Difference, String> {
+    if a.dimensions() != b.dimensions() {
+        let (a_width, a_height) = a.dimensions();
+        let (b_width, b_height) = b.dimensions();
+        return Err(format!("can't compare a {}x{} image with a {}x{} one",
+                           a_width, a_height, b_width, b_height));
+    }
+
+    let (width, height) = a.dimensions();
+    let bounds = PixelBounds::new(width as usize, height as usize);
+    let (a, b) = (a.to_rgba().into_raw(), b.to_rgba().into_raw());
+    let mut pixels = Vec::with_capacity(bounds.pixel_count());
+    let mut total = 0u64;
+    for (a, b) in a.chunks(4).zip(b.chunks(4)) {
+        let mut largest = 0;
+        for (&a, &b) in a.iter().zip(b) {
+            let difference = (a as i16 - b as i16).unsigned_abs() as u8;
+            largest = largest.max(difference);
+            total += difference as u64;
+        }
+        pixels.push(largest);
+    }
+
+    Ok(Difference {
+        max: pixels.iter().copied().max().unwrap_or(0),
+        mean: if a.is_empty() { 0.0 } else { total as f64 / a.len() as f64 },
+        pixels,
+        bounds,
+    })
+}
+
+/// compare test
+#[test]
+fn test_compare() {
+    use image::{GrayImage, RgbImage};
+
+    let mut gray = GrayImage::new(3, 2);
+    for (i, pixel) in gray.pixels_mut().enumerate() {
+        pixel.data = [i as u8 * 40];
+    }
+    let gray = DynamicImage::ImageLuma8(gray);
+
+    // An image against itself: no difference at all.
+    let same = compare(&gray, &gray).unwrap();
+    assert_eq!((same.max, same.mean), (0, 0.0));
+    assert_eq!(same.pixels, vec![0; 6]);
+    assert_eq!(same.bounds, PixelBounds::new(3, 2));
+
+    // The same gray as RGB compares equal too; change one channel of
+    // one pixel and only that pixel differs.
+    let mut rgb = gray.to_rgb();
+    rgb.get_pixel_mut(1, 1).data[2] += 12;
+    let changed = compare(&gray, &DynamicImage::ImageRgb8(rgb)).unwrap();
+    assert_eq!(changed.max, 12);
+    assert_eq!(changed.mean, 12.0 / 24.0);
+    assert_eq!(changed.pixels, vec![0, 0, 0, 0, 12, 0]);
+
+    let error = compare(&gray, &DynamicImage::ImageRgb8(RgbImage::new(2, 3))).unwrap_err();
+    assert_eq!(error, "can't compare a 3x2 image with a 2x3 one");
+}
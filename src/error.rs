@@ -0,0 +1,84 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::render::Threading;
+use crate::view::{ComplexView, PixelBounds};
+
+/// Everything that can go wrong rendering and saving an image.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The image has no pixels, or more than a buffer can hold.
+    InvalidBounds(PixelBounds),
+    /// The view has a corner that isn't a finite number, or covers no
+    /// area of the plane.
+    InvalidView(ComplexView),
+    /// The rows of the image can't be split up as this asks without
+    /// overflowing, as `Threading::check` finds.
+    InvalidThreading(Threading),
+    /// The pixel buffer, this many bytes long, couldn't be allocated.
+    Allocation { bytes: usize },
+    /// The pixels couldn't be encoded in the requested format.
+    Encode(io::Error),
+    /// The encoded image couldn't be written.
+    Io(io::Error),
+    /// Two renders of the same image differed, first at this pixel.
+    Nondeterministic { column: usize, row: usize },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::InvalidBounds(bounds) =>
+                write!(f, "can't render an image of {}x{} pixels", bounds.width, bounds.height),
+            RenderError::InvalidView(view) =>
+                write!(f, "can't render the view from {} to {}", view.upper_left, view.lower_right),
+            RenderError::InvalidThreading(threading) => match threading.chunk_rows {
+                Some(rows) => write!(f, "can't split the image into chunks of {} rows for {} threads",
+                                     rows, threading.threads),
+                None => write!(f, "can't split the image between {} threads", threading.threads),
+            },
+            RenderError::Allocation { bytes } =>
+                write!(f, "couldn't allocate {} bytes for the image", bytes),
+            RenderError::Encode(error) => write!(f, "error encoding the image: {}", error),
+            RenderError::Io(error) => write!(f, "error writing the image: {}", error),
+            RenderError::Nondeterministic { column, row } =>
+                write!(f, "two renders of the image differ, first at pixel ({}, {})", column, row),
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::Encode(error) | RenderError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(error: io::Error) -> RenderError {
+        RenderError::Io(error)
+    }
+}
+
+/// RenderError test
+#[test]
+fn test_render_error() {
+    let error = RenderError::InvalidBounds(PixelBounds::new(0, 10));
+    assert_eq!(error.to_string(), "can't render an image of 0x10 pixels");
+    assert!(error.source().is_none());
+
+    let error = RenderError::from(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+    assert!(matches!(error, RenderError::Io(_)));
+    assert_eq!(error.to_string(), "error writing the image: denied");
+    assert!(error.source().is_some());
+
+    let error = RenderError::InvalidThreading(Threading { threads: 3, chunk_rows: Some(usize::MAX) });
+    assert_eq!(error.to_string(),
+               format!("can't split the image into chunks of {} rows for 3 threads", usize::MAX));
+
+    let error = RenderError::Nondeterministic { column: 3, row: 17 };
+    assert_eq!(error.to_string(), "two renders of the image differ, first at pixel (3, 17)");
+}
@@ -0,0 +1,361 @@
+use num::Complex;
+
+#[cfg(test)]
+use crate::view::{ComplexView, PixelBounds, pixel_to_point};
+
+pub use crate::kernel::escape_time;
+
+/// Carry on the orbit computed by `escape_time` from where it left off:
+/// 'z' is the orbit's value after 'start' iterations, and iteration
+/// continues until 'limit' in total. Returns `Ok(i)` if 'c' escapes at
+/// iteration 'i', exactly as `escape_time(c, limit)` would report it, or
+/// `Err(z)` with the orbit's value after 'limit' iterations, from which
+/// it can be continued again.
+pub fn continue_escape_time(c: Complex<f64>, z: Complex<f64>, start: u32, limit: u32)
+    -> Result<u32, Complex<f64>> {
+    let mut z = z;
+    for i in start..limit {
+        z = z*z + c;
+        if z.norm_sqr() > 4.0 {
+            return Ok(i);
+        }
+    }
+
+    Err(z)
+}
+
+/// Like `escape_time`, but with |z|^2 at the iteration 'c' escapes on as
+/// well: Some((i, norm_sqr)), where 'norm_sqr' is past the bailout radius
+/// of two squared, 4. How far past says how fast the orbit was leaving,
+/// which is what smooth coloring and bailout tuning look at. Returns None
+/// for points that seem to be members of the set.
+pub fn escape_time_detailed(c: Complex<f64>, limit: u32) -> Option<(u32, f64)> {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    for i in 0..limit {
+        z = z*z + c;
+        let norm_sqr = z.norm_sqr();
+        if norm_sqr > 4.0 {
+            return Some((i, norm_sqr));
+        }
+    }
+
+    None
+}
+
+/// Number of extra iterations `smooth_escape_time` runs past escape
+/// unless `--smooth-extra` says otherwise.
+pub const DEFAULT_SMOOTH_EXTRA: u32 = 3;
+
+/// Like `escape_time`, but return a fractional ("smooth") escape count
+/// so neighbouring pixels with different integer counts blend together.
+///
+/// Once 'c' escapes, the orbit is iterated 'extra' more times before
+/// taking the logarithm. The formula assumes |z| is large compared to
+/// 'c', which is barely true right at the bailout radius, so with no
+/// extra steps the value jumps by about half a count wherever the integer
+/// count changes and faint bands remain. Each extra step squares |z| and
+/// shrinks that error; two to four steps hide it completely, and more
+/// only cost time. Returns None for points that seem to be members.
+///
+/// Far enough out, or with enough extra steps, |z|^2 overflows to
+/// infinity (or the orbit to NaN) and the formula has no finite value.
+/// The integer escape count is returned for those points instead, so
+/// they are shaded like their neighbours rather than as black specks.
+pub fn smooth_escape_time(c: Complex<f64>, limit: u32, extra: u32) -> Option<f64> {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    for i in 0..limit {
+        z = z*z + c;
+        if z.norm_sqr() > 4.0 {
+            for _ in 0..extra {
+                z = z*z + c;
+            }
+            let smooth = smooth_count(i + extra, z, 2.0);
+            return Some(if smooth.is_finite() { smooth } else { i as f64 });
+        }
+    }
+
+    None
+}
+
+/// The fractional escape count of an orbit of z -> z^'degree' + c that
+/// escaped at iteration 'count', counting any extra steps, and has got
+/// to 'z': `count + 1 - log_degree(ln|z|)`.
+///
+/// Far from the set each step raises |z| to the power 'degree', so
+/// ln|z| grows 'degree'-fold and its logarithm to base 'degree' goes up
+/// by one per step; only in that base does the fraction make up exactly
+/// for the whole count. The textbook formula also divides ln|z| by the
+/// log of the bailout radius, which only shifts every count by the same
+/// constant, and is left out.
+pub fn smooth_count(count: u32, z: Complex<f64>, degree: f64) -> f64 {
+    let log_modulus = z.norm_sqr().ln() / 2.0;
+    count as f64 + 1.0 - log_modulus.ln() / degree.ln()
+}
+
+/// Once |z| passes this, `lyapunov` considers the orbit gone to infinity.
+/// It is far beyond the usual radius of two so that the rapidly growing
+/// terms of a divergent orbit dominate the average and make it clearly
+/// positive, even for slow escapers that first linger near the set.
+const LYAPUNOV_ESCAPE_NORM_SQR: f64 = 1e20;
+
+/// Estimate the Lyapunov exponent of the orbit of 0 under z -> z^2 + c:
+/// the average over at most 'limit' iterations of ln|f'(z)| = ln|2z|.
+///
+/// It measures how quickly nearby orbits separate. Members of the set
+/// whose orbits settle into an attracting cycle give a negative value,
+/// the more negative the more strongly attracting; divergent exterior
+/// orbits give a positive one. Points whose orbit hits 0 exactly (the
+/// centers of the hyperbolic components) would give minus infinity, so
+/// each term is floored at the logarithm of the smallest positive f64,
+/// keeping the estimate finite.
+pub fn lyapunov(c: Complex<f64>, limit: u32) -> f64 {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    let mut sum = 0.0;
+    let mut steps = 0;
+    for _ in 0..limit {
+        z = z*z + c;
+        sum += (2.0 * z.norm()).max(f64::MIN_POSITIVE).ln();
+        steps += 1;
+        if z.norm_sqr() > LYAPUNOV_ESCAPE_NORM_SQR {
+            break;
+        }
+    }
+
+    if steps == 0 { 0.0 } else { sum / steps as f64 }
+}
+
+/// The longest cycle `attractor_period` looks for unless told otherwise.
+pub const MAX_PERIOD: u32 = 64;
+
+/// How close, squared, a point of an orbit has to come back to itself
+/// for `attractor_period` to take the orbit as having closed.
+const PERIOD_TOLERANCE_SQR: f64 = 1e-12;
+
+/// The period of the attracting cycle the orbit of 0 under z -> z^2 + c
+/// settles into: 1 in the main cardioid, 2 in the bulb to its left, and
+/// so on, the same throughout each bulb. The orbit is iterated 'limit'
+/// times to let it settle, then for up to 'max_period' more steps until
+/// it comes back to where it was. Returns None if 'c' escapes within
+/// 'limit' iterations, as for `escape_time`, or if its orbit hasn't
+/// settled onto a cycle of at most 'max_period' points by then, as near
+/// the edges of bulbs, where orbits are attracted very slowly.
+pub fn attractor_period(c: Complex<f64>, limit: u32, max_period: u32) -> Option<u32> {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    for _ in 0..limit {
+        z = z*z + c;
+        if z.norm_sqr() > 4.0 {
+            return None;
+        }
+    }
+
+    let settled = z;
+    for period in 1..=max_period {
+        z = z*z + c;
+        if (z - settled).norm_sqr() < PERIOD_TOLERANCE_SQR {
+            return Some(period);
+        }
+    }
+    None
+}
+
+/// Once |z| passes this, `escape_derivative` stops. A radius well past
+/// two lets the ratio z/z' settle on the direction away from the set.
+const DERIVATIVE_ESCAPE_NORM_SQR: f64 = 1e4;
+
+/// Iterate z -> z^2 + c from 0 for at most 'limit' steps, tracking the
+/// derivative of z with respect to c alongside (z' -> 2zz' + 1). Returns
+/// the final z and z' once |z| is well clear of the circle of radius two,
+/// or None for points that seem to be members of the set.
+///
+/// These are what the distance estimator is built from: |z| ln|z| / |z'|
+/// estimates the distance from 'c' to the set, and z/z' points away from
+/// it, which makes it a surface normal for lighting.
+pub fn escape_derivative(c: Complex<f64>, limit: u32) -> Option<(Complex<f64>, Complex<f64>)> {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    let mut derivative = Complex { re: 0.0, im: 0.0 };
+    for _ in 0..limit {
+        derivative = 2.0 * z * derivative + 1.0;
+        z = z*z + c;
+        if z.norm_sqr() > DERIVATIVE_ESCAPE_NORM_SQR {
+            return Some((z, derivative));
+        }
+    }
+
+    None
+}
+
+/// Once |z| passes this, `potential` stops iterating. Out there the
+/// next step would square |z| to within a part in 1e10, so ln|z| / 2^n
+/// has settled on the potential.
+const POTENTIAL_ESCAPE_NORM_SQR: f64 = 1e20;
+
+/// The exterior potential of 'c', the Green's function of the set:
+/// G(c) = lim ln|z_n| / 2^n, where z_n is the orbit of 'c' under
+/// z -> z^2 + c after 'n' steps. It is 0 on the boundary and grows
+/// outward, like ln|c| far from the set, and is smooth everywhere
+/// outside it. Returns None if the orbit doesn't leave the circle of
+/// radius two within 'limit' iterations, as for `escape_time`; orbits
+/// that do are carried on until |z| is far larger, so the limit is
+/// reached to within rounding.
+pub fn potential(c: Complex<f64>, limit: u32) -> Option<f64> {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    for i in 0..limit {
+        z = z*z + c;
+        if z.norm_sqr() > 4.0 {
+            // The orbit from 0 reaches 'c' itself after one step.
+            let mut steps = i;
+            while z.norm_sqr() <= POTENTIAL_ESCAPE_NORM_SQR {
+                z = z*z + c;
+                steps += 1;
+            }
+            return Some(z.norm_sqr().ln() / 2.0 * (-(steps as f64)).exp2());
+        }
+    }
+
+    None
+}
+
+/// smooth_escape_time test: along a row that stays outside the set, the
+/// smooth value must not jump where the integer escape count changes.
+#[test]
+fn test_smooth_escape_time_continuity() {
+    let bounds = PixelBounds::new(2000, 1);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.2 },
+                                Complex { re: 1.0, im: 1.2 });
+
+    // Largest jump in the smooth value between neighbouring pixels whose
+    // integer escape counts differ.
+    let max_jump = |extra: u32| {
+        let mut boundaries = 0;
+        let mut max_jump: f64 = 0.0;
+        for column in 1..bounds.width {
+            let left = pixel_to_point(bounds, (column - 1, 0), view);
+            let right = pixel_to_point(bounds, (column, 0), view);
+            if escape_time(left, 255) != escape_time(right, 255) {
+                boundaries += 1;
+                let jump = smooth_escape_time(left, 255, extra).unwrap()
+                    - smooth_escape_time(right, 255, extra).unwrap();
+                max_jump = max_jump.max(jump.abs());
+            }
+        }
+        assert!(boundaries > 0);
+        max_jump
+    };
+
+    assert!(max_jump(DEFAULT_SMOOTH_EXTRA) < 0.05);
+    // Without extra steps the banding is still visible.
+    assert!(max_jump(0) > 0.1);
+}
+
+/// smooth_escape_time test: overflowing orbits fall back to the integer
+/// count instead of an infinite or NaN one.
+#[test]
+fn test_smooth_escape_time_overflow() {
+    // One extra step squares |c|^2 = 2e200 past the largest f64.
+    let c = Complex { re: 1e100, im: 1e100 };
+    assert_eq!(escape_time(c, 255), Some(0));
+    assert_eq!(smooth_escape_time(c, 255, 1), Some(0.0));
+    assert_eq!(smooth_escape_time(c, 255, 5), Some(0.0));
+
+    // Without extra steps the same point is finite and left alone.
+    let smooth = smooth_escape_time(c, 255, 0).unwrap();
+    assert!(smooth.is_finite() && smooth != 0.0);
+}
+
+/// continue_escape_time test
+#[test]
+fn test_continue_escape_time() {
+    let zero = Complex { re: 0.0, im: 0.0 };
+    for &(re, im) in &[(0.26, 0.0), (-0.75, 0.1), (0.3, 0.5), (-0.5, 0.0), (1.0, 1.0)] {
+        let c = Complex { re, im };
+        let resumed = match continue_escape_time(c, zero, 0, 10) {
+            Ok(count) => Some(count),
+            Err(z) => continue_escape_time(c, z, 10, 1000).ok(),
+        };
+        assert_eq!(resumed, escape_time(c, 1000));
+    }
+}
+
+/// escape_time_detailed test
+#[test]
+fn test_escape_time_detailed() {
+    assert_eq!(escape_time_detailed(Complex { re: -0.5, im: 0.0 }, 255), None);
+    assert_eq!(escape_time_detailed(Complex { re: 3.0, im: 0.0 }, 255), Some((0, 9.0)));
+
+    for &(re, im) in &[(0.5, 0.0), (0.26, 0.0), (-0.75, 0.1), (-2.1, 0.0), (0.0, 1.1)] {
+        let c = Complex { re, im };
+        let (count, norm_sqr) = escape_time_detailed(c, 1000).unwrap();
+        assert_eq!(Some(count), escape_time(c, 1000));
+        assert!(norm_sqr > 4.0, "{} at {},{}", norm_sqr, re, im);
+    }
+}
+
+/// lyapunov test
+#[test]
+fn test_lyapunov() {
+    // In the main cardioid and the period-2 bulb orbits are attracted to a
+    // cycle; outside they diverge, even when they linger near the set first.
+    for &(re, im) in &[(-0.5, 0.0), (-1.3, 0.0), (-0.12, 0.75), (0.0, 0.0)] {
+        let exponent = lyapunov(Complex { re, im }, 255);
+        assert!(exponent.is_finite() && exponent <= 0.0, "{} at {},{}", exponent, re, im);
+    }
+    for &(re, im) in &[(1.0, 0.0), (0.26, 0.0), (-0.75, 0.1), (-2.5, 0.0)] {
+        let exponent = lyapunov(Complex { re, im }, 255);
+        assert!(exponent.is_finite() && exponent > 0.0, "{} at {},{}", exponent, re, im);
+    }
+}
+
+/// attractor_period test
+#[test]
+fn test_attractor_period() {
+    // The main cardioid, the bulbs of periods 2, 3 (the "rabbit") and 4,
+    // and the period 3 window on the real axis.
+    for &(re, im, period) in &[(0.0, 0.0, 1), (-0.5, 0.3, 1), (-1.0, 0.0, 2), (-1.1, 0.1, 2),
+                               (-0.12, 0.75, 3), (-1.31, 0.0, 4), (-1.755, 0.0, 3)] {
+        assert_eq!(attractor_period(Complex { re, im }, 1000, MAX_PERIOD), Some(period),
+                   "at {},{}", re, im);
+    }
+    assert_eq!(attractor_period(Complex { re: 1.0, im: 0.0 }, 1000, MAX_PERIOD), None);
+    // Longer than allowed.
+    assert_eq!(attractor_period(Complex { re: -1.31, im: 0.0 }, 1000, 3), None);
+}
+
+/// escape_derivative test
+#[test]
+fn test_escape_derivative() {
+    assert_eq!(escape_derivative(Complex { re: -0.5, im: 0.0 }, 255), None);
+
+    // Just right of the cusp of the cardioid the set lies to the left, so
+    // z/z' points right.
+    let (z, derivative) = escape_derivative(Complex { re: 0.3, im: 0.0 }, 255).unwrap();
+    let away = z / derivative;
+    assert!(away.re > 0.0 && away.im.abs() < 1e-9 * away.re);
+
+    // The distance estimate is of the right size: 0.75 is about half a
+    // unit from the set, whose rightmost point is 0.25.
+    let (z, derivative) = escape_derivative(Complex { re: 0.75, im: 0.0 }, 255).unwrap();
+    let distance = z.norm() * z.norm().ln() / derivative.norm();
+    assert!(distance > 0.1 && distance < 1.0, "{}", distance);
+}
+
+/// potential test: it grows steadily along a ray leaving the set, and
+/// approaches ln|c| far out.
+#[test]
+fn test_potential() {
+    assert_eq!(potential(Complex { re: -0.5, im: 0.0 }, 255), None);
+
+    // From the tip of the needle at -2 out along the negative real axis,
+    // and from near the cusp at 0.25 out along the positive one.
+    for &(start, direction) in &[(-2.0, -1.0), (0.2501, 1.0)] {
+        let mut previous = 0.0;
+        for step in 1 .. 2000 {
+            let re = start + direction * step as f64 * 0.005;
+            let value = potential(Complex { re, im: 0.0 }, 10_000).unwrap();
+            assert!(value > previous, "{} after {} at {}", value, previous, re);
+            previous = value;
+        }
+    }
+
+    let far = Complex { re: 300.0, im: 400.0 };
+    assert!((potential(far, 255).unwrap() - far.norm().ln()).abs() < 1e-2);
+}
@@ -0,0 +1,721 @@
+use num::Complex;
+
+use crate::escape::{continue_escape_time, escape_time, smooth_escape_time};
+use crate::rng;
+use crate::view::{ComplexView, PixelBounds, Projection, pixel_to_point};
+
+/// The outcome of iterating one point, without the loss of detail that
+/// comes from squeezing it into a gray level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Escape {
+    /// The point left the circle of radius two after this many iterations.
+    Escaped(u32),
+    /// The point was still inside after the iteration limit, so it seems
+    /// to be a member of the set.
+    Interior,
+}
+
+impl Escape {
+    /// The escape count, or None for interior points.
+    pub fn count(self) -> Option<u32> {
+        match self {
+            Escape::Escaped(count) => Some(count),
+            Escape::Interior => None,
+        }
+    }
+
+    pub fn is_interior(self) -> bool {
+        self == Escape::Interior
+    }
+
+    /// How many iterations were run to reach this outcome with at most
+    /// 'limit': one more than the escape count, since escaping at count
+    /// 0 takes one, or all of 'limit' for interior points.
+    pub fn iterations(self, limit: u32) -> u32 {
+        match self {
+            Escape::Escaped(count) => count + 1,
+            Escape::Interior => limit,
+        }
+    }
+}
+
+impl From<Option<u32>> for Escape {
+    /// Convert the result of `escape_time`.
+    fn from(count: Option<u32>) -> Escape {
+        match count {
+            Some(count) => Escape::Escaped(count),
+            None => Escape::Interior,
+        }
+    }
+}
+
+/// Iterate every pixel of an image of size 'bounds' covering 'view',
+/// using at most 'limit' iterations, and return the outcomes in row-major
+/// order.
+pub fn render_field(bounds: PixelBounds, view: ComplexView, limit: u32) -> Vec<Escape> {
+    let mut field = Vec::with_capacity(bounds.pixel_count());
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let point = pixel_to_point(bounds, (column, row), view);
+            field.push(Escape::from(escape_time(point, limit)));
+        }
+    }
+
+    field
+}
+
+/// Whether 'c' lies in the main cardioid or the period-2 bulb, where
+/// every orbit is drawn to a cycle and never escapes. Most of the set's
+/// area is in one or the other, and this settles those points without
+/// iterating them all the way to the limit.
+fn in_main_bulbs(c: Complex<f64>) -> bool {
+    let shifted = Complex { re: c.re - 0.25, ..c };
+    let q = shifted.norm_sqr();
+    q * (q + shifted.re) <= 0.25 * c.im * c.im
+        || (c.re + 1.0) * (c.re + 1.0) + c.im * c.im <= 1.0 / 16.0
+}
+
+/// Whether each pixel of an image of size 'bounds' covering 'view' seems
+/// to be a member of the set at 'limit' iterations, in row-major order:
+/// the pixels `render_field` would find `Escape::Interior`, without
+/// keeping escape counts or colors. Points in the main cardioid and the
+/// period-2 bulb are known to be members without iterating them.
+pub fn render_membership(bounds: PixelBounds, view: ComplexView, limit: u32) -> Vec<bool> {
+    let mut members = Vec::with_capacity(bounds.pixel_count());
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let point = pixel_to_point(bounds, (column, row), view);
+            members.push(in_main_bulbs(point) || escape_time(point, limit).is_none());
+        }
+    }
+
+    members
+}
+
+/// Whether any pixel of an image of size 'bounds' covering 'view' seems
+/// to be a member of the set at 'limit' iterations. Stops at the first
+/// one found, without keeping the rest of the field.
+pub fn contains_interior(bounds: PixelBounds, view: ComplexView, limit: u32) -> bool {
+    (0 .. bounds.height).any(|row| (0 .. bounds.width).any(|column| {
+        escape_time(pixel_to_point(bounds, (column, row), view), limit).is_none()
+    }))
+}
+
+/// The fraction of the pixels of an image of size 'bounds' covering
+/// 'view' that seem to be members of the set at 'limit' iterations, or 0
+/// for an image with no pixels.
+pub fn interior_fraction(bounds: PixelBounds, view: ComplexView, limit: u32) -> f64 {
+    let interior = render_membership(bounds, view, limit).iter().filter(|&&member| member).count();
+
+    if interior == 0 { 0.0 } else { interior as f64 / bounds.pixel_count() as f64 }
+}
+
+/// The iteration limit `auto_limit` starts from for `--iterations-auto`.
+pub const AUTO_LIMIT_START: u32 = 64;
+
+/// The limit `--iterations-auto` never goes past, however slowly the
+/// interior settles.
+pub const AUTO_LIMIT_MAX: u32 = 1 << 20;
+
+/// How small a change in the interior, as a fraction of it, counts as
+/// settled for `--iterations-auto`.
+pub const AUTO_LIMIT_THRESHOLD: f64 = 0.01;
+
+/// Find an iteration limit high enough for an image of size 'bounds'
+/// covering 'view'. Starting at 'start', the limit is doubled until the
+/// number of pixels still inside changes by no more than 'threshold'
+/// times itself, or the limit reaches 'max', and the last limit tried is
+/// returned. Too low a limit counts slowly escaping points as members of
+/// the set, so the interior shrinks until the limit is high enough; past
+/// that, more iterations only cost time.
+///
+/// Pixels that escape stay escaped at higher limits, so only those still
+/// inside are iterated again, carrying on their orbits where they left
+/// off; no iteration is done twice.
+pub fn auto_limit(bounds: PixelBounds, view: ComplexView, start: u32, threshold: f64, max: u32)
+    -> u32 {
+    assert!(start >= 1 && start <= max);
+    let zero = Complex { re: 0.0, im: 0.0 };
+    let mut limit = start;
+    let mut inside = Vec::new();
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let point = pixel_to_point(bounds, (column, row), view);
+            if let Err(z) = continue_escape_time(point, zero, 0, limit) {
+                inside.push((point, z));
+            }
+        }
+    }
+
+    while limit < max {
+        let next = limit.saturating_mul(2).min(max);
+        let before = inside.len();
+        inside = inside.into_iter()
+            .filter_map(|(point, z)| continue_escape_time(point, z, limit, next).err()
+                                         .map(|z| (point, z)))
+            .collect();
+        limit = next;
+        if (before - inside.len()) as f64 <= threshold * before as f64 {
+            break;
+        }
+    }
+
+    limit
+}
+
+/// The rectangle `estimate_area` samples: it holds the whole set, which
+/// reaches from -2 to about 0.47 on the real axis and about 1.12 either
+/// side of it.
+pub const AREA_BOUNDS: ComplexView = ComplexView {
+    upper_left: Complex { re: -2.0, im: 1.25 },
+    lower_right: Complex { re: 0.5, im: -1.25 },
+    projection: Projection::Plane,
+};
+
+/// The fewest iterations `--area` runs. Points that take longer than the
+/// limit to escape are counted in, so the estimate is always a little
+/// high; at this many that bias is well below the standard error of
+/// any affordable number of samples.
+pub const AREA_LIMIT: u32 = 10_000;
+
+/// Estimate the area of the set by Monte Carlo: iterate 'samples' random
+/// points of `AREA_BOUNDS`, uniformly spread, to at most 'limit'
+/// iterations, and scale the fraction that seem to be members by the
+/// rectangle's area. Returns the estimate and its standard error. The
+/// points come from `rng` hashes of 'seed', so the same seed always
+/// gives the same estimate.
+pub fn estimate_area(samples: u64, limit: u32, seed: u64) -> (f64, f64) {
+    let view = AREA_BOUNDS;
+    let (width, height) = (view.lower_right.re - view.upper_left.re,
+                           view.upper_left.im - view.lower_right.im);
+    let interior = (0..samples)
+        .filter(|&i| {
+            let point = Complex {
+                re: view.upper_left.re + width * rng::unit_float(rng::hash(seed, &[i, 0])),
+                im: view.lower_right.im + height * rng::unit_float(rng::hash(seed, &[i, 1])),
+            };
+            in_main_bulbs(point) || escape_time(point, limit).is_none()
+        })
+        .count();
+
+    let fraction = if samples == 0 { 0.0 } else { interior as f64 / samples as f64 };
+    let error = (fraction * (1.0 - fraction) / samples.max(1) as f64).sqrt();
+    (width * height * fraction, width * height * error)
+}
+
+/// Like `render_field`, but with the fractional `smooth_escape_time` of
+/// every pixel (run 'extra' steps past escape), or None for interior ones.
+pub fn smooth_field(bounds: PixelBounds, view: ComplexView, limit: u32, extra: u32)
+    -> Vec<Option<f64>> {
+    let mut field = Vec::with_capacity(bounds.pixel_count());
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let point = pixel_to_point(bounds, (column, row), view);
+            field.push(smooth_escape_time(point, limit, extra));
+        }
+    }
+
+    field
+}
+
+/// The potential of every pixel: `lim ln|z_n| / 2^n` over the orbit of
+/// its point, which is 0 on the set and grows continuously away from it,
+/// like the potential of a charged conductor the shape of the set. It is
+/// `2^-count` of the `smooth_field` count (run 'extra' steps past
+/// escape), so pixels that don't escape within 'limit' iterations get 0.
+pub fn potential_field(bounds: PixelBounds, view: ComplexView, limit: u32, extra: u32) -> Vec<f32> {
+    smooth_field(bounds, view, limit, extra).into_iter()
+        .map(|count| count.map_or(0.0, |count| (-count).exp2() as f32))
+        .collect()
+}
+
+/// Like `render_field`, but spend at most 'probe_limit' iterations on
+/// every pixel first, and only carry on to the full 'limit' for the
+/// pixels still inside after that.
+///
+/// Far from the set most pixels escape within a handful of iterations,
+/// while the candidate members near and inside it run to the full limit.
+/// The first pass settles the cheap pixels quickly and leaves the slow
+/// ones in a list of their own, which the second pass works through.
+/// Pixels that go on to the full limit resume their orbit where the
+/// probe left it rather than starting again, so no iteration is done
+/// twice and the result is exactly the same as
+/// `render_field(bounds, view, limit)`.
+pub fn render_field_adaptive(bounds: PixelBounds, view: ComplexView, probe_limit: u32, limit: u32)
+    -> Vec<Escape> {
+    let probe_limit = probe_limit.min(limit);
+    let zero = Complex { re: 0.0, im: 0.0 };
+
+    let mut field = Vec::with_capacity(bounds.pixel_count());
+    let mut pending = Vec::new();
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let point = pixel_to_point(bounds, (column, row), view);
+            match continue_escape_time(point, zero, 0, probe_limit) {
+                Ok(count) => field.push(Escape::Escaped(count)),
+                Err(z) => {
+                    pending.push((field.len(), point, z));
+                    field.push(Escape::Interior);
+                }
+            }
+        }
+    }
+
+    for (index, point, z) in pending {
+        if let Ok(count) = continue_escape_time(point, z, probe_limit, limit) {
+            field[index] = Escape::Escaped(count);
+        }
+    }
+
+    field
+}
+
+/// Rectangles of the Mariani-Silver subdivision this narrow or narrower
+/// are iterated pixel by pixel: their borders are most of their pixels.
+const MARIANI_SILVER_MIN_SIDE: usize = 4;
+
+/// Like `render_field`, but skip iterating the pixels of rectangles that
+/// look uniform, by Mariani-Silver subdivision. Returns the field and the
+/// number of pixels actually iterated.
+///
+/// Starting from the whole image, the border of each rectangle is
+/// iterated. If every border pixel has the same outcome, and so do a few
+/// pixels sampled inside (the middle and the middles of its quarters), the
+/// inside is filled with that outcome; otherwise the rectangle is split
+/// into quarters, which share the borders already iterated. Since the set
+/// is connected and has no holes, a rectangle bordered by members holds
+/// nothing else; the sampling is there to catch the small copies of the
+/// set that a band of equal escape counts can surround. Views with large
+/// areas of one outcome come out far quicker, and match `render_field`
+/// unless a feature too small to touch a border or a sample is missed.
+pub fn render_field_mariani_silver(bounds: PixelBounds, view: ComplexView, limit: u32)
+    -> (Vec<Escape>, usize) {
+    let mut field: Vec<Option<Escape>> = vec![None; bounds.pixel_count()];
+    let mut computed = 0;
+    let mut outcome = |field: &mut Vec<Option<Escape>>, column: usize, row: usize| {
+        let index = row * bounds.width + column;
+        *field[index].get_or_insert_with(|| {
+            computed += 1;
+            let point = pixel_to_point(bounds, (column, row), view);
+            Escape::from(escape_time(point, limit))
+        })
+    };
+
+    // Rectangles still to do, as `(left, top, right, bottom)`, exclusive
+    // of 'right' and 'bottom'.
+    let mut rectangles = vec![(0, 0, bounds.width, bounds.height)];
+    while let Some((left, top, right, bottom)) = rectangles.pop() {
+        if right - left <= MARIANI_SILVER_MIN_SIDE || bottom - top <= MARIANI_SILVER_MIN_SIDE {
+            for row in top..bottom {
+                for column in left..right {
+                    outcome(&mut field, column, row);
+                }
+            }
+            continue;
+        }
+
+        let first = outcome(&mut field, left, top);
+        let mut uniform = true;
+        for column in left..right {
+            uniform &= outcome(&mut field, column, top) == first;
+            uniform &= outcome(&mut field, column, bottom - 1) == first;
+        }
+        for row in top..bottom {
+            uniform &= outcome(&mut field, left, row) == first;
+            uniform &= outcome(&mut field, right - 1, row) == first;
+        }
+        let (middle_x, middle_y) = ((left + right) / 2, (top + bottom) / 2);
+        if uniform {
+            let samples = [(middle_x, middle_y),
+                           ((left + middle_x) / 2, (top + middle_y) / 2),
+                           ((middle_x + right) / 2, (top + middle_y) / 2),
+                           ((left + middle_x) / 2, (middle_y + bottom) / 2),
+                           ((middle_x + right) / 2, (middle_y + bottom) / 2)];
+            uniform = samples.iter().all(|&(column, row)| outcome(&mut field, column, row) == first);
+        }
+
+        if uniform {
+            for row in top + 1 .. bottom - 1 {
+                for pixel in &mut field[row * bounds.width + left + 1 .. row * bounds.width + right - 1] {
+                    pixel.get_or_insert(first);
+                }
+            }
+        } else {
+            rectangles.push((left, top, middle_x, middle_y));
+            rectangles.push((middle_x, top, right, middle_y));
+            rectangles.push((left, middle_y, middle_x, bottom));
+            rectangles.push((middle_x, middle_y, right, bottom));
+        }
+    }
+
+    (field.into_iter().map(|escape| escape.expect("every pixel is iterated or filled")).collect(),
+     computed)
+}
+
+/// The lowest and highest escape counts in 'field', or None if every
+/// pixel is interior.
+pub fn escape_range(field: &[Escape]) -> Option<(u32, u32)> {
+    field.iter().filter_map(|escape| escape.count()).fold(None, |range, count| match range {
+        None => Some((count, count)),
+        Some((lowest, highest)) => Some((lowest.min(count), highest.max(count))),
+    })
+}
+
+/// Count how many pixels of 'field' escaped after each number of
+/// iterations. Entry 'n' of the result is the number of pixels with
+/// `Escape::Escaped(n)`; interior pixels are not counted. Escape counts
+/// are kept as full `u32`s, so limits far beyond what fits in a byte or
+/// a `u16` are binned exactly.
+pub fn escape_histogram(field: &[Escape], limit: u32) -> Vec<usize> {
+    let mut histogram = vec![0; limit as usize];
+    for count in field.iter().filter_map(|escape| escape.count()) {
+        histogram[count as usize] += 1;
+    }
+
+    histogram
+}
+
+/// A text report of `escape_histogram`, for looking at how a view's
+/// escape counts are spread: a line `FIRST-LAST: PIXELS` for each run of
+/// 'bucket' counts that any pixel escaped in (just `COUNT: PIXELS` when
+/// 'bucket' is 1), in order, then `interior: PIXELS`. Empty buckets are
+/// left out, so high limits with few distinct counts stay short.
+pub fn escape_count_report(field: &[Escape], limit: u32, bucket: u32) -> String {
+    assert!(bucket >= 1, "a bucket needs at least one escape count");
+    let mut report = String::new();
+    for (i, counts) in escape_histogram(field, limit).chunks(bucket as usize).enumerate() {
+        let pixels: usize = counts.iter().sum();
+        if pixels == 0 {
+            continue;
+        }
+        let first = i * bucket as usize;
+        if counts.len() == 1 {
+            report += &format!("{}: {}\n", first, pixels);
+        } else {
+            report += &format!("{}-{}: {}\n", first, first + counts.len() - 1, pixels);
+        }
+    }
+    let interior = field.iter().filter(|escape| escape.is_interior()).count();
+    report + &format!("interior: {}\n", interior)
+}
+
+/// Turn 'field' into 8-bit gray levels, scaling escape counts from
+/// `0..limit` down to `255..0` as `render` does for a limit of 255. The
+/// scaling is done in 64 bits, so large limits and counts don't overflow
+/// or wrap around. Interior pixels are black.
+pub fn tone_map(field: &[Escape], limit: u32) -> Vec<u8> {
+    field.iter()
+        .map(|escape| match *escape {
+            Escape::Escaped(count) => 255 - (count as u64 * 255 / limit as u64) as u8,
+            Escape::Interior => 0,
+        })
+        .collect()
+}
+
+/// Like `tone_map`, but into 16-bit gray levels, scaling escape counts
+/// from `0..limit` down to `65535..0`, for limits too high for 256
+/// levels to tell the counts apart. Interior pixels are black.
+pub fn tone_map_16(field: &[Escape], limit: u32) -> Vec<u16> {
+    field.iter()
+        .map(|escape| match *escape {
+            Escape::Escaped(count) => 65535 - (count as u64 * 65535 / limit as u64) as u16,
+            Escape::Interior => 0,
+        })
+        .collect()
+}
+
+/// Like `tone_map`, but into just `2^bits` gray levels, 'bits' from 1 to
+/// 8, scaling escape counts from `0..limit` down to `2^bits - 1..0`, for
+/// textures that trade detail for size. Interior pixels are 0. At 8 bits
+/// this is `tone_map`. `output::pack_samples` packs the levels tightly.
+pub fn tone_map_bits(field: &[Escape], limit: u32, bits: u8) -> Vec<u8> {
+    assert!((1..=8).contains(&bits));
+    let top = (1u64 << bits) - 1;
+    field.iter()
+        .map(|escape| match *escape {
+            Escape::Escaped(count) => (top - count as u64 * top / limit as u64) as u8,
+            Escape::Interior => 0,
+        })
+        .collect()
+}
+
+/// Turn 'field' into a grayscale map of the work each pixel took: the
+/// iterations run, from none in black to all of 'limit' in white. The
+/// interior, which always runs to the limit, comes out white, so the
+/// expensive parts of a render stand out.
+pub fn heatmap(field: &[Escape], limit: u32) -> Vec<u8> {
+    field.iter()
+        .map(|escape| (escape.iterations(limit) as u64 * 255 / limit as u64) as u8)
+        .collect()
+}
+
+/// render_field test
+#[test]
+fn test_render_field() {
+    // Two pixels: c = 0 never escapes, c = 3 escapes on the first iteration.
+    let view = ComplexView::new(Complex { re: 0.0, im: 0.0 },
+                                Complex { re: 6.0, im: 0.0 });
+    let field = render_field(PixelBounds::new(2, 1), view, 255);
+    assert_eq!(field, vec![Escape::Interior, Escape::Escaped(0)]);
+    assert!(field[0].is_interior());
+    assert_eq!(field[0].count(), None);
+    assert_eq!(field[1].count(), Some(0));
+}
+
+/// render_field test with counts that don't fit in a u16
+#[test]
+fn test_render_field_large_limit() {
+    // Just to the right of the cusp at 1/4 the orbit creeps away slowly.
+    let c = Complex { re: 0.25 + 2e-9, im: 0.0 };
+    let limit = 100_000;
+    let field = render_field(PixelBounds::new(1, 1), ComplexView::new(c, c), limit);
+
+    let count = field[0].count().unwrap();
+    assert!(count > 65535);
+    assert_eq!(Some(count), escape_time(c, limit));
+
+    let histogram = escape_histogram(&field, limit);
+    assert_eq!(histogram.len(), limit as usize);
+    assert_eq!(histogram[count as usize], 1);
+    assert_eq!(histogram.iter().sum::<usize>(), 1);
+
+    assert_eq!(tone_map(&field, limit), vec![255 - (count as u64 * 255 / 100_000) as u8]);
+}
+
+/// escape_range test
+#[test]
+fn test_escape_range() {
+    let field = [Escape::Escaped(7), Escape::Interior, Escape::Escaped(3), Escape::Escaped(12)];
+    assert_eq!(escape_range(&field), Some((3, 12)));
+    assert_eq!(escape_range(&[Escape::Escaped(5)]), Some((5, 5)));
+    assert_eq!(escape_range(&[Escape::Interior]), None);
+    assert_eq!(escape_range(&[]), None);
+}
+
+/// escape_count_report test
+#[test]
+fn test_escape_count_report() {
+    let bounds = PixelBounds::new(40, 30);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.2 },
+                                Complex { re: 1.0, im: -1.2 });
+    let limit = 100;
+    let field = render_field(bounds, view, limit);
+
+    for &bucket in &[1, 7, 100, 1000] {
+        let report = escape_count_report(&field, limit, bucket);
+        assert!(report.ends_with(&format!("interior: {}\n",
+                                          field.iter().filter(|e| e.is_interior()).count())));
+        let total: usize = report.lines()
+            .map(|line| line.rsplit(": ").next().unwrap().parse::<usize>().unwrap())
+            .sum();
+        assert_eq!(total, bounds.pixel_count(), "bucket {}", bucket);
+    }
+
+    let field = [Escape::Escaped(0), Escape::Escaped(3), Escape::Escaped(3), Escape::Interior];
+    assert_eq!(escape_count_report(&field, 10, 1), "0: 1\n3: 2\ninterior: 1\n");
+    assert_eq!(escape_count_report(&field, 10, 3), "0-2: 1\n3-5: 2\ninterior: 1\n");
+    assert_eq!(escape_count_report(&field, 10, 4), "0-3: 3\ninterior: 1\n");
+}
+
+/// auto_limit test
+#[test]
+fn test_auto_limit() {
+    // Near the boundary, slowly escaping points need far more than the
+    // starting limit to be told from the set.
+    let bounds = PixelBounds::new(40, 30);
+    let seahorse = ComplexView::new(Complex { re: -0.765, im: 0.128 },
+                                    Complex { re: -0.725, im: 0.098 });
+    let limit = auto_limit(bounds, seahorse, 16, AUTO_LIMIT_THRESHOLD, AUTO_LIMIT_MAX);
+    assert!(limit >= 1024, "{}", limit);
+    assert!(limit.is_power_of_two() && limit < AUTO_LIMIT_MAX);
+    // A stricter threshold never settles sooner.
+    assert!(auto_limit(bounds, seahorse, 16, AUTO_LIMIT_THRESHOLD / 2.0, AUTO_LIMIT_MAX) >= limit);
+
+    // Far outside the set nothing is inside to settle: one doubling does.
+    let outside = ComplexView::new(Complex { re: 2.0, im: 2.0 }, Complex { re: 3.0, im: 1.0 });
+    assert_eq!(auto_limit(bounds, outside, 16, AUTO_LIMIT_THRESHOLD, AUTO_LIMIT_MAX), 32);
+    assert_eq!(auto_limit(bounds, seahorse, 16, 0.0, 100), 100);
+}
+
+/// render_field_mariani_silver test: the same field as brute force, for
+/// less work
+#[test]
+fn test_render_field_mariani_silver() {
+    // Mostly the inside of the main cardioid, with the boundary running
+    // along the edges of the view.
+    let bounds = PixelBounds::new(240, 180);
+    let view = ComplexView::new(Complex { re: -0.8, im: 0.6 },
+                                Complex { re: 0.3, im: -0.6 });
+    let limit = 64;
+    let (field, computed) = render_field_mariani_silver(bounds, view, limit);
+    assert_eq!(field, render_field(bounds, view, limit));
+    assert!(computed < bounds.pixel_count() / 2, "{} of {}", computed, bounds.pixel_count());
+
+    // Tiny images, all border, and odd sizes are iterated in full.
+    for &(width, height) in &[(1, 1), (3, 7), (17, 5)] {
+        let bounds = PixelBounds::new(width, height);
+        let (field, _) = render_field_mariani_silver(bounds, view, limit);
+        assert_eq!(field, render_field(bounds, view, limit));
+    }
+}
+
+/// render_field_adaptive test
+#[test]
+fn test_render_field_adaptive() {
+    // Covers the far exterior, the boundary and the interior.
+    let bounds = PixelBounds::new(60, 40);
+    let view = ComplexView::new(Complex { re: -2.2, im: 1.3 },
+                                Complex { re: 0.8, im: -1.3 });
+    let uniform = render_field(bounds, view, 2000);
+    assert!(uniform.iter().any(|escape| escape.is_interior()));
+    assert!(uniform.iter().any(|escape| escape.count().is_some_and(|count| count > 50)));
+
+    for &probe_limit in &[0, 1, 10, 50, 2000, 5000] {
+        assert!(render_field_adaptive(bounds, view, probe_limit, 2000) == uniform,
+                "probe limit {} changed the field", probe_limit);
+    }
+}
+
+/// tone_map test
+#[test]
+fn test_tone_map() {
+    let field = [Escape::Interior, Escape::Escaped(0), Escape::Escaped(254)];
+    assert_eq!(tone_map(&field, 255), vec![0, 255, 1]);
+}
+
+/// smooth_field test
+#[test]
+fn test_smooth_field() {
+    let bounds = PixelBounds::new(30, 20);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 },
+                                Complex { re: 1.0, im: -1.0 });
+    let smooth = smooth_field(bounds, view, 255, 3);
+    let field = render_field(bounds, view, 255);
+    assert_eq!(smooth.len(), field.len());
+    for (count, escape) in smooth.iter().zip(&field) {
+        assert_eq!(count.is_none(), escape.is_interior());
+    }
+}
+
+/// potential_field test: 0 on the set, and ln|z_n| / 2^n outside it.
+#[test]
+fn test_potential_field() {
+    let bounds = PixelBounds::new(30, 20);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 },
+                                Complex { re: 1.0, im: -1.0 });
+    let potential = potential_field(bounds, view, 255, 4);
+    for (value, escape) in potential.iter().zip(&render_field(bounds, view, 255)) {
+        assert_eq!(*value == 0.0, escape.is_interior());
+        assert!(*value >= 0.0);
+    }
+
+    // Straight from the definition, far enough out that the orbit has
+    // settled into squaring.
+    let c = Complex { re: 0.5, im: 0.5 };
+    let mut z: Complex<f64> = Complex { re: 0.0, im: 0.0 };
+    for _ in 0..10 {
+        z = z*z + c;
+    }
+    let expected = (z.norm_sqr().ln() / 2.0) / 2f64.powi(10);
+    let point = PixelBounds::new(1, 1);
+    let at_c = ComplexView::new(c, c + Complex { re: 1e-9, im: -1e-9 });
+    let value = potential_field(point, at_c, 255, 8)[0] as f64;
+    assert!((value - expected).abs() < 1e-6 * expected, "{} against {}", value, expected);
+}
+
+/// render_membership test
+#[test]
+fn test_render_membership() {
+    // The whole set, and a close view across the edge of the cardioid and
+    // the bulb, where the shortcut meets the iteration.
+    let views = [ComplexView::new(Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 }),
+                 ComplexView::new(Complex { re: -0.8, im: 0.15 }, Complex { re: -0.7, im: -0.15 }),
+                 ComplexView::new(Complex { re: 0.2, im: 0.6 }, Complex { re: 0.3, im: 0.5 })];
+    let bounds = PixelBounds::new(90, 70);
+    for &view in &views {
+        let members = render_membership(bounds, view, 500);
+        let field = render_field(bounds, view, 500);
+        assert_eq!(members.len(), field.len());
+        for (&member, escape) in members.iter().zip(&field) {
+            assert_eq!(member, escape.is_interior());
+        }
+    }
+
+    assert!(in_main_bulbs(Complex { re: 0.0, im: 0.0 }));
+    assert!(in_main_bulbs(Complex { re: -1.0, im: 0.0 }));
+    assert!(!in_main_bulbs(Complex { re: 0.3, im: 0.0 }));
+    assert!(!in_main_bulbs(Complex { re: -0.1, im: 0.9 }));
+}
+
+/// contains_interior and interior_fraction test
+#[test]
+fn test_interior_probe() {
+    let bounds = PixelBounds::new(40, 30);
+
+    // Entirely outside the circle of radius two.
+    let outside = ComplexView::new(Complex { re: 3.0, im: 1.0 }, Complex { re: 5.0, im: -1.0 });
+    assert!(!contains_interior(bounds, outside, 255));
+    assert_eq!(interior_fraction(bounds, outside, 255), 0.0);
+
+    let whole = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let field = render_field(bounds, whole, 255);
+    let interior = field.iter().filter(|escape| escape.is_interior()).count();
+    assert!(contains_interior(bounds, whole, 255));
+    assert_eq!(interior_fraction(bounds, whole, 255), interior as f64 / field.len() as f64);
+
+    // Inside the main cardioid.
+    let inside = ComplexView::new(Complex { re: -0.3, im: 0.1 }, Complex { re: -0.1, im: -0.1 });
+    assert_eq!(interior_fraction(bounds, inside, 255), 1.0);
+
+    assert_eq!(interior_fraction(PixelBounds::new(0, 0), whole, 255), 0.0);
+}
+
+/// estimate_area test: close to the set's true area of about 1.5066.
+#[test]
+fn test_estimate_area() {
+    let (area, error) = estimate_area(20_000, 2_000, 1);
+    assert!(error > 0.01 && error < 0.03, "standard error {}", error);
+    assert!((area - 1.5066).abs() < 3.0 * error + 0.02, "area {} ± {}", area, error);
+
+    assert_eq!(estimate_area(1_000, 500, 7), estimate_area(1_000, 500, 7));
+    assert!(estimate_area(1_000, 500, 7) != estimate_area(1_000, 500, 8));
+    assert_eq!(estimate_area(0, 500, 7), (0.0, 0.0));
+}
+
+/// heatmap test
+#[test]
+fn test_heatmap() {
+    let field = [Escape::Interior, Escape::Escaped(0), Escape::Escaped(127), Escape::Escaped(254)];
+    assert_eq!(field[0].iterations(255), 255);
+    assert_eq!(field[1].iterations(255), 1);
+    assert_eq!(heatmap(&field, 255), vec![255, 1, 128, 255]);
+
+    // Every interior pixel of a real render registers the full limit.
+    let bounds = PixelBounds::new(40, 30);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let field = render_field(bounds, view, 1000);
+    let map = heatmap(&field, 1000);
+    assert!(field.iter().any(|escape| escape.is_interior()));
+    for (escape, &level) in field.iter().zip(&map) {
+        assert_eq!(escape.is_interior(), escape.iterations(1000) == 1000);
+        if escape.is_interior() {
+            assert_eq!(level, 255);
+        }
+    }
+}
+
+/// tone_map_16 test
+#[test]
+fn test_tone_map_16() {
+    let field = [Escape::Interior, Escape::Escaped(0), Escape::Escaped(1), Escape::Escaped(4095)];
+    assert_eq!(tone_map_16(&field, 4096), vec![0, 65535, 65520, 16]);
+}
+
+/// tone_map_bits test
+#[test]
+fn test_tone_map_bits() {
+    let field = [Escape::Interior, Escape::Escaped(0), Escape::Escaped(100), Escape::Escaped(255)];
+    assert_eq!(tone_map_bits(&field, 256, 4), vec![0, 15, 10, 1]);
+    assert_eq!(tone_map_bits(&field, 256, 1), vec![0, 1, 1, 1]);
+    assert_eq!(tone_map_bits(&field, 256, 8), tone_map(&field, 256));
+}
@@ -0,0 +1,275 @@
+//! Escape-time fractals other than the Mandlebrot set, as iteration maps
+//! that share one escape loop.
+
+use num::Complex;
+
+use crate::escape::smooth_count;
+pub use crate::kernel::{IterMap, escape_time_generic, has_escaped};
+use crate::view::{ComplexView, PixelBounds, pixel_to_point};
+
+/// The Mandlebrot set: z -> z^2 + c from 0, as in `escape::escape_time`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mandelbrot;
+
+impl IterMap for Mandelbrot {
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        z*z + c
+    }
+}
+
+/// The filled Julia set of z -> z^2 + 'c' for a fixed 'c': every point of
+/// the plane is a starting value rather than a parameter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Julia {
+    pub c: Complex<f64>,
+}
+
+impl Julia {
+    /// The Julia set whose 'c' is the point at 'pixel', a (column, row)
+    /// pair, of an image of size 'bounds' covering 'view' of the
+    /// Mandlebrot set. The most intricate Julia sets come from points on
+    /// its boundary; points well inside give blobs, and points outside
+    /// dust.
+    pub fn from_pixel(bounds: PixelBounds, pixel: (usize, usize), view: ComplexView) -> Julia {
+        Julia { c: pixel_to_point(bounds, pixel, view) }
+    }
+
+    /// A view of a whole Julia set for an image of size 'bounds': four
+    /// units wide about the origin, which holds every filled Julia set
+    /// of z^2 + c, and as tall as the image's shape makes it.
+    pub fn whole_view(bounds: PixelBounds) -> ComplexView {
+        let half_height = 2.0 * bounds.height as f64 / bounds.width.max(1) as f64;
+        ComplexView::new(Complex { re: -2.0, im: half_height },
+                         Complex { re: 2.0, im: -half_height })
+    }
+}
+
+impl IterMap for Julia {
+    fn step(&self, z: Complex<f64>, _c: Complex<f64>) -> Complex<f64> {
+        z*z + self.c
+    }
+
+    fn z0(&self, c: Complex<f64>) -> Complex<f64> {
+        c
+    }
+}
+
+/// The Tricorn, or Mandelbar: z -> conj(z)^2 + c from 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tricorn;
+
+impl IterMap for Tricorn {
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        let z = z.conj();
+        z*z + c
+    }
+}
+
+/// The Burning Ship: z -> (|re z| + i |im z|)^2 + c from 0. With the
+/// imaginary axis pointing up, as `view` has it, the ship is upside down;
+/// swap the corners' imaginary parts to right it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BurningShip;
+
+impl IterMap for BurningShip {
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        let z = Complex { re: z.re.abs(), im: z.im.abs() };
+        z*z + c
+    }
+}
+
+/// The Multibrot set of a real, possibly fractional, 'exponent':
+/// z -> z^exponent + c from 0. An exponent of 2 is the Mandlebrot set,
+/// up to rounding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Multibrot {
+    pub exponent: f64,
+}
+
+impl IterMap for Multibrot {
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+        z.powf(self.exponent) + c
+    }
+
+    fn degree(&self) -> f64 {
+        self.exponent
+    }
+}
+
+/// Like `escape::smooth_escape_time`, but iterating 'map': the fractional
+/// `smooth_count` of an orbit that escapes, as `has_escaped` decides,
+/// after 'extra' more steps, to the base of the map's degree. Orbits that
+/// overflow have no finite fraction and get the integer count, as there.
+pub fn smooth_escape_time_generic<M: IterMap>(map: &M, c: Complex<f64>, limit: u32, extra: u32)
+    -> Option<f64> {
+    let mut z = map.z0(c);
+    for i in 0..limit {
+        z = map.step(z, c);
+        if has_escaped(z) {
+            for _ in 0..extra {
+                z = map.step(z, c);
+            }
+            let smooth = smooth_count(i + extra, z, map.degree());
+            return Some(if smooth.is_finite() { smooth } else { i as f64 });
+        }
+    }
+
+    None
+}
+
+/// escape_time_generic test: the generic Mandlebrot is the hand-written one.
+#[test]
+fn test_escape_time_generic_mandelbrot() {
+    use crate::escape::escape_time;
+
+    let bounds = PixelBounds::new(120, 80);
+    let view = ComplexView::new(Complex { re: -2.2, im: 1.3 }, Complex { re: 0.8, im: -1.3 });
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let c = pixel_to_point(bounds, (column, row), view);
+            assert_eq!(escape_time_generic(&Mandelbrot, c, 500), escape_time(c, 500), "at {}", c);
+        }
+    }
+}
+
+/// Julia::from_pixel and Julia::whole_view test
+#[test]
+fn test_julia_from_pixel() {
+    let bounds = PixelBounds::new(400, 300);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.125 }, Complex { re: 1.0, im: -1.125 });
+    for &pixel in &[(0, 0), (123, 45), (399, 299)] {
+        assert_eq!(Julia::from_pixel(bounds, pixel, view).c, pixel_to_point(bounds, pixel, view));
+    }
+    // The origin of the plane is at the image's middle pixel column.
+    assert!(Julia::from_pixel(bounds, (266, 150), view).c.re.abs() < 0.01);
+
+    assert_eq!(Julia::whole_view(bounds),
+               ComplexView::new(Complex { re: -2.0, im: 1.5 }, Complex { re: 2.0, im: -1.5 }));
+}
+
+/// Julia, Tricorn and BurningShip test
+#[test]
+fn test_other_maps() {
+    // With c = 0 the Julia set is the unit disk.
+    let disk = Julia { c: Complex { re: 0.0, im: 0.0 } };
+    assert_eq!(escape_time_generic(&disk, Complex { re: 0.6, im: -0.7 }, 255), None);
+    assert!(escape_time_generic(&disk, Complex { re: 0.8, im: 0.7 }, 255).is_some());
+
+    // On the real axis the orbit stays real, so conjugating it changes
+    // nothing and the Tricorn agrees with the Mandlebrot set there.
+    for &re in &[-0.1, 0.2, 0.25, 0.3, 1.0] {
+        let c = Complex { re, im: 0.0 };
+        let expected = escape_time_generic(&Mandelbrot, c, 255);
+        assert_eq!(escape_time_generic(&Tricorn, c, 255), expected, "{}", re);
+    }
+    // Elsewhere the maps differ: -1 + 0.2i is inside the Mandlebrot set's
+    // period-2 bulb, but the Burning Ship throws its orbit away.
+    let c = Complex { re: -1.0, im: 0.2 };
+    assert_eq!(escape_time_generic(&Mandelbrot, c, 255), None);
+    assert!(escape_time_generic(&BurningShip, c, 255).is_some());
+}
+
+/// Multibrot and has_escaped test
+#[test]
+fn test_multibrot_overflow() {
+    // 1.5 is inside the circle, but 1.5^2000 overflows: in polar form
+    // that is infinity at angle 0, whose imaginary part, infinity times
+    // zero, is NaN. The orbit has escaped, at the second iteration.
+    let multibrot = Multibrot { exponent: 2000.0 };
+    let c = Complex { re: 1.5, im: 0.0 };
+    let z = multibrot.step(multibrot.step(multibrot.z0(c), c), c);
+    assert!(z.im.is_nan() && z.norm_sqr().is_nan());
+    assert!(has_escaped(z));
+    assert_eq!(escape_time_generic(&multibrot, c, 255), Some(1));
+
+    // Members of the set stay finite and inside.
+    assert_eq!(escape_time_generic(&multibrot, Complex { re: -0.5, im: 0.0 }, 255), None);
+    let cubic = Multibrot { exponent: 3.0 };
+    assert_eq!(escape_time_generic(&cubic, Complex { re: 0.2, im: 0.3 }, 255), None);
+    assert!(!has_escaped(Complex { re: 2.0, im: 0.0 }));
+    assert!(has_escaped(Complex { re: f64::INFINITY, im: 0.0 }));
+}
+
+/// smooth_escape_time_generic test: with the count's fraction in base 3,
+/// the cubic Multibrot set is shaded without jumps where the integer
+/// count changes; in base 2, as for the Mandlebrot set, it isn't.
+#[test]
+fn test_smooth_escape_time_generic_continuity() {
+    // The cubic set lies within |c| <= sqrt(2), so this line misses it.
+    let cubic = Multibrot { exponent: 3.0 };
+    let points: Vec<Complex<f64>> = (0..2000)
+        .map(|i| Complex { re: -1.5 + 3.0 * i as f64 / 2000.0, im: 1.42 })
+        .collect();
+
+    // Largest jump in the smooth value between neighbouring points whose
+    // integer escape counts differ, with the fraction to base 'base'.
+    let max_jump = |base: f64| {
+        let smooth = |c| {
+            let count = escape_time_generic(&cubic, c, 255).unwrap();
+            let mut z = cubic.z0(c);
+            for _ in 0 ..= count + 3 {
+                z = cubic.step(z, c);
+            }
+            let log_modulus = z.norm_sqr().ln() / 2.0;
+            (count + 3) as f64 + 1.0 - log_modulus.ln() / base.ln()
+        };
+        let mut boundaries = 0;
+        let mut max_jump: f64 = 0.0;
+        for pair in points.windows(2) {
+            if escape_time_generic(&cubic, pair[0], 255) != escape_time_generic(&cubic, pair[1], 255) {
+                boundaries += 1;
+                max_jump = max_jump.max((smooth(pair[0]) - smooth(pair[1])).abs());
+            }
+        }
+        assert!(boundaries > 0);
+        max_jump
+    };
+    assert!(max_jump(3.0) < 0.05, "{}", max_jump(3.0));
+    assert!(max_jump(2.0) > 0.2, "{}", max_jump(2.0));
+
+    // The generic version is the one in base 3.
+    for &c in &points[..50] {
+        let count = escape_time_generic(&cubic, c, 255).unwrap() as f64;
+        let smooth = smooth_escape_time_generic(&cubic, c, 255, 3).unwrap();
+        assert!(smooth > count - 1.0 && smooth < count + 2.0, "{} for {}", smooth, count);
+    }
+
+    // The Mandlebrot map agrees with the hand-written smooth count.
+    let c = Complex { re: 0.3, im: 0.6 };
+    let expected = crate::escape::smooth_escape_time(c, 255, 3).unwrap();
+    assert!((smooth_escape_time_generic(&Mandelbrot, c, 255, 3).unwrap() - expected).abs() < 1e-12);
+}
+
+/// critical_point test: the orbit starts where the map says.
+#[test]
+fn test_critical_point() {
+    /// z -> (z - 1)^2 + c + 1, the Mandlebrot map moved one unit right,
+    /// whose critical point is 1.
+    struct Shifted {
+        critical: bool,
+    }
+    impl IterMap for Shifted {
+        fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+            let w = z - 1.0;
+            w*w + c + 1.0
+        }
+        fn critical_point(&self, c: Complex<f64>) -> Complex<f64> {
+            if self.critical { Complex { re: 1.0, im: 0.0 } } else { Mandelbrot.critical_point(c) }
+        }
+    }
+
+    // From its critical point the orbit of 0.2 settles down, as that of
+    // the Mandlebrot set does; from the origin it escapes at once.
+    let c = Complex { re: 0.2, im: 0.0 };
+    assert_eq!(Shifted { critical: true }.z0(c), Complex { re: 1.0, im: 0.0 });
+    assert_eq!(escape_time_generic(&Shifted { critical: true }, c, 255), None);
+    assert_eq!(escape_time_generic(&Shifted { critical: false }, c, 255), Some(0));
+
+    // The Mandlebrot set starts from the origin, as ever, and Julia sets
+    // from the point itself.
+    let c = Complex { re: -0.75, im: 0.1 };
+    assert_eq!(Mandelbrot.critical_point(c), Complex { re: 0.0, im: 0.0 });
+    assert_eq!(Mandelbrot.z0(c), Complex { re: 0.0, im: 0.0 });
+    assert_eq!(escape_time_generic(&Mandelbrot, c, 255), crate::escape::escape_time(c, 255));
+    assert_eq!(Julia { c: Complex { re: 0.0, im: 0.0 } }.z0(c), c);
+}
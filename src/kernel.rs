@@ -0,0 +1,145 @@
+//! The escape-time math every render is built on, written against `core`
+//! and `num`'s `Complex` alone: no allocation, files, images or threads.
+//! Built without the default `std` feature, this is all the crate holds,
+//! for microcontrollers and other runtimes without an operating system.
+//!
+//! The rest of the crate uses these same functions, through `escape`,
+//! `fractal` and `view`, so the two builds iterate identically.
+
+use num::Complex;
+
+/// Try to Determine if c is in the Mandelbrot set, using at most
+/// limit iterations to determine if c is a member.
+///
+/// If 'c' is not a member of the set, return Some(i) where 'i' is
+/// the number of iterations it took for 'c' to leave the circle of
+/// radius two centered on the origin. If 'c' seems to be a member
+/// (more precisely, if we reached the iteration limit without being
+/// able to prove that 'c' is not a member).
+/// return None
+pub fn escape_time(c: Complex<f64>, limit: u32) -> Option<u32> {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    for i in 0..limit {
+        z = z*z + c;
+        if z.norm_sqr() > 4.0 {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
+/// The point of the plane at position 'pixel', a (column, row) pair that
+/// needn't be whole, of an image 'size' pixels wide and high whose top
+/// left corner is 'upper_left' and bottom right corner 'lower_right'.
+/// Whole positions are the top left corners of pixels.
+pub fn subpixel_to_point(size: (usize, usize),
+        pixel: (f64, f64),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>) -> Complex<f64>
+{
+    let (width, height) = (lower_right.re - upper_left.re, upper_left.im - lower_right.im);
+    Complex {
+        re: upper_left.re + pixel.0 * width / size.0 as f64,
+        // Subtraction here because pixel.1 increases as we go down, but
+        // the imaginary component increases as we go up.
+        im: upper_left.im - pixel.1 * height / size.1 as f64,
+    }
+}
+
+/// `subpixel_to_point` for the whole pixel at (column, row) 'pixel'.
+pub fn pixel_to_point(size: (usize, usize),
+        pixel: (usize, usize),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>) -> Complex<f64>
+{
+    subpixel_to_point(size, (pixel.0 as f64, pixel.1 as f64), upper_left, lower_right)
+}
+
+/// The map iterated for one escape-time fractal: the orbit of each point
+/// 'c' starts at `z0(c)` and goes on through `step(z, c)` until it leaves
+/// the circle of radius two or runs out of iterations.
+pub trait IterMap {
+    /// The next value of the orbit of 'c' after 'z'.
+    fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64>;
+
+    /// Where the orbit of 'c' starts: by default the map's
+    /// `critical_point`, whose orbit decides whether 'c' belongs to the
+    /// set. Maps over the plane of starting values rather than of
+    /// parameters, such as `fractal::Julia`, start from 'c' instead.
+    fn z0(&self, c: Complex<f64>) -> Complex<f64> {
+        self.critical_point(c)
+    }
+
+    /// The critical point of the map for parameter 'c', where its
+    /// derivative with respect to 'z' is 0. That is the origin, the
+    /// default, for z^2 + c and the other maps here; maps whose critical
+    /// point lies elsewhere, such as shifted or rational ones, say where,
+    /// so their orbits start from it.
+    fn critical_point(&self, _c: Complex<f64>) -> Complex<f64> {
+        Complex { re: 0.0, im: 0.0 }
+    }
+
+    /// The power of 'z' the map raises it to, which sets how fast orbits
+    /// grow once they escape and so how `smooth_escape_time_generic`
+    /// turns that growth into a fraction of a count.
+    fn degree(&self) -> f64 {
+        2.0
+    }
+}
+
+/// Whether an orbit that has reached 'z' has escaped: it is outside the
+/// circle of radius two, or a step overflowed and left a part of 'z'
+/// infinite or NaN. High powers can overflow in a single step from
+/// inside the circle, and NaN compares as inside it, so without the
+/// second check such an orbit would be taken for a member of the set.
+pub fn has_escaped(z: Complex<f64>) -> bool {
+    !z.re.is_finite() || !z.im.is_finite() || z.norm_sqr() > 4.0
+}
+
+/// Like `escape_time`, but iterating 'map' instead of the Mandlebrot
+/// map: Some(i) if the orbit of 'c' escapes, as `has_escaped` decides,
+/// at iteration 'i', or None if it is still inside after 'limit'
+/// iterations. For `fractal::Mandelbrot` the two give identical results.
+pub fn escape_time_generic<M: IterMap>(map: &M, c: Complex<f64>, limit: u32) -> Option<u32> {
+    let mut z = map.z0(c);
+    for i in 0..limit {
+        z = map.step(z, c);
+        if has_escaped(z) {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
+/// kernel test: escape counts and pixel positions from `core` alone.
+/// `cargo test --lib --no-default-features` runs it on a `no_std` build.
+#[test]
+fn test_kernel() {
+    assert_eq!(escape_time(Complex { re: 0.0, im: 0.0 }, 255), None);
+    assert_eq!(escape_time(Complex { re: 3.0, im: 0.0 }, 255), Some(0));
+    assert_eq!(escape_time(Complex { re: 0.5, im: 0.0 }, 255), Some(4));
+
+    /// z -> z^2 + c from 0, spelled out as a map.
+    struct Quadratic;
+    impl IterMap for Quadratic {
+        fn step(&self, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+            z*z + c
+        }
+        fn z0(&self, _c: Complex<f64>) -> Complex<f64> {
+            Complex { re: 0.0, im: 0.0 }
+        }
+    }
+    for &re in &[-2.5, -1.0, 0.26, 0.5, 1.0] {
+        let c = Complex { re, im: 0.1 };
+        assert_eq!(escape_time_generic(&Quadratic, c, 100), escape_time(c, 100));
+    }
+    assert!(has_escaped(Complex { re: f64::NAN, im: 0.0 }));
+
+    let (upper_left, lower_right) = (Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert_eq!(pixel_to_point((100, 100), (25, 75), upper_left, lower_right),
+               Complex { re: -0.5, im: -0.5 });
+    assert_eq!(subpixel_to_point((100, 100), (50.0, 50.0), upper_left, lower_right),
+               Complex { re: 0.0, im: 0.0 });
+}
@@ -0,0 +1,86 @@
+//! Rendering of the Mandlebrot set into grayscale images.
+//!
+//! The binary in `main.rs` is a thin command-line front end over these
+//! modules: `escape` holds the iteration math, `fractal` the maps of
+//! other escape-time fractals, `view` the mapping between pixels and the
+//! complex plane, `regions` a catalog of famous views, `render` fills
+//! pixel buffers, `field` keeps the raw per-pixel escape outcomes, `mask`
+//! limits which pixels get rendered, `output`/`postprocess`/`overlay`
+//! deal with the finished image, `trace` outlines the set as vector
+//! paths, and `repl` drives renders from commands typed one at a time.
+//! `animation` renders zooms frame by frame, `compare` measures how far
+//! apart two finished images are, and `classify` sorts listed points into
+//! members of the set and not. Failures along the way are reported as
+//! `error::RenderError`.
+//!
+//! Threaded rendering is behind the default `threads` feature. Without it
+//! `render_parallel` renders on the calling thread, and the crate builds
+//! for targets such as `wasm32-unknown-unknown`, where `render_rgba`
+//! gives a buffer ready for a canvas. Everything but `kernel`, the bare
+//! iteration math, is behind the default `std` feature; without that too
+//! the crate is `no_std`. `profile`, which samples renders for flame
+//! graphs, is behind the `profile` feature, off by default, and
+//! `precision`, which renders views too deep for f64, behind the default
+//! `high-precision` feature.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate num;
+#[cfg(feature = "std")]
+extern crate image;
+#[cfg(feature = "std")]
+extern crate png;
+#[cfg(feature = "std")]
+extern crate deflate;
+#[cfg(feature = "std")]
+extern crate exr;
+#[cfg(feature = "threads")]
+extern crate crossbeam;
+#[cfg(feature = "profile")]
+extern crate pprof;
+
+#[cfg(feature = "std")]
+pub mod animation;
+#[cfg(feature = "std")]
+pub mod classify;
+#[cfg(feature = "std")]
+pub mod compare;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod escape;
+#[cfg(feature = "std")]
+pub mod field;
+#[cfg(feature = "std")]
+pub mod fractal;
+pub mod kernel;
+#[cfg(feature = "std")]
+pub mod mask;
+#[cfg(feature = "std")]
+pub mod output;
+#[cfg(feature = "std")]
+pub mod overlay;
+#[cfg(feature = "std")]
+pub mod palette;
+#[cfg(feature = "std")]
+pub mod parse;
+#[cfg(feature = "std")]
+pub mod postprocess;
+#[cfg(feature = "profile")]
+pub mod profile;
+#[cfg(feature = "high-precision")]
+pub mod precision;
+#[cfg(feature = "std")]
+pub mod regions;
+#[cfg(feature = "std")]
+pub mod render;
+#[cfg(feature = "std")]
+pub mod repl;
+#[cfg(feature = "std")]
+pub mod rng;
+#[cfg(feature = "std")]
+pub mod selftest;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod view;
@@ -1,140 +1,1278 @@
-extern crate num;
 extern crate image;
-extern crate crossbeam;
+extern crate mandelbrot;
 
-use num::Complex;
-use std::str::FromStr;
 use image::ColorType;
-use image::png::PNGEncoder;
+use std::env;
+use std::ffi::{OsStr, OsString};
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, BufReader, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use mandelbrot::animation::{DEFAULT_FRAME_ZOOM, Zoom, frame_path, manifest, render_frame};
+use mandelbrot::classify::classify;
+use mandelbrot::compare::compare;
+use mandelbrot::escape::DEFAULT_SMOOTH_EXTRA;
+use mandelbrot::field::{AREA_LIMIT, AUTO_LIMIT_MAX, AUTO_LIMIT_START, AUTO_LIMIT_THRESHOLD,
+                        auto_limit, contains_interior, escape_count_report, estimate_area, heatmap,
+                        interior_fraction, potential_field, render_field, render_membership,
+                        smooth_field, tone_map_16, tone_map_bits};
+use mandelbrot::fractal::Julia;
+use mandelbrot::mask::Mask;
+use mandelbrot::error::RenderError;
+use mandelbrot::overlay::{Corner, draw_overlay, legend};
+use mandelbrot::output::{Endian, OutputFormat, PngCompression, PngFilter, PngOptions, data_uri,
+                         encode_indexed_png, encode_samples, image_bounds, is_netpbm, is_svg,
+                         pack_bits, pack_samples, png_data_uri, resolve_output_path, save_exr, save_image,
+                         save_image_with, save_indexed_png, thumbnail_path, write_atomically};
+use mandelbrot::palette::{self, DEFAULT_LUT_RESOLUTION, Interpolation, PaletteLut};
+use mandelbrot::parse::{format_complex, parse_complex, parse_pair, parse_rect, parse_rgb};
+use mandelbrot::postprocess::{EmptyView, autocrop, blur, downscale, draw_contours, emphasize_edges,
+                              normal_map, quantize, sobel, solid_color, tile, vignette};
+use mandelbrot::render::{Coloring, DEFAULT_SUPERSAMPLE_SEED, LIMIT, Pause, Quality, Region, RenderStats,
+                         Shading, Supersampling, Threading, auto_tune_threads, estimate_render_time,
+                         fit_time_budget, measure_speed, render_image, render_image_auto_contrast, render_image_map,
+                         render_image_mariani_silver, render_image_pausable,
+                         render_image_with_preview, render_verified, take_iterations};
+#[cfg(feature = "high-precision")]
+use mandelbrot::render::render_image_deep;
+#[cfg(feature = "profile")]
+use mandelbrot::profile::Profile;
+use mandelbrot::regions;
+use mandelbrot::repl;
+use mandelbrot::selftest::self_test;
+use mandelbrot::trace::{svg, trace_boundary};
+use mandelbrot::view::{ComplexView, PixelBounds, Projection, exceeds_f64_resolution, recenter,
+                      snap_to_grid};
 
+/// Seed of the random points `--area` samples, fixed so that every run
+/// reports the same estimate.
+const AREA_SEED: u64 = 0x6172_6561;
 
-/// Try to Determine if c is in the Mandelbrot set, using at most
-/// limit iterations to determine if c is a member.
-/// 
-/// If 'c' is not a member of the set, return Some(i) where 'i' is 
-/// the number of iterations it took for 'c' to leave the circle of
-/// radius two centered on the origin. If 'c' seems to be a member 
-/// (more precisely, if we reached the iteration limit without being
-/// able to prove that 'c' is not a member). 
-/// return None
-#[allow(dead_code)]
-fn escape_time(c: Complex<f64>, limit: u32) -> Option<u32> {
-    let mut z = Complex { re: 0.0, im: 0.0 };
-    for i in 0..limit {
-        z = z*z + c;
-        if z.norm_sqr() > 4.0 {
-            return Some(i);
+/// The most iterations `--iterations` may ask for unless `--clamp-iterations`
+/// raises the cap or `--allow-huge` lifts it: enough for deep zooms, but
+/// not so many that a stray digit leaves a render running for days.
+const DEFAULT_ITERATION_CAP: u32 = 5_000_000;
+
+/// Optional settings given as `--flag [value]` alongside the
+/// positional arguments.
+#[derive(Debug, PartialEq)]
+struct Options {
+    /// Use smooth coloring instead of the integer escape count.
+    smooth: bool,
+    /// Extra iterations past escape for smooth coloring.
+    smooth_extra: u32,
+    /// Shade by the Lyapunov exponent instead of escape time.
+    lyapunov: bool,
+    /// Light the set from this azimuth and elevation, in degrees.
+    light: Option<(f64, f64)>,
+    /// Shade by the exterior potential instead of escape time.
+    potential: bool,
+    /// Crop the saved image to the bounding box of the fractal.
+    autocrop: bool,
+    /// Repeat the image in a grid of this many rows and columns.
+    tile_output: Option<(usize, usize)>,
+    /// How the corners given map onto the complex plane.
+    projection: Projection,
+    /// Render the view recentered on this pixel of the one given.
+    center_on: Option<(usize, usize)>,
+    /// Move the view's center onto the nearest point of the pixel grid.
+    snap_to_grid: bool,
+    /// Render the Julia set whose constant is the point at this pixel of
+    /// the Mandlebrot view, instead of the view itself.
+    julia_from: Option<(usize, usize)>,
+    /// How much narrower the recentered view is.
+    zoom: f64,
+    /// Burn a scale bar and coordinate label into this corner.
+    overlay: Option<Corner>,
+    /// Draw contour lines every this many iterations.
+    contours: Option<u32>,
+    /// Color of the contour lines.
+    contour_color: [u8; 3],
+    /// Brighten the edges between escape counts by this much.
+    edges: Option<f64>,
+    /// Darken the corners of the image by this fraction.
+    vignette: Option<f64>,
+    /// Soften the finished image with a Gaussian blur of this radius.
+    blur: Option<f32>,
+    /// Also save a copy this many times smaller, beside FILE.
+    thumbnail: Option<usize>,
+    /// Color through this palette instead of rendering grayscale.
+    palette: Option<Vec<[u8; 3]>>,
+    /// How many times the palette cycles over the escape counts.
+    palette_repeat: u32,
+    /// Run the palette backwards.
+    palette_reverse: bool,
+    /// Start the palette this many colors along, after any reversal.
+    palette_rotate: usize,
+    /// How palette colors are blended between stops.
+    interpolation: Interpolation,
+    /// Steps per escape count in the palette lookup table.
+    lut_resolution: u32,
+    /// Make this region of the image transparent.
+    alpha: Option<Region>,
+    /// Write a normal map of the smooth escape counts instead of an image
+    /// of them.
+    normal_map: bool,
+    /// Write an indexed PNG of this many colors instead of full color.
+    indexed: Option<usize>,
+    /// Bits per pixel: 8 for the usual image, 1 for plain black and
+    /// white set membership, or 16 for finely graded escape counts.
+    bit_depth: u8,
+    /// Also write the 16-bit escape counts, headerless, to this file.
+    raw_dump: Option<PathBuf>,
+    /// Byte order of the samples in the raw dump.
+    endian: Endian,
+    /// Quantize the raw dump to this many bits per pixel, packed, instead
+    /// of writing 16-bit samples.
+    quantize_bits: Option<u8>,
+    /// How hard to compress the PNG FILE, and how to filter its rows.
+    png: PngOptions,
+    /// Also write a map of the iterations each pixel took to this file.
+    heatmap: Option<PathBuf>,
+    /// Also write a key to the coloring and the view's corners to this
+    /// file.
+    legend: Option<PathBuf>,
+    /// Also write the potential of every pixel to this OpenEXR file.
+    potential_exr: Option<PathBuf>,
+    /// Also write a report of how many pixels escaped at each count to
+    /// this file, or to stdout for `-`.
+    escape_count_histogram: Option<PathBuf>,
+    /// Escape counts per line of that report.
+    histogram_bucket: u32,
+    /// Only render the pixels this image is non-black in.
+    mask: Option<PathBuf>,
+    /// Render at the size of this image, instead of PIXELS.
+    dimensions_from: Option<PathBuf>,
+    /// Directory to write the image into when FILE is a bare filename.
+    output_dir: Option<PathBuf>,
+    /// Run the built-in self-test instead of rendering.
+    selftest: bool,
+    /// Read commands from stdin instead of rendering one image.
+    repl: bool,
+    /// Write over an output file that already exists.
+    force: bool,
+    /// Print what would be rendered, and how long it might take, instead
+    /// of rendering it.
+    dry_run: bool,
+    /// Estimate the area of the set from this many random points instead
+    /// of rendering.
+    area: Option<u64>,
+    /// Compare these two images instead of rendering.
+    compare: Option<(PathBuf, PathBuf)>,
+    /// Where to write the image of the `compare` differences.
+    diff: Option<PathBuf>,
+    /// Classify the points listed in this file instead of rendering.
+    classify: Option<PathBuf>,
+    /// Where to write the classifications, instead of stdout.
+    classify_output: Option<PathBuf>,
+    /// Supersample each pixel on an `aa`×`aa` grid.
+    aa: u32,
+    /// Columns of the supersampling grid, if not `aa`.
+    aa_x: Option<u32>,
+    /// Rows of the supersampling grid, if not `aa`.
+    aa_y: Option<u32>,
+    /// Only supersample the pixels in this rectangle, as (x, y, w, h).
+    aa_roi: Option<(usize, usize, usize, usize)>,
+    /// Seed for the supersampling jitter.
+    supersample_seed: u64,
+    /// Stop supersampling a pixel once this many samples agree.
+    aa_early_out: u32,
+    /// Iteration limit for every pixel.
+    limit: u32,
+    /// Refuse an iteration limit over this.
+    iteration_cap: u32,
+    /// Allow an iteration limit over `iteration_cap`.
+    allow_huge: bool,
+    /// Find the iteration limit by doubling it until the interior
+    /// settles, instead of using `limit`.
+    iterations_auto: bool,
+    /// Number of rendering threads.
+    threads: usize,
+    /// Time a few thread counts on a small probe and use the fastest.
+    auto_tune: bool,
+    /// Lower the resolution, iterations and supersampling as needed to
+    /// render within this long.
+    time_budget: Option<Duration>,
+    /// Rows per unit of work handed to a thread.
+    chunk_rows: Option<usize>,
+    /// Save the partly rendered image this often while rendering.
+    preview_interval: Option<Duration>,
+    /// Render twice with different threading and fail unless the two
+    /// agree.
+    verify: bool,
+    /// Pause and resume the render each time Enter is pressed.
+    pausable: bool,
+    /// Fill uniform areas by Mariani-Silver subdivision instead of
+    /// iterating every pixel.
+    mariani_silver: bool,
+    /// Stretch the coloring over the escape counts in the image instead
+    /// of over `0..limit`.
+    auto_contrast: bool,
+    /// Color the members of the set by the period of their attracting
+    /// cycle.
+    interior_period_color: bool,
+    /// Render this region of the catalog unless corners are given.
+    region: Option<ComplexView>,
+    /// Render `regions::benchmark` at `regions::BENCHMARK_LIMIT`, whatever
+    /// view and limit are given.
+    benchmark_view: bool,
+    /// Render a zoom animation of this many frames instead of one image.
+    frames: Option<u32>,
+    /// How many times narrower each frame of the animation is.
+    frame_zoom: f64,
+    /// How many times further each frame of the animation is iterated.
+    frame_iteration_growth: f64,
+    /// Renders averaged into each frame of the animation, for motion blur.
+    subframes: u32,
+    /// Write a JSON manifest of the animation's frames here once they
+    /// are all written.
+    manifest: Option<PathBuf>,
+    /// Sample the render and write a flame graph or folded stacks here.
+    profile: Option<PathBuf>,
+    /// Write the render's time, iterations and parameters as JSON here,
+    /// or to stdout if it is `-`.
+    stats_json: Option<PathBuf>,
+    /// Print the image to stdout as a base64 PNG `data:` URI instead of
+    /// writing a file.
+    stdout_base64: bool,
+    /// Report whether the view holds any of the set, and how much,
+    /// instead of writing an image.
+    probe: bool,
+    /// What to do when the image comes out a single solid color.
+    warn_empty: EmptyView,
+    /// Problems with non-critical settings that were replaced by their
+    /// defaults under `--continue-on-parse-error`.
+    warnings: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            smooth: false,
+            smooth_extra: DEFAULT_SMOOTH_EXTRA,
+            lyapunov: false,
+            potential: false,
+            light: None,
+            autocrop: false,
+            tile_output: None,
+            overlay: None,
+            contours: None,
+            contour_color: [0, 0, 0],
+            edges: None,
+            vignette: None,
+            blur: None,
+            thumbnail: None,
+            palette: None,
+            palette_repeat: 1,
+            palette_reverse: false,
+            palette_rotate: 0,
+            interpolation: Interpolation::Rgb,
+            lut_resolution: DEFAULT_LUT_RESOLUTION,
+            alpha: None,
+            normal_map: false,
+            indexed: None,
+            bit_depth: 8,
+            raw_dump: None,
+            endian: Endian::Little,
+            quantize_bits: None,
+            png: PngOptions::default(),
+            heatmap: None,
+            legend: None,
+            potential_exr: None,
+            escape_count_histogram: None,
+            histogram_bucket: 1,
+            mask: None,
+            projection: Projection::Plane,
+            center_on: None,
+            julia_from: None,
+            zoom: 1.0,
+            snap_to_grid: false,
+            dimensions_from: None,
+            output_dir: None,
+            selftest: false,
+            repl: false,
+            force: false,
+            dry_run: false,
+            area: None,
+            compare: None,
+            classify: None,
+            classify_output: None,
+            diff: None,
+            aa: 1,
+            aa_x: None,
+            aa_y: None,
+            aa_roi: None,
+            aa_early_out: 0,
+            supersample_seed: DEFAULT_SUPERSAMPLE_SEED,
+            limit: LIMIT,
+            iteration_cap: DEFAULT_ITERATION_CAP,
+            allow_huge: false,
+            threads: 8,
+            auto_tune: false,
+            time_budget: None,
+            iterations_auto: false,
+            chunk_rows: None,
+            preview_interval: None,
+            region: None,
+            benchmark_view: false,
+            frames: None,
+            frame_zoom: DEFAULT_FRAME_ZOOM,
+            frame_iteration_growth: 1.0,
+            subframes: 1,
+            manifest: None,
+            profile: None,
+            stats_json: None,
+            verify: false,
+            pausable: false,
+            mariani_silver: false,
+            auto_contrast: false,
+            interior_period_color: false,
+            stdout_base64: false,
+            probe: false,
+            warn_empty: EmptyView::Warn,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Options {
+    /// The shading these options ask `render` for.
+    fn shading(&self) -> Shading {
+        if self.lyapunov {
+            Shading::Lyapunov
+        } else if let Some((azimuth, elevation)) = self.light {
+            Shading::Lit { azimuth: azimuth.to_radians(), elevation: elevation.to_radians() }
+        } else if self.potential {
+            Shading::Potential
+        } else if self.smooth {
+            Shading::Smooth { extra: self.smooth_extra }
+        } else {
+            Shading::EscapeTime
         }
     }
 
-    None
+    /// How these options ask for the work to be split between threads.
+    fn threading(&self) -> Threading {
+        Threading { threads: self.threads, chunk_rows: self.chunk_rows }
+    }
+
+    /// The supersampling these options ask for.
+    fn sampling(&self) -> Supersampling {
+        Supersampling {
+            grid_x: self.aa_x.unwrap_or(self.aa),
+            grid_y: self.aa_y.unwrap_or(self.aa),
+            seed: self.supersample_seed,
+            agree: self.aa_early_out,
+            roi: self.aa_roi,
+        }
+    }
 }
 
-/// Parse a command-line string as a coordinate pair in multiple formats
-/// example: `"400x600"` , `"1.0,1.5"`
-/// 
-/// The string should be in the following format <left><sep><right>
-/// where <sep> is a seperator argument and left and right are both 
-/// strings that can be parsed by `T::from_str`.
-#[allow(dead_code)]
-fn parse_pair<T:FromStr>(s: &str, separator: char) -> Option<(T, T)> {
-    match s.find(separator) {
-        None => None,
-        Some(index) => {
-            match(T::from_str(&s[..index]), T::from_str(&s[index + 1..])) {
-                (Ok(i), Ok(r)) => Some((i, r)),
-                _ => None
+/// Split the command-line arguments (without the program name) into the
+/// positional arguments and the `Options` given as flags.
+///
+/// Anything starting with `--` is a flag; everything else, including
+/// negative coordinates like `-1.20,0.35`, is positional. Arguments
+/// needn't be valid UTF-8: positional ones are passed on as given, for
+/// FILE to be used as a path, and so are the values of flags that name
+/// files.
+///
+/// With `--continue-on-parse-error` anywhere among the arguments, a bad
+/// value for a non-critical setting (`--iterations`, `--threads` or
+/// `--palette`) doesn't fail: the setting keeps its default, and the
+/// problem is noted in `Options::warnings` instead.
+fn parse_args<S: AsRef<OsStr>>(args: &[S]) -> Result<(Vec<&OsStr>, Options), String> {
+    let mut positional = Vec::new();
+    let mut options = Options::default();
+    let lenient = args.iter().any(|arg| arg.as_ref() == "--continue-on-parse-error");
+    let mut args = args.iter();
+
+    while let Some(raw) = args.next() {
+        let arg = match raw.as_ref().to_str() {
+            Some(arg) => arg,
+            None => {
+                positional.push(raw.as_ref());
+                continue;
+            }
+        };
+        match arg {
+            "--smooth" => options.smooth = true,
+            "--smooth-extra" => options.smooth_extra = flag_value(&mut args, arg)?,
+            "--lyapunov" => options.lyapunov = true,
+            "--potential" => options.potential = true,
+            "--light" => {
+                let value: String = flag_value(&mut args, arg)?;
+                options.light = Some(parse_pair(&value, ',')
+                    .ok_or_else(|| format!("invalid value `{}` for `--light`, expected AZ,EL", value))?);
+            }
+            "--projection" => {
+                let name: String = flag_value(&mut args, arg)?;
+                options.projection = match name.as_str() {
+                    "plane" => Projection::Plane,
+                    "stereographic" => Projection::Stereographic,
+                    _ => return Err(format!("invalid value `{}` for `--projection`, expected \
+                                             `plane` or `stereographic`", name)),
+                };
+            }
+            "--center-on" => {
+                let value: String = flag_value(&mut args, arg)?;
+                options.center_on = Some(parse_pair(&value, ',')
+                    .ok_or_else(|| format!("invalid value `{}` for `--center-on`, expected COL,ROW", value))?);
+            }
+            "--seed-julia-from-mandelbrot" => {
+                let value: String = flag_value(&mut args, arg)?;
+                options.julia_from = Some(parse_pair(&value, ',').ok_or_else(|| {
+                    format!("invalid value `{}` for `--seed-julia-from-mandelbrot`, expected COL,ROW",
+                            value)
+                })?);
+            }
+            "--zoom" => {
+                options.zoom = flag_value(&mut args, arg)?;
+                if !options.zoom.is_finite() || options.zoom <= 0.0 {
+                    return Err("`--zoom` must be a positive factor".to_string());
+                }
+            }
+            "--snap-to-grid" => options.snap_to_grid = true,
+            "--autocrop" => options.autocrop = true,
+            "--tile-output" => {
+                let value: String = flag_value(&mut args, arg)?;
+                options.tile_output = match parse_pair(&value, 'x') {
+                    Some((rows, columns)) if rows > 0 && columns > 0 => Some((rows, columns)),
+                    _ => return Err(format!("invalid value `{}` for `--tile-output`, expected RxC \
+                                             with R and C at least 1", value)),
+                };
+            }
+            "--overlay" => options.overlay = Some(options.overlay.unwrap_or(Corner::BottomLeft)),
+            "--overlay-corner" => {
+                let name: String = flag_value(&mut args, arg)?;
+                let corner = Corner::from_name(&name)
+                    .ok_or_else(|| format!("invalid value `{}` for `--overlay-corner`, expected \
+                                            top-left, top-right, bottom-left or bottom-right", name))?;
+                options.overlay = Some(corner);
+            }
+            "--contours" => {
+                let interval = flag_value(&mut args, arg)?;
+                if interval == 0 {
+                    return Err("`--contours` must be at least 1".to_string());
+                }
+                options.contours = Some(interval);
+            }
+            "--contour-color" => {
+                let value: String = flag_value(&mut args, arg)?;
+                options.contour_color = parse_rgb(&value)
+                    .ok_or_else(|| format!("invalid value `{}` for `--contour-color`, expected R,G,B", value))?;
+            }
+            "--edges" => {
+                let strength: f64 = flag_value(&mut args, arg)?;
+                if !strength.is_finite() || strength < 0.0 {
+                    return Err("`--edges` must be a finite strength of at least 0".to_string());
+                }
+                options.edges = Some(strength);
+            }
+            "--vignette" => {
+                let strength: f64 = flag_value(&mut args, arg)?;
+                if !strength.is_finite() || strength < 0.0 {
+                    return Err("`--vignette` must be a finite strength of at least 0".to_string());
+                }
+                options.vignette = Some(strength);
+            }
+            "--blur" => {
+                let radius: f32 = flag_value(&mut args, arg)?;
+                if !radius.is_finite() || radius < 0.0 {
+                    return Err("`--blur` must be a finite radius of at least 0".to_string());
+                }
+                options.blur = Some(radius);
+            }
+            "--thumbnail" => {
+                let divisor: usize = flag_value(&mut args, arg)?;
+                if divisor < 2 || !divisor.is_power_of_two() {
+                    return Err("`--thumbnail` must be a power of two of at least 2".to_string());
+                }
+                options.thumbnail = Some(divisor);
+            }
+            "--palette" => {
+                let colors = flag_value(&mut args, arg).and_then(|name: String| {
+                    palette::named(&name)
+                        .ok_or_else(|| format!("unknown palette `{}`, expected one of: {}",
+                                               name, palette::NAMES.join(", ")))
+                });
+                if let Some(colors) = non_critical(colors, lenient, &mut options.warnings)? {
+                    options.palette = Some(colors);
+                }
+            }
+            "--region" => {
+                let name: String = flag_value(&mut args, arg)?;
+                let view = regions::named(&name)
+                    .ok_or_else(|| format!("unknown region `{}`, expected one of: {}",
+                                           name, regions::names().join(", ")))?;
+                options.region = Some(view);
+            }
+            "--benchmark-view" => options.benchmark_view = true,
+            "--interp" => {
+                let name: String = flag_value(&mut args, arg)?;
+                options.interpolation = Interpolation::from_name(&name)
+                    .ok_or_else(|| format!("invalid value `{}` for `--interp`, expected `lab` or `rgb`",
+                                           name))?;
+            }
+            "--palette-repeat" => options.palette_repeat = flag_value(&mut args, arg)?,
+            "--palette-reverse" => options.palette_reverse = true,
+            "--palette-rotate" => options.palette_rotate = flag_value(&mut args, arg)?,
+            "--lut-resolution" => {
+                options.lut_resolution = flag_value(&mut args, arg)?;
+                if options.lut_resolution == 0 {
+                    return Err("`--lut-resolution` must be at least 1".to_string());
+                }
+            }
+            "--alpha" => {
+                let region: String = flag_value(&mut args, arg)?;
+                options.alpha = Some(match region.as_str() {
+                    "interior" => Region::Interior,
+                    "exterior" => Region::Exterior,
+                    _ => return Err(format!("invalid value `{}` for `--alpha`, expected \
+                                             `interior` or `exterior`", region)),
+                });
             }
+            "--heatmap" => options.heatmap = Some(path_value(&mut args, arg)?),
+            "--legend" => options.legend = Some(path_value(&mut args, arg)?),
+            "--potential-exr" => options.potential_exr = Some(path_value(&mut args, arg)?),
+            "--escape-count-histogram" =>
+                options.escape_count_histogram = Some(path_value(&mut args, arg)?),
+            "--histogram-bucket" => {
+                options.histogram_bucket = flag_value(&mut args, arg)?;
+                if options.histogram_bucket == 0 {
+                    return Err("`--histogram-bucket` must be at least 1".to_string());
+                }
+            }
+            "--mask" => options.mask = Some(path_value(&mut args, arg)?),
+            "--output-dimensions-from-file" =>
+                options.dimensions_from = Some(path_value(&mut args, arg)?),
+            "--output-dir" => options.output_dir = Some(path_value(&mut args, arg)?),
+            "--selftest" => options.selftest = true,
+            "--repl" => options.repl = true,
+            "--dry-run" => options.dry_run = true,
+            "--force" => options.force = true,
+            "--area" => {
+                let samples = flag_value(&mut args, arg)?;
+                if samples == 0 {
+                    return Err("`--area` must be at least 1 sample".to_string());
+                }
+                options.area = Some(samples);
+            }
+            "--compare" => {
+                let a = path_value(&mut args, arg)?;
+                options.compare = Some((a, path_value(&mut args, arg)?));
+            }
+            "--diff" => options.diff = Some(path_value(&mut args, arg)?),
+            "--classify" => options.classify = Some(path_value(&mut args, arg)?),
+            "--classify-output" => options.classify_output = Some(path_value(&mut args, arg)?),
+            "--stdout-base64" => options.stdout_base64 = true,
+            "--probe" => options.probe = true,
+            "--warn-empty" => {
+                let policy: String = flag_value(&mut args, arg)?;
+                options.warn_empty = match policy.as_str() {
+                    "off" => EmptyView::Ignore,
+                    "on" => EmptyView::Warn,
+                    "solid" => EmptyView::Solid,
+                    _ => return Err(format!("invalid value `{}` for `--warn-empty`, expected `off`, \
+                                             `on` or `solid`", policy)),
+                };
+            }
+            // Handled by `Painter::new`, which has to know before parsing.
+            "--no-color" => {}
+            "--normal-map" => options.normal_map = true,
+            "--indexed" => {
+                let colors = flag_value(&mut args, arg)?;
+                if !(2 ..= 256).contains(&colors) {
+                    return Err("`--indexed` must be from 2 to 256 colors".to_string());
+                }
+                options.indexed = Some(colors);
+            }
+            "--bit-depth" => {
+                options.bit_depth = flag_value(&mut args, arg)?;
+                if ![1, 8, 16].contains(&options.bit_depth) {
+                    return Err("`--bit-depth` must be 1, 8 or 16".to_string());
+                }
+            }
+            "--raw-dump" => options.raw_dump = Some(path_value(&mut args, arg)?),
+            "--quantize-bits" => {
+                let bits: u8 = flag_value(&mut args, arg)?;
+                if !(1..=8).contains(&bits) {
+                    return Err("`--quantize-bits` must be from 1 to 8".to_string());
+                }
+                options.quantize_bits = Some(bits);
+            }
+            "--endian" => {
+                let name: String = flag_value(&mut args, arg)?;
+                options.endian = match name.as_str() {
+                    "le" => Endian::Little,
+                    "be" => Endian::Big,
+                    _ => return Err(format!("invalid value `{}` for `--endian`, expected `le` or `be`",
+                                            name)),
+                };
+            }
+            "--png-compression" => {
+                let name: String = flag_value(&mut args, arg)?;
+                options.png.compression = match name.as_str() {
+                    "fast" => PngCompression::Fast,
+                    "default" => PngCompression::Default,
+                    "best" => PngCompression::Best,
+                    _ => return Err(format!("invalid value `{}` for `--png-compression`, expected \
+                                             `fast`, `default` or `best`", name)),
+                };
+            }
+            "--png-filter" => {
+                let name: String = flag_value(&mut args, arg)?;
+                options.png.filter = match name.as_str() {
+                    "none" => PngFilter::None,
+                    "sub" => PngFilter::Sub,
+                    "up" => PngFilter::Up,
+                    "average" => PngFilter::Average,
+                    "paeth" => PngFilter::Paeth,
+                    "adaptive" => PngFilter::Adaptive,
+                    _ => return Err(format!("invalid value `{}` for `--png-filter`, expected `none`, \
+                                             `sub`, `up`, `average`, `paeth` or `adaptive`", name)),
+                };
+            }
+            "--aa" => options.aa = flag_value(&mut args, arg)?,
+            "--aa-x" => options.aa_x = Some(flag_value(&mut args, arg)?),
+            "--aa-y" => options.aa_y = Some(flag_value(&mut args, arg)?),
+            "--aa-roi" => {
+                let value: String = flag_value(&mut args, arg)?;
+                options.aa_roi = Some(parse_rect(&value).ok_or_else(|| {
+                    format!("invalid value `{}` for `--aa-roi`, expected X,Y,W,H", value)
+                })?);
+                options.aa = flag_value(&mut args, arg)?;
+            }
+            "--aa-early-out" => {
+                options.aa_early_out = flag_value(&mut args, arg)?;
+                if options.aa_early_out < 2 {
+                    return Err("`--aa-early-out` needs at least 2 samples to agree".to_string());
+                }
+            }
+            "--supersample-seed" => options.supersample_seed = flag_value(&mut args, arg)?,
+            "--iterations" => {
+                let limit = flag_value(&mut args, arg).and_then(|limit| match limit {
+                    0 => Err("`--iterations` must be at least 1".to_string()),
+                    limit => Ok(limit),
+                });
+                if let Some(limit) = non_critical(limit, lenient, &mut options.warnings)? {
+                    options.limit = limit;
+                }
+            }
+            "--threads" => {
+                let threads = flag_value(&mut args, arg).and_then(|threads| match threads {
+                    0 => Err("`--threads` must be at least 1".to_string()),
+                    threads => Ok(threads),
+                });
+                if let Some(threads) = non_critical(threads, lenient, &mut options.warnings)? {
+                    options.threads = threads;
+                }
+            }
+            "--preview-interval" => {
+                let seconds: f64 = flag_value(&mut args, arg)?;
+                if !seconds.is_finite() || seconds <= 0.0 {
+                    return Err("`--preview-interval` must be a positive number of seconds".to_string());
+                }
+                options.preview_interval = Some(Duration::from_secs_f64(seconds));
+            }
+            "--verify" => options.verify = true,
+            "--pausable" => options.pausable = true,
+            "--mariani-silver" => options.mariani_silver = true,
+            "--auto-contrast" => options.auto_contrast = true,
+            "--interior-period-color" => options.interior_period_color = true,
+            "--frames" => {
+                let frames = flag_value(&mut args, arg)?;
+                if frames == 0 {
+                    return Err("`--frames` must be at least 1".to_string());
+                }
+                options.frames = Some(frames);
+            }
+            "--frame-zoom" => {
+                options.frame_zoom = flag_value(&mut args, arg)?;
+                if !options.frame_zoom.is_finite() || options.frame_zoom <= 0.0 {
+                    return Err("`--frame-zoom` must be a positive number".to_string());
+                }
+            }
+            "--frame-iteration-growth" => {
+                let growth: f64 = flag_value(&mut args, arg)?;
+                options.frame_iteration_growth = growth;
+                if !growth.is_finite() || growth <= 0.0 {
+                    return Err("`--frame-iteration-growth` must be a positive number".to_string());
+                }
+            }
+            "--manifest" => options.manifest = Some(path_value(&mut args, arg)?),
+            "--profile" => options.profile = Some(path_value(&mut args, arg)?),
+            "--stats-json" => options.stats_json = Some(path_value(&mut args, arg)?),
+            "--subframes" => {
+                options.subframes = flag_value(&mut args, arg)?;
+                if options.subframes == 0 {
+                    return Err("`--subframes` must be at least 1".to_string());
+                }
+            }
+            "--continue-on-parse-error" => {}
+            "--auto-tune" => options.auto_tune = true,
+            "--time-budget" => {
+                let seconds: f64 = flag_value(&mut args, arg)?;
+                if !seconds.is_finite() || seconds <= 0.0 {
+                    return Err("`--time-budget` must be a positive number of seconds".to_string());
+                }
+                options.time_budget = Some(Duration::from_secs_f64(seconds));
+            }
+            "--iterations-auto" => options.iterations_auto = true,
+            "--clamp-iterations" => {
+                options.iteration_cap = flag_value(&mut args, arg)?;
+                if options.iteration_cap == 0 {
+                    return Err("`--clamp-iterations` must be at least 1".to_string());
+                }
+            }
+            "--allow-huge" => options.allow_huge = true,
+            "--chunk-rows" => {
+                let rows = flag_value(&mut args, arg)?;
+                if rows == 0 {
+                    return Err("`--chunk-rows` must be at least 1".to_string());
+                }
+                options.chunk_rows = Some(rows);
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{}`", flag)),
+            _ => positional.push(raw.as_ref()),
+        }
+    }
+
+    // The benchmark wins over any view or limit, wherever they come.
+    if options.benchmark_view {
+        options.region = Some(regions::benchmark());
+        options.limit = regions::BENCHMARK_LIMIT;
+    }
+    // Checked once every flag is in, as the cap may come after the limit.
+    if options.limit > options.iteration_cap && !options.allow_huge {
+        return Err(format!("`--iterations {}` is over the cap of {}; raise it with \
+                            `--clamp-iterations N` or pass `--allow-huge`",
+                           options.limit, options.iteration_cap));
+    }
+    // Frames iterate further as the limit grows; the view doesn't matter.
+    if let Some(frames) = options.frames {
+        let zoom = Zoom {
+            start: regions::benchmark(),
+            factor: options.frame_zoom,
+            limit_growth: options.frame_iteration_growth,
+        };
+        let last = zoom.limit_at(frames - 1, options.limit);
+        if last > options.iteration_cap && !options.allow_huge {
+            return Err(format!("`--frame-iteration-growth {}` takes the last frame to {} \
+                                iterations, over the cap of {}; raise it with \
+                                `--clamp-iterations N` or pass `--allow-huge`",
+                               options.frame_iteration_growth, last, options.iteration_cap));
         }
     }
+    // Likewise the palette modifiers may come before the palette.
+    if let Some(colors) = options.palette.take() {
+        let colors = if options.palette_reverse { palette::reverse(colors) } else { colors };
+        options.palette = Some(palette::rotate(colors, options.palette_rotate));
+    }
+
+    Ok((positional, options))
 }
 
-/// Parse a pair of floating-point numbers seperated by a comma as a complex number
-#[allow(dead_code)]
-fn parse_complex(s: &str) -> Option<Complex<f64>> {
-    match parse_pair(s, ',') {
-        Some((re, im)) => Some(Complex { re, im}),
-        None => None
+/// The parsed value of a non-critical setting, or None if it couldn't be
+/// parsed but 'lenient' says to carry on, in which case the problem is
+/// added to 'warnings'.
+fn non_critical<T>(result: Result<T, String>, lenient: bool, warnings: &mut Vec<String>)
+    -> Result<Option<T>, String>
+{
+    match result {
+        Err(message) if lenient => {
+            warnings.push(format!("{}; using the default", message));
+            Ok(None)
+        }
+        result => result.map(Some),
     }
 }
 
-#[allow(dead_code)]
-fn pixel_to_point(bounds:(usize, usize),
-            pixel: (usize, usize),
-            upper_left: Complex<f64>,
-            lower_right: Complex<f64>) -> Complex<f64> {
-            let (width, height) = (lower_right.re - upper_left.re, 
-                                upper_left.im - lower_right.im);
+/// Take the value following `flag` and parse it with `T::from_str`.
+fn flag_value<'a, T, S, I>(args: &mut I, flag: &str) -> Result<T, String>
+    where T: FromStr, S: AsRef<OsStr> + 'a, I: Iterator<Item = &'a S>
+{
+    let value = args.next()
+        .ok_or_else(|| format!("missing value for `{}`", flag))?
+        .as_ref();
+    value.to_str().and_then(|text| T::from_str(text).ok())
+        .ok_or_else(|| format!("invalid value `{}` for `{}`", value.to_string_lossy(), flag))
+}
 
-            Complex {
-                re: upper_left.re +pixel.0 as f64 * width / bounds.0 as f64,
-                im: upper_left.im - pixel.1 as f64 * height / bounds.1 as f64
-                //subtraction here because pixel.1 increases as we go down,
-                // but the imaginary component increases as we go up.
-            }
+/// Like `flag_value`, for a flag naming a file: the value is taken as a
+/// path as it is, whether or not it is valid UTF-8.
+fn path_value<'a, S, I>(args: &mut I, flag: &str) -> Result<PathBuf, String>
+    where S: AsRef<OsStr> + 'a, I: Iterator<Item = &'a S>
+{
+    args.next()
+        .map(|value| PathBuf::from(value.as_ref()))
+        .ok_or_else(|| format!("missing value for `{}`", flag))
 }
 
+/// parse_args test
+#[test]
+fn test_parse_args() {
+    let args: Vec<String> = ["out.png", "--smooth", "100x100", "-1,1", "--smooth-extra", "2", "1,-1"]
+        .iter().map(|s| s.to_string()).collect();
+    let (positional, options) = parse_args(&args).unwrap();
+    assert_eq!(positional, vec!["out.png", "100x100", "-1,1", "1,-1"]);
+    assert_eq!(options.shading(), Shading::Smooth { extra: 2 });
 
-#[allow(dead_code)]
-fn render(pixels: &mut [u8],
-        bounds:(usize, usize),
-        upper_left: Complex<f64>,
-        lower_right: Complex<f64>)
-{
-    assert!(pixels.len() == bounds.0 * bounds.1);
+    let (_, options) = parse_args::<String>(&[]).unwrap();
+    assert_eq!(options.shading(), Shading::EscapeTime);
+    let (_, options) = parse_args(&["--lyapunov".to_string()]).unwrap();
+    assert_eq!(options.shading(), Shading::Lyapunov);
+    let (_, options) = parse_args(&["--light".to_string(), "180,90".to_string()]).unwrap();
+    assert_eq!(options.shading(), Shading::Lit { azimuth: std::f64::consts::PI,
+                                                 elevation: std::f64::consts::FRAC_PI_2 });
+    assert!(parse_args(&["--light".to_string(), "45".to_string()]).is_err());
+    let (_, options) = parse_args(&["--potential".to_string(), "--smooth".to_string()]).unwrap();
+    assert_eq!(options.shading(), Shading::Potential);
 
-    for row in 0 .. bounds.1 {
-        for column in 0 .. bounds.0 {
-            let point = pixel_to_point(bounds, (column, row),
-                            upper_left, lower_right);
+    assert!(parse_args(&["--smooth-extra".to_string()]).is_err());
+    assert!(parse_args(&["--smooth-extra".to_string(), "x".to_string()]).is_err());
+    assert!(parse_args(&["--bogus".to_string()]).is_err());
 
-            pixels[row * bounds.0 + column] = match escape_time(point, 255) { 
-                None => 0,
-                Some(count) => 255 - count as u8
-            };
+    // FILE and the paths of flags come through whatever bytes they hold.
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let name = OsStr::from_bytes(b"caf\xe9 render.png");
+        let args = [name, OsStr::new("--heatmap"), name, OsStr::new("10x10")];
+        let (positional, options) = parse_args(&args).unwrap();
+        assert_eq!(positional, vec![name, OsStr::new("10x10")]);
+        assert_eq!(options.heatmap.as_deref(), Some(Path::new(name)));
+        assert!(parse_args(&[OsStr::new("--iterations"), name]).is_err());
+    }
+
+    let (_, options) = parse_args(&["--tile-output".to_string(), "2x3".to_string()]).unwrap();
+    assert_eq!(options.tile_output, Some((2, 3)));
+    assert!(parse_args(&["--tile-output".to_string(), "0x3".to_string()]).is_err());
+    assert!(parse_args(&["--tile-output".to_string(), "2".to_string()]).is_err());
+
+    let args: Vec<String> = ["--palette", "fire", "--palette-repeat", "3"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!(options.palette, palette::named("fire"));
+    assert_eq!(options.interpolation, Interpolation::Rgb);
+    assert_eq!(options.palette_repeat, 3);
+    assert!(parse_args(&["--palette".to_string(), "plaid".to_string()]).is_err());
+    let args: Vec<String> = ["--palette-rotate", "1", "--palette-reverse", "--palette", "fire"]
+        .iter().map(|s| s.to_string()).collect();
+    let fire = palette::named("fire").unwrap();
+    assert_eq!(parse_args(&args).unwrap().1.palette,
+               Some(vec![fire[3], fire[2], fire[1], fire[0], fire[4]]));
+    let (_, options) = parse_args(&["--interp".to_string(), "lab".to_string()]).unwrap();
+    assert_eq!(options.interpolation, Interpolation::Lab);
+    assert!(parse_args(&["--interp".to_string(), "hsl".to_string()]).is_err());
+
+    let (_, options) = parse_args(&["--alpha".to_string(), "exterior".to_string()]).unwrap();
+    assert_eq!(options.alpha, Some(Region::Exterior));
+    assert_eq!(Options::default().alpha, None);
+    assert!(parse_args(&["--alpha".to_string(), "outside".to_string()]).is_err());
+
+    assert_eq!(parse_args(&["--bit-depth".to_string(), "1".to_string()]).unwrap().1.bit_depth, 1);
+    assert_eq!(Options::default().bit_depth, 8);
+    assert!(parse_args(&["--normal-map".to_string()]).unwrap().1.normal_map);
+    assert!(parse_args(&["--bit-depth".to_string(), "4".to_string()]).is_err());
+    assert_eq!(parse_args(&["--bit-depth".to_string(), "16".to_string()]).unwrap().1.bit_depth, 16);
+    let args: Vec<String> = ["--raw-dump", "counts.raw", "--endian", "be"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!(options.raw_dump, Some(PathBuf::from("counts.raw")));
+    assert_eq!(options.endian, Endian::Big);
+    assert_eq!(Options::default().endian, Endian::Little);
+    assert!(parse_args(&["--endian".to_string(), "middle".to_string()]).is_err());
+    let args: Vec<String> = ["--raw-dump", "texture.raw", "--quantize-bits", "4"]
+        .iter().map(|s| s.to_string()).collect();
+    assert_eq!(parse_args(&args).unwrap().1.quantize_bits, Some(4));
+    assert_eq!(Options::default().quantize_bits, None);
+    assert!(parse_args(&["--quantize-bits".to_string(), "0".to_string()]).is_err());
+    assert!(parse_args(&["--quantize-bits".to_string(), "16".to_string()]).is_err());
+
+    let args: Vec<String> = ["--warn-empty", "solid"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(parse_args(&args).unwrap().1.warn_empty, EmptyView::Solid);
+    assert_eq!(Options::default().warn_empty, EmptyView::Warn);
+    assert!(parse_args(&["--warn-empty".to_string(), "loud".to_string()]).is_err());
+
+    let args: Vec<String> = ["--png-compression", "best", "--png-filter", "adaptive"]
+        .iter().map(|s| s.to_string()).collect();
+    assert_eq!(parse_args(&args).unwrap().1.png,
+               PngOptions { compression: PngCompression::Best, filter: PngFilter::Adaptive });
+    assert_eq!(Options::default().png, PngOptions::default());
+    assert!(parse_args(&["--png-filter".to_string(), "median".to_string()]).is_err());
+    assert!(parse_args(&["--png-compression".to_string(), "9".to_string()]).is_err());
+    assert_eq!(parse_args(&["--indexed".to_string(), "16".to_string()]).unwrap().1.indexed, Some(16));
+    assert!(parse_args(&["--indexed".to_string(), "1".to_string()]).is_err());
+    assert!(parse_args(&["--indexed".to_string(), "257".to_string()]).is_err());
+
+    assert_eq!(parse_args(&["--overlay".to_string()]).unwrap().1.overlay, Some(Corner::BottomLeft));
+    let args: Vec<String> = ["--overlay-corner", "top-right", "--overlay"]
+        .iter().map(|s| s.to_string()).collect();
+    assert_eq!(parse_args(&args).unwrap().1.overlay, Some(Corner::TopRight));
+    assert!(parse_args(&["--overlay-corner".to_string(), "middle".to_string()]).is_err());
+
+    let (_, options) = parse_args(&["--mask".to_string(), "mask.png".to_string()]).unwrap();
+    assert_eq!(options.mask, Some(PathBuf::from("mask.png")));
+    assert_eq!(parse_args(&["--area".to_string(), "100000".to_string()]).unwrap().1.area, Some(100_000));
+    assert!(parse_args(&["--area".to_string(), "0".to_string()]).is_err());
+    let (_, options) = parse_args(&["--heatmap".to_string(), "heat.png".to_string()]).unwrap();
+    assert_eq!(options.heatmap, Some(PathBuf::from("heat.png")));
+    let (_, options) = parse_args(&["--legend".to_string(), "key.png".to_string()]).unwrap();
+    assert_eq!(options.legend, Some(PathBuf::from("key.png")));
+    let (_, options) = parse_args(&["--potential-exr".to_string(), "g.exr".to_string()]).unwrap();
+    assert_eq!(options.potential_exr, Some(PathBuf::from("g.exr")));
+    let args: Vec<String> = ["--escape-count-histogram", "-", "--histogram-bucket", "10"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!(options.escape_count_histogram, Some(PathBuf::from("-")));
+    assert_eq!(options.histogram_bucket, 10);
+    assert_eq!(Options::default().histogram_bucket, 1);
+    assert!(parse_args(&["--histogram-bucket".to_string(), "0".to_string()]).is_err());
+    let args: Vec<String> = ["--compare", "a.png", "b.png", "--diff", "diff.png"]
+        .iter().map(|s| s.to_string()).collect();
+    let (positional, options) = parse_args(&args).unwrap();
+    assert_eq!(options.compare, Some((PathBuf::from("a.png"), PathBuf::from("b.png"))));
+    assert_eq!(options.diff, Some(PathBuf::from("diff.png")));
+    assert!(positional.is_empty());
+    assert!(parse_args(&["--compare".to_string(), "a.png".to_string()]).is_err());
+    let args: Vec<String> = ["--classify", "points.txt", "--classify-output", "labels.csv"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!(options.classify, Some(PathBuf::from("points.txt")));
+    assert_eq!(options.classify_output, Some(PathBuf::from("labels.csv")));
+    assert!(parse_args(&["--force".to_string()]).unwrap().1.force);
+    assert!(!Options::default().force);
+    let args: Vec<String> = ["out.png", "--output-dimensions-from-file", "ref.png", "0,0", "1,1"]
+        .iter().map(|s| s.to_string()).collect();
+    let (positional, options) = parse_args(&args).unwrap();
+    assert_eq!(options.dimensions_from, Some(PathBuf::from("ref.png")));
+    assert_eq!(positional, vec!["out.png", "0,0", "1,1"]);
+    let args = ["--projection".to_string(), "stereographic".to_string()];
+    assert_eq!(parse_args(&args).unwrap().1.projection, Projection::Stereographic);
+    assert_eq!(Options::default().projection, Projection::Plane);
+    assert!(parse_args(&["--projection".to_string(), "mercator".to_string()]).is_err());
+    let (_, options) = parse_args(&["--region".to_string(), "seahorse-valley".to_string()]).unwrap();
+    assert_eq!(options.region, regions::named("seahorse-valley"));
+    let args: Vec<String> = ["--iterations", "100", "--benchmark-view", "--region", "whole-set"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert!(options.benchmark_view);
+    assert_eq!(options.region, Some(regions::benchmark()));
+    assert_eq!(options.limit, regions::BENCHMARK_LIMIT);
+    assert!(parse_args(&["--region".to_string(), "atlantis".to_string()]).unwrap_err()
+        .contains("seahorse-valley"));
+    let args: Vec<String> = ["--center-on", "40,30", "--zoom", "2"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!((options.center_on, options.zoom), (Some((40, 30)), 2.0));
+    assert!(parse_args(&["--snap-to-grid".to_string()]).unwrap().1.snap_to_grid);
+    assert_eq!(Options::default().zoom, 1.0);
+    assert!(parse_args(&["--center-on".to_string(), "40".to_string()]).is_err());
+    let args: Vec<String> = ["--seed-julia-from-mandelbrot", "120,45"]
+        .iter().map(|s| s.to_string()).collect();
+    assert_eq!(parse_args(&args).unwrap().1.julia_from, Some((120, 45)));
+    assert!(parse_args(&["--seed-julia-from-mandelbrot".to_string(), "-1,2".to_string()]).is_err());
+    assert!(parse_args(&["--zoom".to_string(), "0".to_string()]).is_err());
+    let (_, options) = parse_args(&["--lut-resolution".to_string(), "4".to_string()]).unwrap();
+    assert_eq!(options.lut_resolution, 4);
+    assert!(parse_args(&["--lut-resolution".to_string(), "0".to_string()]).is_err());
+
+    let args: Vec<String> = ["--contours", "10", "--contour-color", "255,0,0"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!((options.contours, options.contour_color), (Some(10), [255, 0, 0]));
+    assert_eq!(Options::default().contours, None);
+    assert!(parse_args(&["--contours".to_string(), "0".to_string()]).is_err());
+    assert!(parse_args(&["--contour-color".to_string(), "red".to_string()]).is_err());
+    assert_eq!(parse_args(&["--edges".to_string(), "0.5".to_string()]).unwrap().1.edges, Some(0.5));
+    assert!(parse_args(&["--edges".to_string(), "-1".to_string()]).is_err());
+    assert!(parse_args(&["--edges".to_string(), "NaN".to_string()]).is_err());
+    assert_eq!(parse_args(&["--vignette".to_string(), "0.4".to_string()]).unwrap().1.vignette,
+               Some(0.4));
+    assert!(parse_args(&["--vignette".to_string(), "-0.1".to_string()]).is_err());
+    assert_eq!(parse_args(&["--blur".to_string(), "1.5".to_string()]).unwrap().1.blur, Some(1.5));
+    assert!(parse_args(&["--blur".to_string(), "-1".to_string()]).is_err());
+    assert_eq!(parse_args(&["--thumbnail".to_string(), "4".to_string()]).unwrap().1.thumbnail,
+               Some(4));
+    assert!(parse_args(&["--thumbnail".to_string(), "3".to_string()]).is_err());
+    assert!(parse_args(&["--thumbnail".to_string(), "1".to_string()]).is_err());
+
+    let args: Vec<String> = ["--aa", "4", "--supersample-seed", "99"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!(options.sampling(), Supersampling::square(4, 99));
+    let args: Vec<String> = ["--aa", "3", "--aa-x", "2", "--aa-y", "1"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!(options.sampling(), Supersampling { grid_x: 2, grid_y: 1, ..Supersampling::none() });
+    let (_, options) = parse_args(&["--aa-y".to_string(), "4".to_string()]).unwrap();
+    assert_eq!(options.sampling().samples(), 4);
+    let (_, options) = parse_args(&["--aa-early-out".to_string(), "3".to_string()]).unwrap();
+    assert_eq!(options.sampling().agree, 3);
+    assert!(parse_args(&["--aa-early-out".to_string(), "1".to_string()]).is_err());
+    let args: Vec<String> = ["--aa-roi", "10,20,30,40", "4"].iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!(options.sampling(),
+               Supersampling { roi: Some((10, 20, 30, 40)),
+                              ..Supersampling::square(4, DEFAULT_SUPERSAMPLE_SEED) });
+    assert!(parse_args(&["--aa-roi".to_string(), "10,20,30,40".to_string()]).is_err());
+    assert!(parse_args(&["--aa-roi".to_string(), "10,20".to_string(), "4".to_string()]).is_err());
+    assert_eq!(Options::default().sampling(), Supersampling::none());
+
+    let args: Vec<String> = ["--threads", "3", "--chunk-rows", "16"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!(options.threading(), Threading { threads: 3, chunk_rows: Some(16) });
+    assert!(parse_args(&["--threads".to_string(), "0".to_string()]).is_err());
+    assert!(parse_args(&["--auto-tune".to_string()]).unwrap().1.auto_tune);
+    assert!(parse_args(&["--iterations-auto".to_string()]).unwrap().1.iterations_auto);
+    assert!(parse_args(&["--chunk-rows".to_string(), "0".to_string()]).is_err());
+    assert!(parse_args(&["--verify".to_string()]).unwrap().1.verify);
+    assert!(parse_args(&["--pausable".to_string()]).unwrap().1.pausable);
+    assert!(parse_args(&["--mariani-silver".to_string()]).unwrap().1.mariani_silver);
+    assert!(parse_args(&["--auto-contrast".to_string()]).unwrap().1.auto_contrast);
+    assert!(parse_args(&["--interior-period-color".to_string()]).unwrap().1.interior_period_color);
+    let (_, options) = parse_args(&["--preview-interval".to_string(), "2.5".to_string()]).unwrap();
+    assert_eq!(options.preview_interval, Some(Duration::from_millis(2500)));
+    assert!(parse_args(&["--preview-interval".to_string(), "0".to_string()]).is_err());
+    let (_, options) = parse_args(&["--time-budget".to_string(), "0.5".to_string()]).unwrap();
+    assert_eq!(options.time_budget, Some(Duration::from_millis(500)));
+    assert!(parse_args(&["--time-budget".to_string(), "-1".to_string()]).is_err());
+    let args: Vec<String> = ["--frames", "30", "--frame-zoom", "1.25", "--subframes", "4"]
+        .iter().map(|s| s.to_string()).collect();
+    let (_, options) = parse_args(&args).unwrap();
+    assert_eq!((options.frames, options.frame_zoom, options.subframes), (Some(30), 1.25, 4));
+    assert_eq!(Options::default().frame_zoom, DEFAULT_FRAME_ZOOM);
+    assert!(parse_args(&["--frames".to_string(), "0".to_string()]).is_err());
+    assert!(parse_args(&["--frame-zoom".to_string(), "-2".to_string()]).is_err());
+    assert!(parse_args(&["--subframes".to_string(), "0".to_string()]).is_err());
+    let (_, options) = parse_args(&["--manifest".to_string(), "zoom.json".to_string()]).unwrap();
+    assert_eq!(options.manifest, Some(PathBuf::from("zoom.json")));
+    let (_, options) = parse_args(&["--profile".to_string(), "render.svg".to_string()]).unwrap();
+    assert_eq!(options.profile, Some(PathBuf::from("render.svg")));
+    let (_, options) = parse_args(&["--stats-json".to_string(), "-".to_string()]).unwrap();
+    assert_eq!(options.stats_json, Some(PathBuf::from("-")));
+    let (_, options) = parse_args(&["--frame-iteration-growth".to_string(), "1.05".to_string()])
+        .unwrap();
+    assert_eq!(options.frame_iteration_growth, 1.05);
+    assert!(parse_args(&["--frame-iteration-growth".to_string(), "0".to_string()]).is_err());
+
+    let (_, options) = parse_args(&["--iterations".to_string(), "1000".to_string()]).unwrap();
+    assert_eq!(options.limit, 1000);
+    assert_eq!(Options::default().limit, LIMIT);
+    assert!(parse_args(&["--iterations".to_string(), "0".to_string()]).is_err());
+}
+
+/// Iteration cap test: a limit over the cap is refused unless the cap is
+/// raised or lifted, wherever the flags come.
+#[test]
+fn test_iteration_cap() {
+    let parse = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        parse_args(&args).map(|(_, options)| options.limit)
+    };
+    let huge = (DEFAULT_ITERATION_CAP + 1).to_string();
+    let error = parse(&["--iterations", &huge]).unwrap_err();
+    assert!(error.contains("`--allow-huge`"), "{}", error);
+    assert_eq!(parse(&["--iterations", &DEFAULT_ITERATION_CAP.to_string()]),
+               Ok(DEFAULT_ITERATION_CAP));
+    assert_eq!(parse(&["--iterations", &huge, "--allow-huge"]), Ok(DEFAULT_ITERATION_CAP + 1));
+    assert_eq!(parse(&["--iterations", "4000000000", "--allow-huge"]), Ok(4_000_000_000));
+
+    // The cap can be raised, or lowered, and come after the limit.
+    assert_eq!(parse(&["--iterations", &huge, "--clamp-iterations", "10000000"]),
+               Ok(DEFAULT_ITERATION_CAP + 1));
+    assert!(parse(&["--iterations", "2000", "--clamp-iterations", "1000"]).is_err());
+    assert!(parse(&["--clamp-iterations", "0"]).is_err());
+    // Even when carrying on past bad values, a limit over the cap stops.
+    assert!(parse(&["--iterations", &huge, "--continue-on-parse-error"]).is_err());
+
+    // So does a limit that only grows over it by the last frame.
+    let error = parse(&["--iterations", "1000000", "--frames", "30",
+                        "--frame-iteration-growth", "1.5"]).unwrap_err();
+    assert!(error.contains("`--allow-huge`"), "{}", error);
+    assert!(parse(&["--frame-iteration-growth", "1.5", "--frames", "30", "--iterations", "1000000",
+                    "--allow-huge"]).is_ok());
+    assert!(parse(&["--iterations", "1000000", "--frames", "30", "--frame-iteration-growth", "1.01"])
+                .is_ok());
+}
+
+/// parse_args test with `--continue-on-parse-error`
+#[test]
+fn test_parse_args_continue_on_parse_error() {
+    let args: Vec<String> = ["out.png", "--iterations", "lots", "100x75", "-2,1", "1,-1",
+                             "--threads", "0", "--palette", "plaid", "--continue-on-parse-error"]
+        .iter().map(|s| s.to_string()).collect();
+    let (positional, options) = parse_args(&args).unwrap();
+    assert_eq!(positional, vec!["out.png", "100x75", "-2,1", "1,-1"]);
+    assert_eq!(options.limit, LIMIT);
+    assert_eq!(options.threads, Options::default().threads);
+    assert_eq!(options.palette, None);
+    assert_eq!(options.warnings.len(), 3);
+    assert!(options.warnings[0].contains("`lots`"));
+
+    // The defaults still render.
+    let bounds = PixelBounds::new(100, 75);
+    let view = ComplexView::new(parse_complex(positional[2].to_str().unwrap()).unwrap(),
+                                parse_complex(positional[3].to_str().unwrap()).unwrap());
+    let pixels = render_image(bounds, view, options.shading(), options.limit, Coloring::Gray,
+                              options.sampling(), None, options.threading()).unwrap();
+    assert_eq!(pixels.len(), bounds.pixel_count());
+
+    // Critical settings, and everything without the flag, still fail.
+    let args: Vec<String> = ["--continue-on-parse-error", "--bit-depth", "3"]
+        .iter().map(|s| s.to_string()).collect();
+    assert!(parse_args(&args).is_err());
+    assert!(parse_args(&["--iterations".to_string(), "lots".to_string()]).is_err());
+}
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Decides whether messages to stdout and stderr get ANSI colors: green
+/// for success, red for errors.
+///
+/// Colors are only used on terminals, and never when `--no-color` is
+/// given or the `NO_COLOR` environment variable is set to anything but
+/// the empty string (see <https://no-color.org>).
+struct Painter {
+    stdout: bool,
+    stderr: bool,
+}
+
+impl Painter {
+    /// Look at the command-line arguments, the environment and the
+    /// output streams. This happens before the arguments are parsed, so
+    /// that errors from parsing them can be colored too.
+    fn new(args: &[OsString]) -> Painter {
+        Painter::from_parts(args.iter().any(|arg| arg == "--no-color"),
+                            env::var_os("NO_COLOR").as_deref(),
+                            io::stdout().is_terminal(),
+                            io::stderr().is_terminal())
+    }
+
+    fn from_parts(no_color_flag: bool, no_color_env: Option<&OsStr>,
+                  stdout_is_terminal: bool, stderr_is_terminal: bool) -> Painter {
+        let allowed = !no_color_flag && no_color_env.is_none_or(OsStr::is_empty);
+        Painter {
+            stdout: allowed && stdout_is_terminal,
+            stderr: allowed && stderr_is_terminal,
         }
     }
+
+    /// 'text' dressed up as a success message for stdout.
+    fn success(&self, text: &str) -> String {
+        paint(text, GREEN, self.stdout)
+    }
+
+    /// 'text' dressed up as an error message for stderr.
+    fn error(&self, text: &str) -> String {
+        paint(text, RED, self.stderr)
+    }
+}
+
+/// Wrap 'text' in the ANSI 'color' code if 'enabled'.
+fn paint(text: &str, color: &str, enabled: bool) -> String {
+    if enabled {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
 }
 
-#[allow(dead_code)]
-fn write_image(filename: &str, pixels: &[u8], bounds: (usize, usize)) 
-    -> Result<(), std::io::Error> {
-        let output = File::create(filename)?;
+/// The message printed once the image has been written to 'path'.
+fn success_message(path: &Path) -> String {
+    format!("\n Mandlebrot Program Finished! Program exited successfully!\n Image written to {}\n\n",
+            path.display())
+}
 
-        let encoder = PNGEncoder::new(output);
-        encoder.encode(&pixels, bounds.0 as u32, bounds.1 as u32, ColorType::Gray(8))?;
-        Ok(())
+/// Why the program stopped without doing what it was asked.
+#[derive(Debug)]
+enum Failure {
+    /// The wrong number of arguments; the usage text to print, as is.
+    Usage(String),
+    /// Arguments that can't be parsed or don't go together, or a
+    /// self-test mismatch.
+    Invalid(String),
+    /// The image couldn't be rendered or saved.
+    Render(RenderError),
 }
 
-/// parse_pair test
+impl From<RenderError> for Failure {
+    fn from(error: RenderError) -> Failure {
+        Failure::Render(error)
+    }
+}
+
+/// The exit status for 'failure': 1 for a bad command line, and for a
+/// failure to render or save the image, 2 for its parameters, 3 for
+/// running out of memory, 4 for encoding it, 5 for writing it out and 6
+/// for renders that failed `--verify`.
+fn exit_code(failure: &Failure) -> i32 {
+    match failure {
+        Failure::Usage(_) | Failure::Invalid(_) => 1,
+        Failure::Render(RenderError::InvalidBounds(_)) | Failure::Render(RenderError::InvalidView(_))
+        | Failure::Render(RenderError::InvalidThreading(_)) => 2,
+        Failure::Render(RenderError::Allocation { .. }) => 3,
+        Failure::Render(RenderError::Encode(_)) => 4,
+        Failure::Render(RenderError::Io(_)) => 5,
+        Failure::Render(RenderError::Nondeterministic { .. }) => 6,
+    }
+}
+
+/// exit_code test
 #[test]
-fn	test_parse_pair() {
-    assert_eq!(parse_pair::<i32>("", ','), None);
-    assert_eq!(parse_pair::<i32>("10,",	','), None);
-    assert_eq!(parse_pair::<i32>(",10",	','), None);
-    assert_eq!(parse_pair::<i32>("10,20", ','),	Some((10,	20)));
-    assert_eq!(parse_pair::<i32>("10,20xy",	','), None);
-    assert_eq!(parse_pair::<f64>("0.5x", 'x'),	None);
-    assert_eq!(parse_pair::<f64>("0.5x1.5",	'x'), Some((0.5,	1.5)));
+fn test_exit_code() {
+    let io_error = || io::Error::other("broken");
+    assert_eq!(exit_code(&Failure::Usage("Usage: ...".to_string())), 1);
+    assert_eq!(exit_code(&Failure::Invalid("bad flag".to_string())), 1);
+    assert_eq!(exit_code(&RenderError::InvalidBounds(PixelBounds::new(0, 0)).into()), 2);
+    let view = ComplexView::new(num::Complex::new(0.0, 0.0), num::Complex::new(0.0, 0.0));
+    assert_eq!(exit_code(&RenderError::InvalidView(view).into()), 2);
+    let threading = Threading { threads: 2, chunk_rows: Some(usize::MAX) };
+    assert_eq!(exit_code(&RenderError::InvalidThreading(threading).into()), 2);
+    assert_eq!(exit_code(&RenderError::Allocation { bytes: 1 }.into()), 3);
+    assert_eq!(exit_code(&RenderError::Encode(io_error()).into()), 4);
+    assert_eq!(exit_code(&RenderError::Io(io_error()).into()), 5);
+    assert_eq!(exit_code(&RenderError::Nondeterministic { column: 0, row: 0 }.into()), 6);
 }
 
-/// parse_complex test
+/// success_message test
 #[test]
-fn	test_parse_complex() {
-    assert_eq!(parse_complex("1.25,-0.0625"), Some(Complex{	re:	1.25,	im:	-0.0625	}));
-    assert_eq!(parse_complex(",-0.0625"),	None);
+fn test_success_message() {
+    let path = resolve_output_path("out.png", None).unwrap();
+    assert!(path.is_absolute());
+    assert!(success_message(&path).contains(&path.display().to_string()));
 }
 
-/// pixel_to_point test
+/// Painter test
 #[test]
-fn test_pixel_to_point() {
-    assert_eq!(pixel_to_point((100,100), (25, 75),
-                    Complex { re: -1.0, im: 1.0 },
-                    Complex { re: 1.0, im: -1.0 }),
-                    Complex { re: -0.5, im: -0.5 });
+fn test_painter() {
+    let message = success_message(Path::new("/tmp/out.png"));
+
+    // Piped output is never colored.
+    let piped = Painter::from_parts(false, None, false, false);
+    assert!(!piped.success(&message).contains('\x1b'));
+    assert!(!piped.error("bad").contains('\x1b'));
+
+    let terminal = Painter::from_parts(false, None, true, true);
+    assert_eq!(terminal.success("ok"), "\x1b[32mok\x1b[0m");
+    assert_eq!(terminal.error("bad"), "\x1b[31mbad\x1b[0m");
+
+    // Terminals too, when asked not to.
+    let flagged = Painter::from_parts(true, None, true, true);
+    assert_eq!(flagged.success("ok"), "ok");
+    let env = Painter::from_parts(false, Some(OsStr::new("1")), true, true);
+    assert_eq!(env.error("bad"), "bad");
+    let empty_env = Painter::from_parts(false, Some(OsStr::new("")), true, false);
+    assert_eq!(empty_env.success("ok"), "\x1b[32mok\x1b[0m");
+    assert_eq!(empty_env.error("bad"), "bad");
 }
 
 /// This program takes a set of command line arguments and with those
@@ -152,60 +1290,1025 @@ fn test_pixel_to_point() {
 /// The work is split up among threads using crossbeam, and in turn they split up the rows of
 /// the image to be rendered until it's completed. 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let all_args: Vec<OsString> = std::env::args_os().collect();
+    let painter = Painter::new(&all_args);
+    if let Err(failure) = run(&all_args, &painter) {
+        match failure {
+            Failure::Usage(ref usage) => eprint!("{}", usage),
+            Failure::Invalid(ref message) => eprintln!("{}", painter.error(message)),
+            Failure::Render(ref error) => eprintln!("{}", painter.error(&error.to_string())),
+        }
+        std::process::exit(exit_code(&failure));
+    }
+}
+
+/// The summary `--dry-run` prints of a render to 'path' (or stdout) of
+/// an image of size 'bounds' covering 'view', 'bytes' big in memory and
+/// estimated to take 'estimate'.
+fn plan(path: Option<&Path>, bounds: PixelBounds, view: ComplexView, options: &Options,
+        threading: Threading, bytes: usize, estimate: Duration) -> String {
+    let palette = match options.palette {
+        Some(ref colors) => palette::NAMES.iter()
+            .find(|&&name| palette::named(name).as_ref() == Some(colors))
+            .map_or("custom".to_string(), |name| name.to_string()),
+        None => "none (grayscale)".to_string(),
+    };
+    let chunks = match threading.chunk_rows {
+        Some(rows) => format!(", {} rows at a time", rows),
+        None => String::new(),
+    };
+    [
+        format!("file: {}", path.map_or("(stdout)".to_string(), |path| path.display().to_string())),
+        format!("pixels: {}x{}", bounds.width, bounds.height),
+        format!("upper left: {}", format_complex(view.upper_left)),
+        format!("lower right: {}", format_complex(view.lower_right)),
+        format!("iterations: {}", options.limit),
+        format!("threads: {}{}", threading.threads, chunks),
+        format!("palette: {}", palette),
+        format!("memory: {:.1} MiB for the image", bytes as f64 / (1024.0 * 1024.0)),
+        format!("estimated time: {:.2}s", estimate.as_secs_f64()),
+    ].iter().map(|line| line.clone() + "\n").collect()
+}
+
+/// Toggle 'pause' on a thread of its own each time a line is read from
+/// stdin, saying which on stderr. The thread ends with stdin, leaving the
+/// render running.
+fn listen_for_pauses(pause: Arc<Pause>) {
+    thread::spawn(move || {
+        for _ in io::stdin().lines() {
+            let paused = pause.toggle();
+            eprintln!("{}", if paused { "paused; press Enter to resume" } else { "resumed" });
+        }
+        pause.set(false);
+    });
+}
+
+/// Whether a view too deep for f64 can be rendered in double-double
+/// precision with 'options' instead: the `high-precision` feature must be
+/// built in, and 'options' must ask for a plain or smooth escape-time
+/// render of the Mandelbrot set over the flat plane, in 8 bits, without
+/// supersampling, a mask, bulb colors or a render mode of its own.
+fn deep_fallback(options: &Options, julia: bool) -> bool {
+    cfg!(feature = "high-precision")
+        && matches!(options.shading(), Shading::EscapeTime | Shading::Smooth { .. })
+        && !julia && options.projection == Projection::Plane && options.sampling().samples() == 1
+        && options.mask.is_none() && options.bit_depth == 8 && !options.normal_map
+        && !options.interior_period_color && options.preview_interval.is_none() && !options.verify
+        && !options.auto_contrast && !options.mariani_silver && !options.pausable
+}
+
+/// Do what the command line 'all_args' asks, reporting progress through
+/// 'painter'. Failures are left to `main` to report.
+fn run<S: AsRef<OsStr>>(all_args: &[S], painter: &Painter) -> Result<(), Failure> {
+    let (args, mut options) = parse_args(&all_args[1..]).map_err(Failure::Invalid)?;
+    for warning in &options.warnings {
+        eprintln!("{}", painter.error(&format!("warning: {}", warning)));
+    }
+
+    if options.selftest {
+        self_test().map_err(Failure::Invalid)?;
+        println!("{}", painter.success("self-test passed"));
+        return Ok(());
+    }
+
+    if let Some(samples) = options.area {
+        let (area, error) = estimate_area(samples, options.limit.max(AREA_LIMIT), AREA_SEED);
+        println!("area: {:.6}", area);
+        println!("standard error: {:.6}", error);
+        return Ok(());
+    }
+
+    if let Some((a, b)) = &options.compare {
+        let open = |path: &PathBuf| image::open(path).map_err(|error| {
+            Failure::Invalid(format!("error reading `{}`: {}", path.display(), error))
+        });
+        let difference = compare(&open(a)?, &open(b)?).map_err(Failure::Invalid)?;
+        println!("max difference: {}", difference.max);
+        println!("mean difference: {:.6}", difference.mean);
+        if let Some(diff) = &options.diff {
+            save_image(diff, &difference.pixels, difference.bounds, ColorType::Gray(8))?;
+            println!("{}", painter.success(&format!("wrote the differences to {}", diff.display())));
+        }
+        return Ok(());
+    }
+
+    if let Some(points) = &options.classify {
+        let input = File::open(points).map_err(|error| {
+            Failure::Invalid(format!("error reading `{}`: {}", points.display(), error))
+        })?;
+        let input = BufReader::new(input);
+        let count = match &options.classify_output {
+            Some(path) => {
+                let mut csv = Vec::new();
+                let count = classify(input, &mut csv, options.limit).map_err(Failure::Invalid)?;
+                write_atomically(path, &csv).map_err(RenderError::Io)?;
+                count
+            }
+            None => classify(input, io::stdout().lock(), options.limit).map_err(Failure::Invalid)?,
+        };
+        eprintln!("{}", painter.success(&format!("classified {} points", count)));
+        return Ok(());
+    }
+
+    if options.repl {
+        let stdin = io::stdin();
+        return repl::run(stdin.lock(), io::stdout()).map_err(|error| Failure::Render(error.into()));
+    }
 
     // if they have the incorrect (arguments / amount of arguments), tell them!
-    if args.len() != 5 {
-        writeln!(std::io::stderr(),
-            "Usage: mandlebrot FILE PIXELS UPPERLEFT LOWERRIGHT")
-            .unwrap();
+    // FILE is left out when the image goes to stdout, or nowhere. PIXELS
+    // may be when the size comes from another image, and the corners when
+    // `--region` names the view; whatever is left is told apart by count.
+    let writes_file = !options.stdout_base64 && !options.probe;
+    let (file, rest) = match args.split_first() {
+        Some((&file, rest)) if writes_file => (Some(file), rest),
+        _ => (None, &args[..]),
+    };
+    let has_pixels = rest.len() % 2 == 1;
+    let has_corners = rest.len() >= 2;
+    if (writes_file && file.is_none()) || rest.len() > 3
+        || !(has_pixels || options.dimensions_from.is_some())
+        || !(has_corners || options.region.is_some())
+    {
+        let usage = [
+            "Usage: mandlebrot FILE PIXELS UPPERLEFT LOWERRIGHT [--region NAME] [--benchmark-view] [--smooth] [--smooth-extra N] [--lyapunov] [--light AZ,EL] [--potential] [--projection plane|stereographic] [--center-on COL,ROW] [--zoom F] [--snap-to-grid] [--seed-julia-from-mandelbrot COL,ROW] [--autocrop] [--tile-output RxC] [--overlay] [--overlay-corner CORNER] [--contours N] [--contour-color R,G,B] [--edges STRENGTH] [--vignette STRENGTH] [--blur RADIUS] [--thumbnail DIVISOR] [--auto-contrast] [--interior-period-color] [--palette NAME] [--palette-repeat K] [--palette-reverse] [--palette-rotate N] [--interp lab|rgb] [--lut-resolution N] [--alpha interior|exterior] [--normal-map] [--indexed N] [--bit-depth 1|8|16] [--raw-dump FILE] [--endian le|be] [--quantize-bits N] [--png-compression fast|default|best] [--png-filter FILTER] [--heatmap FILE] [--legend FILE] [--potential-exr FILE] [--escape-count-histogram FILE|-] [--histogram-bucket N] [--mask FILE] [--output-dimensions-from-file FILE] [--output-dir DIR] [--aa N] [--aa-x N] [--aa-y M] [--aa-early-out K] [--aa-roi X,Y,W,H N] [--supersample-seed N] [--iterations N] [--clamp-iterations N] [--allow-huge] [--iterations-auto] [--threads N] [--auto-tune] [--time-budget SECS] [--chunk-rows N] [--preview-interval SECS] [--verify] [--pausable] [--mariani-silver] [--frames N] [--frame-zoom F] [--frame-iteration-growth G] [--subframes K] [--manifest FILE] [--profile FILE] [--stats-json FILE|-] [--warn-empty off|on|solid] [--dry-run] [--force] [--no-color] [--continue-on-parse-error]".to_string(),
+            "       mandlebrot --stdout-base64 PIXELS UPPERLEFT LOWERRIGHT [OPTIONS]".to_string(),
+            "       mandlebrot --probe PIXELS UPPERLEFT LOWERRIGHT".to_string(),
+            "       mandlebrot --selftest".to_string(),
+            "       mandlebrot --repl".to_string(),
+            "       mandlebrot --compare A B [--diff FILE]".to_string(),
+            "       mandlebrot --area SAMPLES [--iterations N]".to_string(),
+            "       mandlebrot --classify FILE [--classify-output FILE] [--iterations N]".to_string(),
+            String::new(),
+            format!("Example: {} mandelbrot.png 1000x750 -1.20,0.35 -1,0.20",
+                    all_args[0].as_ref().to_string_lossy()),
+        ];
+        return Err(Failure::Usage(usage.join("\n") + "\n"));
+    }
 
-        writeln!(std::io::stderr(),
-            "Example: {} mandelbrot.png 1000x750 -1.20,0.35 -1,0.20", 
-            args[0])
-            .unwrap();
+    // Only FILE is a path; the rest are numbers, which have to be text.
+    let rest = rest.iter()
+        .map(|arg| arg.to_str().ok_or_else(|| {
+            Failure::Invalid(format!("invalid argument `{}`", arg.to_string_lossy()))
+        }))
+        .collect::<Result<Vec<&str>, _>>()?;
+    let (dimensions, corners) = rest.split_at(rest.len() % 2);
 
-        std::process::exit(1);
+    let given = dimensions.first()
+        .map(|&dimensions| {
+            parse_pair(dimensions, 'x')
+                .map(|(width, height)| PixelBounds::new(width, height))
+                .ok_or_else(|| {
+                    Failure::Invalid(format!("error parsing the image dimensions `{}`", dimensions))
+                })
+        })
+        .transpose()?;
+    let bounds = match options.dimensions_from {
+        Some(ref reference) => {
+            let bounds = image_bounds(reference).map_err(|error| {
+                Failure::Invalid(format!("error reading the size of `{}`: {}",
+                                         reference.display(), error))
+            })?;
+            if given.is_some_and(|given| given != bounds) {
+                eprintln!("{}", painter.error(&format!("warning: rendering at {}x{} to match `{}`, \
+                                                        not the PIXELS given",
+                                                       bounds.width, bounds.height,
+                                                       reference.display())));
+            }
+            bounds
+        }
+        None => given.expect("PIXELS is only left out with `--output-dimensions-from-file`"),
+    };
+    let corner = |text: &str, which: &str| parse_complex(text).ok_or_else(|| {
+        Failure::Invalid(format!("error parsing the {} corner point `{}`", which, text))
+    });
+    let view = match (corners, options.region) {
+        (_, Some(region)) if options.benchmark_view => {
+            if !corners.is_empty() {
+                eprintln!("{}", painter.error("warning: rendering the benchmark view, not the corners \
+                                               given"));
+            }
+            region
+        }
+        (&[upper_left, lower_right], region) => {
+            if region.is_some() {
+                eprintln!("{}", painter.error("warning: rendering the corners given, not `--region`"));
+            }
+            ComplexView::new(corner(upper_left, "upper left")?, corner(lower_right, "lower right")?)
+        }
+        (_, Some(region)) => region,
+        _ => unreachable!("the corners are only left out with `--region`"),
+    };
+    let mut view = view.with_projection(options.projection);
+    if let Some((column, row)) = options.center_on {
+        if column >= bounds.width || row >= bounds.height {
+            return Err(Failure::Invalid(format!("`--center-on {},{}` is outside the {}x{} image",
+                                                column, row, bounds.width, bounds.height)));
+        }
+        view = recenter(view, bounds, (column, row), options.zoom);
+        // For the next step of an explorer loop.
+        eprintln!("center-on: rendering {} {}", format_complex(view.upper_left),
+                  format_complex(view.lower_right));
+    }
+    if options.snap_to_grid {
+        view = snap_to_grid(view, bounds);
+    }
+    if let Some((left, top, width, height)) = options.aa_roi {
+        if width == 0 || height == 0 || left + width > bounds.width || top + height > bounds.height {
+            return Err(Failure::Invalid(format!("`--aa-roi {},{},{},{}` isn't a rectangle inside \
+                                                 the {}x{} image", left, top, width, height,
+                                                bounds.width, bounds.height)));
+        }
+    }
+    // The Julia set is rendered whole, in place of the Mandlebrot view,
+    // which only picks its constant.
+    let julia = match options.julia_from {
+        Some((column, row)) if column >= bounds.width || row >= bounds.height => {
+            return Err(Failure::Invalid(format!("`--seed-julia-from-mandelbrot {},{}` is outside \
+                                                 the {}x{} image", column, row, bounds.width,
+                                                bounds.height)));
+        }
+        Some(pixel) => {
+            let julia = Julia::from_pixel(bounds, pixel, view);
+            eprintln!("julia: rendering the Julia set of c = {}", format_complex(julia.c));
+            view = Julia::whole_view(bounds).with_projection(options.projection);
+            Some(julia)
+        }
+        None => None,
+    };
+    if julia.is_some()
+        && (options.probe || options.shading() != Shading::EscapeTime || options.sampling().samples() > 1
+            || options.mask.is_some() || options.bit_depth != 8 || options.normal_map
+            || options.preview_interval.is_some() || options.verify || options.pausable
+            || options.mariani_silver || options.frames.is_some() || options.contours.is_some()
+            || options.edges.is_some() || options.heatmap.is_some() || options.raw_dump.is_some()
+            || options.potential_exr.is_some()
+            || options.escape_count_histogram.is_some() || options.stats_json.is_some()
+            || file.is_some_and(|file| is_svg(Path::new(file))))
+    {
+        return Err(Failure::Invalid("`--seed-julia-from-mandelbrot` renders a plain escape-time \
+                                     image of the Julia set; it can't be combined with other \
+                                     shadings, `--aa`, `--mask` or options that render or report \
+                                     on the Mandlebrot set itself".to_string()));
+    }
+    let deep = exceeds_f64_resolution(bounds, view) && deep_fallback(&options, julia.is_some());
+    if deep {
+        eprintln!("{}", painter.error("warning: this view is too deep for f64 precision; \
+                                       rendering it in double-double precision instead"));
+    } else if exceeds_f64_resolution(bounds, view) {
+        // Without the `high-precision` feature, or with options it can't
+        // render, the best we can do is say why the image will look wrong.
+        eprintln!("{}", painter.error("warning: this view is too deep for f64 precision; \
+                                       neighbouring pixels will repeat and look blocky"));
+    }
+    if options.probe {
+        // Most views without any of the set are settled in the one pass.
+        let fraction = if contains_interior(bounds, view, options.limit) {
+            interior_fraction(bounds, view, options.limit)
+        } else {
+            0.0
+        };
+        println!("interior: {}", if fraction > 0.0 { "yes" } else { "no" });
+        println!("interior fraction: {:.6}", fraction);
+        return Ok(());
     }
 
-    let bounds = parse_pair(&args[2], 'x')
-        .expect("error parsing the image dimensions");
-    let upper_left = parse_complex(&args[3])
-        .expect("error parsing the upper left corner point");
-    let lower_right = parse_complex(&args[4])
-        .expect("error parsing the lower right corner point");
+    let path = file.map(|file| resolve_output_path(file, options.output_dir.as_deref()))
+        .transpose()
+        .map_err(|error| Failure::Invalid(format!("error resolving the output path: {}", error)))?;
+    // Check now, rather than after what may be a long render.
+    if let (Some(path), false) = (path.as_ref(), options.force) {
+        let existing = match options.frames {
+            Some(frames) => (0..frames).map(|frame| frame_path(path, frame, frames))
+                .find(|frame| frame.exists()),
+            None => Some(path.clone()).filter(|path| path.exists()),
+        };
+        if let Some(existing) = existing {
+            return Err(Failure::Invalid(format!("`{}` already exists; pass `--force` to overwrite it",
+                                                existing.display())));
+        }
+    }
+
+    // A traced boundary has no pixels to shade, color, mask or finish.
+    if path.as_deref().is_some_and(is_svg)
+        && (options.shading() != Shading::EscapeTime || options.sampling().samples() > 1
+            || options.palette.is_some() || options.alpha.is_some() || options.mask.is_some()
+            || options.interior_period_color || options.legend.is_some() || options.bit_depth != 8
+            || options.normal_map || options.indexed.is_some() || options.frames.is_some()
+            || options.stats_json.is_some() || options.preview_interval.is_some() || options.verify
+            || options.pausable || options.mariani_silver || options.auto_contrast
+            || options.time_budget.is_some() || options.contours.is_some() || options.edges.is_some()
+            || options.overlay.is_some() || options.autocrop || options.tile_output.is_some()
+            || options.heatmap.is_some() || options.raw_dump.is_some()
+            || options.potential_exr.is_some() || options.escape_count_histogram.is_some()
+            || options.vignette.is_some() || options.blur.is_some())
+    {
+        return Err(Failure::Invalid("an `.svg` FILE is the traced boundary of the set; it can't be \
+                                     combined with other shadings, `--aa`, `--palette`, `--alpha`, \
+                                     `--mask`, `--bit-depth`, `--normal-map`, `--indexed`, \
+                                     `--frames`, `--stats-json` or options that change or add to a \
+                                     rendered image".to_string()));
+    }
+    if let Some(path) = path.as_ref().filter(|path| is_svg(path) && !options.dry_run) {
+        // Only membership matters, as for `--bit-depth 1`.
+        let inside = render_membership(bounds, view, options.limit);
+        write_atomically(path, svg(&trace_boundary(&inside, bounds), bounds).as_bytes())
+            .map_err(RenderError::Io)?;
+        println!("{}", painter.success(&success_message(path)));
+        return Ok(());
+    }
 
-    let mut pixels = vec![0; bounds.0 * bounds.1];
-    let threads = 8;
-    let rows_per_thread = bounds.1 / threads + 1;
+    // Work the palette out once for every count, rather than per pixel.
+    let lut = options.palette.as_ref().map(|colors| {
+        PaletteLut::new(colors, options.limit, options.palette_repeat, options.lut_resolution,
+                        options.interpolation)
+    });
+    let base = match lut {
+        Some(ref lut) => Coloring::Lut(lut),
+        None => Coloring::Gray,
+    };
+    let periodic = Coloring::InteriorPeriod { base: &base, colors: palette::PERIOD_COLORS };
+    let base = if options.interior_period_color { periodic } else { base };
+    let coloring = match options.alpha {
+        Some(clear) => Coloring::Alpha { base: &base, clear },
+        None => base,
+    };
+    if options.interior_period_color
+        && (options.mariani_silver || options.auto_contrast || julia.is_some())
+    {
+        return Err(Failure::Invalid("`--interior-period-color` iterates every member of the set on its \
+                                     own; it can't be combined with `--mariani-silver`, \
+                                     `--auto-contrast` or `--seed-julia-from-mandelbrot`".to_string()));
+    }
+    if options.legend.is_some()
+        && (!matches!(options.shading(), Shading::EscapeTime | Shading::Smooth { .. })
+            || options.bit_depth != 8 || options.normal_map || options.auto_contrast
+            || options.frames.is_some())
+    {
+        return Err(Failure::Invalid("`--legend` is a key to escape-count colors; it can't be combined \
+                                     with other shadings, `--bit-depth`, `--normal-map`, \
+                                     `--auto-contrast` or `--frames`".to_string()));
+    }
+    let decorated = options.contours.is_some() || options.edges.is_some();
+    // These render escape counts or membership straight from the plane,
+    // one point per pixel and unmasked.
+    if options.bit_depth != 8
+        && (coloring != Coloring::Gray || decorated || options.vignette.is_some()
+            || options.blur.is_some() || options.indexed.is_some()
+            || options.shading() != Shading::EscapeTime || options.sampling().samples() > 1
+            || options.mask.is_some())
+    {
+        return Err(Failure::Invalid(format!("`--bit-depth {}` is plain grayscale; it can't be combined \
+                                             with other shadings, `--aa`, `--mask`, `--palette`, \
+                                             `--alpha`, `--contours`, `--edges`, `--vignette`, \
+                                             `--blur` or `--indexed`", options.bit_depth)));
+    }
+    if options.normal_map
+        && (coloring != Coloring::Gray || options.bit_depth != 8
+            || !matches!(options.shading(), Shading::EscapeTime | Shading::Smooth { .. })
+            || options.sampling().samples() > 1 || options.mask.is_some())
+    {
+        return Err(Failure::Invalid("`--normal-map` writes its own colors; it can't be combined \
+                                     with other shadings, `--aa`, `--mask`, `--palette`, `--alpha` \
+                                     or `--bit-depth`".to_string()));
+    }
+    if options.indexed.is_some() && (coloring.channels() == 4 || options.normal_map) {
+        return Err(Failure::Invalid("`--indexed` can't be combined with `--alpha` or `--normal-map`"
+                                    .to_string()));
+    }
+    let format = match coloring {
+        _ if options.normal_map => OutputFormat::Rgb8,
+        Coloring::Gray if options.bit_depth == 1 => OutputFormat::Gray1,
+        Coloring::Gray if options.bit_depth == 16 => OutputFormat::Gray16,
+        Coloring::Gray => OutputFormat::Gray8,
+        Coloring::Palette { .. } | Coloring::Lut(_) | Coloring::InteriorPeriod { .. } =>
+            OutputFormat::Rgb8,
+        Coloring::Alpha { .. } => OutputFormat::Rgba8,
+        Coloring::Anchored { .. } => unreachable!("only animation frames are anchored"),
+    };
+    let color_type = format.color_type();
+    // A 16-bit sample takes two bytes, which are moved about together.
+    let channels = format.bytes_per_pixel();
+    if channels == 4 && path.as_deref().is_some_and(is_netpbm) {
+        return Err(Failure::Invalid("`--alpha` needs PNG output; Netpbm files have no alpha channel"
+                                    .to_string()));
+    }
+    if options.bit_depth != 8 && path.as_deref().is_some_and(is_netpbm) {
+        return Err(Failure::Invalid(format!("`--bit-depth {}` needs PNG output", options.bit_depth)));
+    }
+    if options.png != PngOptions::default()
+        && (path.as_deref().is_none_or(|path| is_netpbm(path) || is_svg(path))
+            || options.indexed.is_some())
+    {
+        return Err(Failure::Invalid("`--png-compression` and `--png-filter` tune the PNG written to \
+                                     FILE; they need a PNG FILE and can't be combined with \
+                                     `--indexed`".to_string()));
+    }
+    if options.quantize_bits.is_some() && options.raw_dump.is_none() {
+        return Err(Failure::Invalid("`--quantize-bits` sets how the escape counts are written to \
+                                     `--raw-dump FILE`; it needs `--raw-dump`".to_string()));
+    }
+    if path.is_none() && options.escape_count_histogram.as_deref() == Some(Path::new("-")) {
+        return Err(Failure::Invalid("`--escape-count-histogram -` needs a FILE to write the image \
+                                     to, as stdout is taken by the report".to_string()));
+    }
+    if options.preview_interval.is_some() && (path.is_none() || options.verify) {
+        return Err(Failure::Invalid("`--preview-interval` needs a FILE to write, and can't be \
+                                     combined with `--verify`".to_string()));
+    }
+    if options.indexed.is_some() && path.as_deref().is_some_and(is_netpbm) {
+        return Err(Failure::Invalid("`--indexed` needs PNG output".to_string()));
+    }
+    if options.pausable
+        && (options.bit_depth != 8 || options.normal_map || options.preview_interval.is_some()
+            || options.verify || options.frames.is_some())
+    {
+        return Err(Failure::Invalid("`--pausable` can't be combined with `--bit-depth`, \
+                                     `--normal-map`, `--preview-interval`, `--verify` or \
+                                     `--frames`".to_string()));
+    }
+    if options.mariani_silver
+        && (options.shading() != Shading::EscapeTime || options.sampling().samples() > 1 || options.mask.is_some()
+            || options.bit_depth != 8 || options.normal_map || options.preview_interval.is_some()
+            || options.verify || options.pausable || options.frames.is_some())
+    {
+        return Err(Failure::Invalid("`--mariani-silver` only renders plain escape-time images; it \
+                                     can't be combined with other shadings, `--aa`, `--mask`, \
+                                     `--bit-depth`, `--normal-map`, `--preview-interval`, \
+                                     `--verify`, `--pausable` or `--frames`".to_string()));
+    }
+    if options.auto_contrast
+        && (options.shading() != Shading::EscapeTime || options.sampling().samples() > 1 || options.mask.is_some()
+            || options.bit_depth != 8 || options.normal_map || options.preview_interval.is_some()
+            || options.verify || options.pausable || options.mariani_silver || julia.is_some()
+            || options.frames.is_some())
+    {
+        return Err(Failure::Invalid("`--auto-contrast` only renders plain escape-time images; it \
+                                     can't be combined with other shadings, `--aa`, `--mask`, \
+                                     `--bit-depth`, `--normal-map`, `--preview-interval`, \
+                                     `--verify`, `--pausable`, `--mariani-silver`, \
+                                     `--seed-julia-from-mandelbrot` or `--frames`".to_string()));
+    }
+    if options.time_budget.is_some()
+        && (options.dimensions_from.is_some() || options.mask.is_some()
+            || options.iterations_auto || options.frames.is_some() || options.aa_roi.is_some())
+    {
+        return Err(Failure::Invalid("`--time-budget` chooses the size and iterations itself; it \
+                                     can't be combined with `--output-dimensions-from-file`, \
+                                     `--mask`, `--iterations-auto`, `--frames` or `--aa-roi`"
+                                    .to_string()));
+    }
+    let finishing = decorated || options.overlay.is_some() || options.autocrop
+        || options.tile_output.is_some() || options.heatmap.is_some() || options.raw_dump.is_some()
+        || options.potential_exr.is_some()
+        || options.escape_count_histogram.is_some() || options.vignette.is_some()
+        || options.blur.is_some() || options.thumbnail.is_some();
+    if options.thumbnail.is_some()
+        && (path.as_deref().is_none_or(is_svg) || options.bit_depth != 8)
+    {
+        return Err(Failure::Invalid("`--thumbnail` saves a smaller copy of the image beside FILE; \
+                                     it needs a PNG or Netpbm FILE and 8-bit output".to_string()));
+    }
+    if options.frames.is_some()
+        && (path.is_none() || options.mask.is_some() || options.bit_depth != 8 || options.normal_map
+            || options.indexed.is_some() || options.preview_interval.is_some() || options.verify
+            || finishing)
+    {
+        return Err(Failure::Invalid("`--frames` writes plain rendered frames to FILE; it can't be \
+                                     combined with `--mask`, `--bit-depth`, `--normal-map`, \
+                                     `--indexed`, `--preview-interval`, `--verify` or options that \
+                                     change or add to the finished image".to_string()));
+    }
 
+    if options.frame_iteration_growth != 1.0
+        && (options.frames.is_none() || options.lyapunov || options.light.is_some()
+            || options.potential)
     {
-        let bands: Vec<&mut[u8]> =
-            pixels.chunks_mut(rows_per_thread * bounds.0).collect();
+        return Err(Failure::Invalid("`--frame-iteration-growth` needs `--frames`, and escape-time \
+                                     or smooth shading".to_string()));
+    }
+    if options.manifest.is_some() && options.frames.is_none() {
+        return Err(Failure::Invalid("`--manifest` lists the frames of an animation; it needs \
+                                     `--frames`".to_string()));
+    }
+    if options.profile.is_some() && (cfg!(not(feature = "profile")) || options.frames.is_some()) {
+        return Err(Failure::Invalid("`--profile` samples the render of a single image, in a build \
+                                     with the `profile` feature (`cargo build --features profile`); \
+                                     it can't be combined with `--frames`".to_string()));
+    }
+    if options.stats_json.is_some() && options.frames.is_some() {
+        return Err(Failure::Invalid("`--stats-json` measures the render of a single image; it can't \
+                                     be combined with `--frames`".to_string()));
+    }
+    // Only escape-time samples colored pixel by pixel have their
+    // iterations counted.
+    if options.stats_json.is_some()
+        && (options.shading() != Shading::EscapeTime || options.bit_depth != 8 || options.normal_map
+            || options.verify || options.auto_contrast || options.mariani_silver
+            || options.interior_period_color)
+    {
+        return Err(Failure::Invalid("`--stats-json` counts the iterations of plain escape-time \
+                                     renders; it can't be combined with other shadings, \
+                                     `--bit-depth`, `--normal-map`, `--verify`, `--auto-contrast`, \
+                                     `--mariani-silver` or `--interior-period-color`".to_string()));
+    }
+    if path.is_none() && options.stats_json.as_deref() == Some(Path::new("-")) {
+        return Err(Failure::Invalid("`--stats-json -` needs a FILE to write the image to, as stdout \
+                                     is taken by the stats".to_string()));
+    }
+
+    let mask = options.mask.as_ref()
+        .map(|mask| Mask::load(mask, bounds).map_err(|error| {
+            Failure::Invalid(format!("error loading the mask `{}`: {}", mask.display(), error))
+        }))
+        .transpose()?;
 
-        crossbeam::scope(|spawner| {
-            for(i, band) in bands.into_iter().enumerate() {
-                let top = rows_per_thread * i;
-                let height = band.len() / bounds.0;
-                let band_bounds = (bounds.0, height);
-                let band_upper_left = 
-                    pixel_to_point(bounds, (0, top), upper_left, lower_right);
+    if options.iterations_auto {
+        options.limit = auto_limit(bounds, view, AUTO_LIMIT_START, AUTO_LIMIT_THRESHOLD,
+                                   AUTO_LIMIT_MAX);
+        eprintln!("iterations-auto: iterating at most {} times", options.limit);
+    }
 
-                let band_lower_right = 
-                    pixel_to_point(bounds, (bounds.0, top + height), upper_left, lower_right);
+    let mut threading = options.threading();
+    if options.auto_tune {
+        // Allow for twice as many threads as cores, in case hyperthreads
+        // or waiting on memory make oversubscribing pay off.
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+        threading.threads = auto_tune_threads(bounds, view, options.shading(), options.limit, coloring,
+                                              options.sampling(), 2 * cores);
+        eprintln!("auto-tune: rendering on {} threads", threading.threads);
+    }
+    threading.check(bounds, coloring.channels())?;
 
-                spawner.spawn(move || {
-                    render(band, band_bounds, band_upper_left, band_lower_right);
-                });
+    let bounds = match options.time_budget {
+        Some(budget) => {
+            let start = Instant::now();
+            let requested = Quality { bounds, limit: options.limit, sampling: options.sampling() };
+            let speed = measure_speed(requested, view, options.shading(), coloring, threading);
+            let quality = fit_time_budget(requested, speed, budget.saturating_sub(start.elapsed()));
+            eprintln!("time-budget: rendering {}x{} with at most {} iterations and {} samples per \
+                       pixel", quality.bounds.width, quality.bounds.height, quality.limit,
+                      quality.sampling.samples());
+            options.limit = quality.limit;
+            options.aa_x = Some(quality.sampling.grid_x);
+            options.aa_y = Some(quality.sampling.grid_y);
+            quality.bounds
+        }
+        None => bounds,
+    };
+
+    if options.dry_run {
+        let estimate = estimate_render_time(bounds, view, options.shading(), options.limit, coloring,
+                                            options.sampling(), threading);
+        print!("{}", plan(path.as_deref(), bounds, view, &options, threading,
+                          bounds.pixel_count() * channels, estimate));
+        return Ok(());
+    }
+
+    if let (Some(frames), Some(path)) = (options.frames, path.as_ref()) {
+        let zoom = Zoom {
+            start: view,
+            factor: options.frame_zoom,
+            limit_growth: options.frame_iteration_growth,
+        };
+        for frame in 0..frames {
+            let pixels = render_frame(zoom, frame, options.subframes, bounds, options.shading(),
+                                      options.limit, coloring, options.sampling(), threading)?;
+            save_image_with(frame_path(path, frame, frames), &pixels, bounds, color_type,
+                            options.png)?;
+        }
+        if let Some(ref manifest_path) = options.manifest {
+            write_atomically(manifest_path, manifest(zoom, frames, path, options.limit).as_bytes())
+                .map_err(RenderError::Io)?;
+        }
+        let last = frame_path(path, frames - 1, frames);
+        println!("{}", painter.success(&format!("{} frames written, the last to {}", frames,
+                                                 last.display())));
+        return Ok(());
+    }
+
+    // Sampled, and timed, from here until the image is rendered.
+    let start = Instant::now();
+    // Start counting afresh, without the probes of `--auto-tune` and the
+    // like.
+    take_iterations();
+    #[cfg(feature = "profile")]
+    let profile = options.profile.as_ref().map(|_| Profile::start()).transpose()
+        .map_err(|error| Failure::Invalid(format!("error starting the profiler: {}", error)))?;
+    let pixels = if options.bit_depth == 1 {
+        // Only membership matters; shadings, supersampling and masks are
+        // rejected above. Members are black, everything else white.
+        render_membership(bounds, view, options.limit).iter()
+            .map(|&member| if member { 0 } else { 255 })
+            .collect()
+    } else if options.bit_depth == 16 {
+        // PNG stores 16-bit samples big-endian, whatever `--endian` says.
+        encode_samples(&tone_map_16(&render_field(bounds, view, options.limit), options.limit),
+                       Endian::Big)
+    } else if options.normal_map {
+        // Members of the set are a plateau at the iteration limit.
+        let heights: Vec<f64> = smooth_field(bounds, view, options.limit, options.smooth_extra)
+            .iter()
+            .map(|count| count.unwrap_or(options.limit as f64))
+            .collect();
+        normal_map(&heights, bounds, 1.0)
+    } else if let (Some(interval), Some(path)) = (options.preview_interval, path.as_ref()) {
+        render_image_with_preview(bounds, view, options.shading(), options.limit, coloring,
+                                  options.sampling(), mask.as_ref(), threading, interval,
+                                  |pixels| save_image(path, pixels, bounds, color_type))?
+    } else if options.verify {
+        render_verified(bounds, view, options.shading(), options.limit, coloring, options.sampling(),
+                        mask.as_ref(), threading)?
+    } else if let Some(ref julia) = julia {
+        render_image_map(julia, bounds, view, options.limit, coloring)?
+    } else if options.auto_contrast {
+        render_image_auto_contrast(bounds, view, options.limit, coloring)?
+    } else if options.mariani_silver {
+        let (pixels, computed) = render_image_mariani_silver(bounds, view, options.limit, coloring)?;
+        eprintln!("mariani-silver: iterated {} of {} pixels", computed, bounds.pixel_count());
+        pixels
+    } else if options.pausable {
+        let pause = Arc::new(Pause::new());
+        listen_for_pauses(pause.clone());
+        eprintln!("press Enter to pause or resume the render");
+        render_image_pausable(bounds, view, options.shading(), options.limit, coloring,
+                              options.sampling(), mask.as_ref(), threading, &pause)?
+    } else if deep {
+        #[cfg(feature = "high-precision")]
+        let pixels = {
+            let smooth_extra = match options.shading() {
+                Shading::Smooth { extra } => Some(extra),
+                _ => None,
+            };
+            render_image_deep(bounds, view, smooth_extra, options.limit, coloring, threading)?
+        };
+        // Never taken: `deep_fallback` is false without the feature.
+        #[cfg(not(feature = "high-precision"))]
+        let pixels = render_image(bounds, view, options.shading(), options.limit, coloring,
+                                  options.sampling(), mask.as_ref(), threading)?;
+        pixels
+    } else {
+        render_image(bounds, view, options.shading(), options.limit, coloring, options.sampling(),
+                     mask.as_ref(), threading)?
+    };
+
+    let elapsed = start.elapsed();
+    let iterations = take_iterations();
+
+    #[cfg(feature = "profile")]
+    if let (Some(profile), Some(profile_path)) = (profile, &options.profile) {
+        profile.save(profile_path).map_err(|error| {
+            Failure::Invalid(format!("error writing the profile to `{}`: {}", profile_path.display(),
+                                     error))
+        })?;
+        eprintln!("{}", painter.success(&format!("wrote the profile to {}", profile_path.display())));
+    }
+
+    // A render all of one color shows nothing of the set, and is most
+    // likely a mistaken view. Under `solid` it is written as one pixel of
+    // that color, without finishing or encoding the full image.
+    let solid = match options.warn_empty {
+        EmptyView::Ignore => None,
+        EmptyView::Warn | EmptyView::Solid => solid_color(&pixels, channels).map(<[u8]>::to_vec),
+    };
+    if solid.is_some() {
+        eprintln!("{}", painter.error("warning: the image is a single solid color; the view holds \
+                                       no structure of the set"));
+    }
+    let solid = solid.filter(|_| options.warn_empty == EmptyView::Solid);
+
+    let mut pixels = pixels;
+    let decorated = decorated && solid.is_none();
+    let field = if decorated || options.heatmap.is_some() || options.raw_dump.is_some()
+        || options.escape_count_histogram.is_some()
+    {
+        render_field(bounds, view, options.limit)
+    } else {
+        Vec::new()
+    };
+    if let Some(ref heatmap_path) = options.heatmap {
+        save_image(heatmap_path, &heatmap(&field, options.limit), bounds, ColorType::Gray(8))?;
+    }
+    if let Some(ref legend_path) = options.legend {
+        let (key, key_bounds) = legend(coloring, options.limit, view);
+        save_image(legend_path, &key, key_bounds, ColorType::RGB(8))?;
+    }
+    if let Some(ref exr_path) = options.potential_exr {
+        let potential = potential_field(bounds, view, options.limit, options.smooth_extra);
+        save_exr(exr_path, &potential, bounds)?;
+    }
+    if let Some(ref report_path) = options.escape_count_histogram {
+        let report = escape_count_report(&field, options.limit, options.histogram_bucket);
+        if report_path.as_os_str() == "-" {
+            print!("{}", report);
+        } else {
+            write_atomically(report_path, report.as_bytes()).map_err(RenderError::Io)?;
+        }
+    }
+    if let Some(ref stats_path) = options.stats_json {
+        let stats = RenderStats {
+            elapsed,
+            bounds,
+            view,
+            limit: options.limit,
+            samples: options.sampling().samples(),
+            threads: threading.threads,
+            iterations,
+        };
+        if stats_path.as_os_str() == "-" {
+            print!("{}", stats.to_json());
+        } else {
+            write_atomically(stats_path, stats.to_json().as_bytes()).map_err(RenderError::Io)?;
+        }
+    }
+    if let Some(ref raw_path) = options.raw_dump {
+        let samples = match options.quantize_bits {
+            Some(bits) => pack_samples(&tone_map_bits(&field, options.limit, bits), bits, bounds),
+            None => encode_samples(&tone_map_16(&field, options.limit), options.endian),
+        };
+        write_atomically(raw_path, &samples).map_err(RenderError::Io)?;
+    }
+    let (pixels, bounds) = match solid {
+        Some(color) => (color, PixelBounds::new(1, 1)),
+        None => {
+            if let Some(strength) = options.edges {
+                // Members of the set are a plateau at the iteration
+                // limit, as for `--normal-map`.
+                let counts: Vec<f64> = field.iter()
+                    .map(|escape| escape.count().unwrap_or(options.limit) as f64)
+                    .collect();
+                emphasize_edges(&mut pixels, bounds, channels, &sobel(&counts, bounds),
+                                strength);
             }
-        });
+            if let Some(interval) = options.contours {
+                draw_contours(&mut pixels, bounds, channels, &field, interval,
+                              options.contour_color);
+            }
+            if let Some(strength) = options.vignette {
+                vignette(&mut pixels, bounds, channels, strength);
+            }
+            if let Some(radius) = options.blur {
+                pixels = blur(pixels, bounds, channels, radius);
+            }
+            if let Some(corner) = options.overlay {
+                draw_overlay(&mut pixels, bounds, channels, view, corner);
+            }
+
+            let (pixels, bounds) = if options.autocrop {
+                autocrop(pixels, bounds, channels)
+            } else {
+                (pixels, bounds)
+            };
+            match options.tile_output {
+                Some((rows, columns)) => tile(pixels, bounds, channels, rows, columns),
+                None => (pixels, bounds),
+            }
+        }
+    };
+    let thumbnail = options.thumbnail.map(|divisor| downscale(&pixels, bounds, channels, divisor));
+    let pixels = if format == OutputFormat::Gray1 { pack_bits(&pixels, bounds) } else { pixels };
+    assert_eq!(pixels.len(), format.encoded_len(bounds), "buffer doesn't match {:?}", format);
+    // The colors to quantize to, and the pixels as indices into them.
+    let indexed = options.indexed.map(|colors| {
+        let entries = palette::indexed_entries(options.palette.as_deref(), colors);
+        let indices = quantize(&pixels, channels, &entries);
+        (entries, indices)
+    });
+
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let uri = match indexed {
+                Some((ref entries, ref indices)) => {
+                    let mut png = Vec::new();
+                    encode_indexed_png(&mut png, indices, bounds, entries).map_err(RenderError::Encode)?;
+                    data_uri(&png)
+                }
+                None => png_data_uri(&pixels, bounds, color_type).map_err(RenderError::Encode)?,
+            };
+            println!("{}", uri);
+            return Ok(());
+        }
+    };
+
+    match indexed {
+        Some((ref entries, ref indices)) => save_indexed_png(&path, indices, bounds, entries)?,
+        None => save_image_with(&path, &pixels, bounds, color_type, options.png)?,
+    }
+    if let Some((thumbnail, thumbnail_bounds)) = thumbnail {
+        let thumbnail_path = thumbnail_path(&path);
+        save_image_with(&thumbnail_path, &thumbnail, thumbnail_bounds, color_type, options.png)?;
+        println!("{}", painter.success(&format!("wrote the thumbnail to {}",
+                                                thumbnail_path.display())));
     }
 
-    write_image(&args[1], &pixels, bounds)
-        .expect("error writing the PNG file");
+    println!("{}", painter.success(&success_message(&path)));
+    Ok(())
+}
 
-    writeln!(std::io::stdout(),
-        "\n Mandlebrot Program Finished! Program exited successfully!\n Check your Parent Directory for the resulting image!\n\n")
-        .unwrap();
-    std::process::exit(0);
+/// --dry-run test: the plan is printed and no image written.
+#[test]
+fn test_dry_run() {
+    let path = env::temp_dir().join(format!("mandelbrot-dry-run-test-{}.png", std::process::id()));
+    let args: Vec<String> = ["mandlebrot", path.to_str().unwrap(), "80x60", "-2,1", "1,-1", "--dry-run"]
+        .iter().map(|s| s.to_string()).collect();
+    let painter = Painter::from_parts(true, None, false, false);
+    assert!(run(&args, &painter).is_ok());
+    assert!(!path.exists());
+
+    let (_, options) = parse_args(&["--palette".to_string(), "fire".to_string()]).unwrap();
+    let view = ComplexView::new(num::Complex::new(-2.0, 1.0), num::Complex::new(1.0, -1.0));
+    let threading = Threading { threads: 4, chunk_rows: Some(8) };
+    let summary = plan(Some(&path), PixelBounds::new(1024, 512), view, &options, threading,
+                       1024 * 512 * 3, Duration::from_millis(1250));
+    assert_eq!(summary, format!("file: {}\n\
+                                 pixels: 1024x512\n\
+                                 upper left: -2e0,1e0\n\
+                                 lower right: 1e0,-1e0\n\
+                                 iterations: 255\n\
+                                 threads: 4, 8 rows at a time\n\
+                                 palette: fire\n\
+                                 memory: 1.5 MiB for the image\n\
+                                 estimated time: 1.25s\n", path.display()));
+    let summary = plan(None, PixelBounds::new(1, 1), view, &Options::default(), Threading::new(2),
+                       1, Duration::ZERO);
+    assert!(summary.starts_with("file: (stdout)\n"));
+    assert!(summary.contains("threads: 2\npalette: none (grayscale)\n"));
+}
+
+/// --force test: an existing file is only written over when asked.
+#[test]
+fn test_force() {
+    let path = env::temp_dir().join(format!("mandelbrot-force-test-{}.png", std::process::id()));
+    std::fs::write(&path, b"precious").unwrap();
+    let mut args: Vec<String> = ["mandlebrot", path.to_str().unwrap(), "8x6", "-2,1", "1,-1"]
+        .iter().map(|s| s.to_string()).collect();
+    let painter = Painter::from_parts(true, None, false, false);
+
+    match run(&args, &painter) {
+        Err(Failure::Invalid(message)) => assert!(message.contains("--force"), "{}", message),
+        _ => panic!("rendered over an existing file"),
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), b"precious");
+
+    args.push("--force".to_string());
+    assert!(run(&args, &painter).is_ok());
+    assert_eq!(image_bounds(&path).unwrap(), PixelBounds::new(8, 6));
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// svg output test: the boundary is traced, and options with nothing to
+/// apply to are rejected rather than ignored.
+#[test]
+fn test_svg_output() {
+    let path = env::temp_dir().join(format!("mandelbrot-svg-test-{}.svg", std::process::id()));
+    let args: Vec<String> = ["mandlebrot", path.to_str().unwrap(), "16x12", "-2,1", "1,-1", "--force"]
+        .iter().map(|s| s.to_string()).collect();
+    let painter = Painter::from_parts(true, None, false, false);
+
+    assert!(run(&args, &painter).is_ok());
+    assert!(std::fs::read_to_string(&path).unwrap().contains("<svg"));
+    std::fs::remove_file(&path).unwrap();
+
+    // A mask that doesn't exist is never looked for: the combination is
+    // what's wrong.
+    for extra in [&["--frames", "3"][..], &["--stats-json", "stats.json"], &["--palette", "fire"],
+                  &["--aa", "2"], &["--mask", "no-such-mask.png"], &["--smooth"], &["--overlay"]] {
+        let mut rejected = args.clone();
+        rejected.extend(extra.iter().map(|s| s.to_string()));
+        match run(&rejected, &painter) {
+            Err(Failure::Invalid(message)) => assert!(message.contains(".svg"), "{}", message),
+            _ => panic!("{:?} was accepted", extra),
+        }
+        assert!(!path.exists());
+    }
+}
+
+/// --bit-depth and --normal-map test: the options their renders can't
+/// honor are rejected rather than ignored.
+#[test]
+fn test_direct_render_options() {
+    let path = env::temp_dir().join(format!("mandelbrot-direct-test-{}.png", std::process::id()));
+    let args: Vec<String> = ["mandlebrot", path.to_str().unwrap(), "8x6", "-2,1", "1,-1", "--force"]
+        .iter().map(|s| s.to_string()).collect();
+    let painter = Painter::from_parts(true, None, false, false);
+
+    for mode in [&["--bit-depth", "1"][..], &["--bit-depth", "16"], &["--normal-map"]] {
+        let mut accepted = args.clone();
+        accepted.extend(mode.iter().map(|s| s.to_string()));
+        assert!(run(&accepted, &painter).is_ok(), "{:?}", mode);
+        for extra in [&["--mask", "mask.png"][..], &["--aa", "3"], &["--lyapunov"]] {
+            let mut rejected = accepted.clone();
+            rejected.extend(extra.iter().map(|s| s.to_string()));
+            assert!(matches!(run(&rejected, &painter), Err(Failure::Invalid(_))),
+                    "{:?} with {:?} was accepted", mode, extra);
+        }
+    }
+
+    // The normal map is of the smooth counts anyway.
+    let mut smooth = args.clone();
+    smooth.extend(["--normal-map".to_string(), "--smooth".to_string()]);
+    assert!(run(&smooth, &painter).is_ok());
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// --warn-empty test: a view wholly inside the set is caught, and under
+/// `solid` written as a single pixel.
+#[test]
+fn test_warn_empty() {
+    let path = env::temp_dir().join(format!("mandelbrot-warn-empty-test-{}.png", std::process::id()));
+    let mut args: Vec<String> = ["mandlebrot", path.to_str().unwrap(), "16x12", "-0.1,0.1", "0.1,-0.1",
+                                 "--force"]
+        .iter().map(|s| s.to_string()).collect();
+    let painter = Painter::from_parts(true, None, false, false);
+
+    assert!(run(&args, &painter).is_ok());
+    assert_eq!(image_bounds(&path).unwrap(), PixelBounds::new(16, 12));
+
+    args.extend(["--warn-empty".to_string(), "solid".to_string()]);
+    assert!(run(&args, &painter).is_ok());
+    assert_eq!(image_bounds(&path).unwrap(), PixelBounds::new(1, 1));
+
+    // The check is on the render itself, before an overlay or vignette
+    // draws on it, and the pixel written is the set's black.
+    let mut overlaid = args.clone();
+    overlaid.extend(["--overlay".to_string(), "--vignette".to_string(), "0.5".to_string()]);
+    assert!(run(&overlaid, &painter).is_ok());
+    assert_eq!(image_bounds(&path).unwrap(), PixelBounds::new(1, 1));
+    assert_eq!(image::open(&path).unwrap().raw_pixels(), vec![0]);
+    let mut palette = args.clone();
+    palette.extend(["--palette".to_string(), "fire".to_string(), "--overlay".to_string()]);
+    assert!(run(&palette, &painter).is_ok());
+    assert_eq!(image::open(&path).unwrap().raw_pixels(), vec![0, 0, 0]);
+
+    // A view with structure in it is written in full.
+    args[3] = "-2,1".to_string();
+    args[4] = "1,-1".to_string();
+    assert!(run(&args, &painter).is_ok());
+    assert_eq!(image_bounds(&path).unwrap(), PixelBounds::new(16, 12));
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// high-precision fallback test: `run` renders a view 1e-14 wide, past
+/// f64 resolution, in double-double precision.
+#[cfg(feature = "high-precision")]
+#[test]
+fn test_deep_fallback() {
+    let path = env::temp_dir().join(format!("mandelbrot-deep-test-{}.png", std::process::id()));
+    let (re, im) = (-0.743_643_887_037_151, 0.131_825_904_205_33);
+    let view = ComplexView::new(num::Complex::new(re, im + 0.375e-14),
+                                num::Complex::new(re + 1e-14, im - 0.375e-14));
+    let bounds = PixelBounds::new(32, 24);
+    assert!(exceeds_f64_resolution(bounds, view));
+    let args: Vec<String> = vec!["mandlebrot".to_string(), path.to_str().unwrap().to_string(),
+                                 "32x24".to_string(), format_complex(view.upper_left),
+                                 format_complex(view.lower_right), "--iterations".to_string(),
+                                 "5000".to_string(), "--force".to_string()];
+    let painter = Painter::from_parts(true, None, false, false);
+    let (_, options) = parse_args(&args[5..]).unwrap();
+    assert!(deep_fallback(&options, false));
+    assert!(run(&args, &painter).is_ok());
+
+    let written = image::open(&path).unwrap().raw_pixels();
+    let threading = Threading::new(1);
+    let deep = render_image_deep(bounds, view, None, 5000, Coloring::Gray, threading).unwrap();
+    assert_eq!(written, deep);
+    let plain = render_image(bounds, view, Shading::EscapeTime, 5000, Coloring::Gray,
+                             Supersampling::none(), None, threading).unwrap();
+    assert!(plain != deep);
+    std::fs::remove_file(&path).unwrap();
+
+    // Options the double-double renderer can't follow keep f64.
+    let (_, options) = parse_args(&["--aa".to_string(), "2".to_string()]).unwrap();
+    assert!(!deep_fallback(&options, false));
+    assert!(!deep_fallback(&Options::default(), true));
+}
+
+/// --stats-json test: the iterations reported are those the render ran,
+/// and renders it can't count are turned away.
+#[test]
+fn test_stats_json() {
+    let stats_path = env::temp_dir().join(format!("mandelbrot-stats-test-{}.json", std::process::id()));
+    let path = stats_path.with_extension("png");
+    let args: Vec<String> = ["mandlebrot", path.to_str().unwrap(), "40x30", "-2,1", "1,-1",
+                             "--threads", "3", "--force", "--stats-json",
+                             stats_path.to_str().unwrap()]
+        .iter().map(|s| s.to_string()).collect();
+    let painter = Painter::from_parts(true, None, false, false);
+    assert!(run(&args, &painter).is_ok());
+
+    let view = ComplexView::new(num::Complex::new(-2.0, 1.0), num::Complex::new(1.0, -1.0));
+    let expected: u64 = render_field(PixelBounds::new(40, 30), view, LIMIT).iter()
+        .map(|escape| escape.iterations(LIMIT) as u64)
+        .sum();
+    let json = std::fs::read_to_string(&stats_path).unwrap();
+    assert!(json.contains(&format!("\"iterations\": {},", expected)), "{}", json);
+    std::fs::remove_file(&stats_path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    for extra in [&["--smooth"][..], &["--seed-julia-from-mandelbrot", "10,10"], &["--mariani-silver"]] {
+        let mut rejected = args.clone();
+        rejected.extend(extra.iter().map(|s| s.to_string()));
+        assert!(matches!(run(&rejected, &painter), Err(Failure::Invalid(_))), "{:?} was counted", extra);
+    }
 }
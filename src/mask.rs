@@ -0,0 +1,86 @@
+use image::{self, ImageResult};
+use std::path::Path;
+
+use crate::view::PixelBounds;
+
+/// Which pixels of an image to render. Pixels the mask doesn't cover are
+/// skipped entirely and left as background.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mask {
+    bounds: PixelBounds,
+    covered: Vec<bool>,
+}
+
+impl Mask {
+    /// A mask covering every pixel of 'pixels', one gray byte per pixel,
+    /// that isn't black.
+    pub fn from_gray(pixels: &[u8], bounds: PixelBounds) -> Mask {
+        assert!(pixels.len() == bounds.pixel_count());
+        Mask { bounds, covered: pixels.iter().map(|&pixel| pixel != 0).collect() }
+    }
+
+    /// Load the image at 'path' as a mask for an image of size 'bounds'.
+    /// Colors are converted to gray first, and an image of some other
+    /// size is stretched to 'bounds' with nearest-neighbour sampling, so
+    /// that no pixel is half covered.
+    pub fn load(path: &Path, bounds: PixelBounds) -> ImageResult<Mask> {
+        let gray = image::open(path)?.to_luma();
+        let (width, height) = gray.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        if (width, height) == (bounds.width, bounds.height) {
+            return Ok(Mask::from_gray(&gray.into_raw(), bounds));
+        }
+
+        // Take each pixel from the one under its center in the image.
+        let gray = gray.into_raw();
+        let mut covered = Vec::with_capacity(bounds.pixel_count());
+        for row in 0..bounds.height {
+            let source_row = (2 * row + 1) * height / (2 * bounds.height);
+            for column in 0..bounds.width {
+                let source_column = (2 * column + 1) * width / (2 * bounds.width);
+                covered.push(gray[source_row * width + source_column] != 0);
+            }
+        }
+        Ok(Mask { bounds, covered })
+    }
+
+    /// The size of image this mask is for.
+    pub fn bounds(&self) -> PixelBounds {
+        self.bounds
+    }
+
+    /// Which pixels of row 'row' the mask covers, left to right.
+    pub fn row(&self, row: usize) -> &[bool] {
+        &self.covered[row * self.bounds.width .. (row + 1) * self.bounds.width]
+    }
+
+    /// Number of pixels the mask covers.
+    pub fn count(&self) -> usize {
+        self.covered.iter().filter(|&&covered| covered).count()
+    }
+}
+
+/// Mask::load test
+#[test]
+fn test_mask_load() {
+    use image::{ImageBuffer, Luma};
+    use std::env;
+    use std::process;
+
+    // A 4x2 mask whose left half is black.
+    let pixels = vec![0, 0, 200, 9, 0, 0, 255, 1];
+    let path = env::temp_dir().join(format!("mandelbrot-mask-test-{}.png", process::id()));
+    ImageBuffer::<Luma<u8>, _>::from_raw(4, 2, pixels.clone()).unwrap().save(&path).unwrap();
+
+    let mask = Mask::load(&path, PixelBounds::new(4, 2)).unwrap();
+    assert_eq!(mask, Mask::from_gray(&pixels, PixelBounds::new(4, 2)));
+    assert_eq!(mask.row(1), &[false, false, true, true]);
+
+    // Stretched to twice the size, each pixel becomes a 2x2 block.
+    let mask = Mask::load(&path, PixelBounds::new(8, 4)).unwrap();
+    assert_eq!(mask.count(), 16);
+    assert_eq!(mask.row(3), &[false, false, false, false, true, true, true, true]);
+
+    let _ = std::fs::remove_file(&path);
+    assert!(Mask::load(&path, PixelBounds::new(4, 2)).is_err());
+}
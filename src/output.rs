@@ -0,0 +1,1003 @@
+use image::{self, ColorType, GenericImage, ImageResult};
+use png::HasParameters;
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::error::RenderError;
+use crate::view::PixelBounds;
+
+/// The layouts a rendered image can be written in. Each one fixes both
+/// how many bytes a pixel takes in the buffer the image is rendered into
+/// and the `ColorType` the encoder is told, so the two can't disagree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Black and white, rendered a byte per pixel and packed by
+    /// `pack_bits` into a bit per pixel for the encoder.
+    Gray1,
+    Gray8,
+    /// Two bytes per pixel, big-endian, as PNG stores them.
+    Gray16,
+    Rgb8,
+    Rgba8,
+}
+
+impl OutputFormat {
+    /// The color type to pass the encoder.
+    pub fn color_type(self) -> ColorType {
+        match self {
+            OutputFormat::Gray1 => ColorType::Gray(1),
+            OutputFormat::Gray8 => ColorType::Gray(8),
+            OutputFormat::Gray16 => ColorType::Gray(16),
+            OutputFormat::Rgb8 => ColorType::RGB(8),
+            OutputFormat::Rgba8 => ColorType::RGBA(8),
+        }
+    }
+
+    /// Bytes per pixel of the rendered buffer, which postprocessing moves
+    /// about as a unit.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            OutputFormat::Gray1 | OutputFormat::Gray8 => 1,
+            OutputFormat::Gray16 => 2,
+            OutputFormat::Rgb8 => 3,
+            OutputFormat::Rgba8 => 4,
+        }
+    }
+
+    /// The length of the buffer the encoder takes for an image of size
+    /// 'bounds': every row starts on a fresh byte.
+    pub fn encoded_len(self, bounds: PixelBounds) -> usize {
+        match self {
+            OutputFormat::Gray1 => bounds.width.div_ceil(8) * bounds.height,
+            _ => bounds.pixel_count() * self.bytes_per_pixel(),
+        }
+    }
+}
+
+/// How hard the zlib stream holding a PNG's pixels is compressed:
+/// harder makes smaller files, at the cost of a slower encode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best,
+}
+
+/// The filter every row of a PNG goes through before it is compressed.
+/// Filters store each byte as its difference from a neighbor's, which
+/// smooth gradients, such as the bands around the set, turn into long
+/// runs that compress well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PngFilter {
+    None,
+    /// Difference from the pixel to the left.
+    Sub,
+    /// Difference from the pixel above.
+    Up,
+    /// Difference from the mean of the pixels to the left and above.
+    Average,
+    /// Difference from whichever of left, above and above left is
+    /// closest to left + above - above left.
+    Paeth,
+    /// Whichever of the above suits each row best, by the usual guess of
+    /// the one whose bytes, taken as signed, add up smallest in size.
+    Adaptive,
+}
+
+/// How a PNG is encoded. The default, the fast compression and `Sub`
+/// filter the `png` crate always uses, gives the same files as before
+/// there was a choice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PngOptions {
+    pub compression: PngCompression,
+    pub filter: PngFilter,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions {
+            compression: PngCompression::Fast,
+            filter: PngFilter::Sub,
+        }
+    }
+}
+
+/// Write the buffer 'pixels', whose dimensions are given by 'bounds', to
+/// the file at 'path' as a PNG. 'color' says how the bytes of 'pixels'
+/// are laid out, e.g. `ColorType::Gray(8)` for one byte per pixel.
+/// 'path' can be anything that converts to a `Path`, including ones that
+/// aren't valid UTF-8.
+pub fn write_image<P: AsRef<Path>>(path: P, pixels: &[u8], bounds: PixelBounds, color: ColorType)
+    -> Result<(), std::io::Error> {
+        write_image_with(path, pixels, bounds, color, PngOptions::default())
+}
+
+/// `write_image`, encoding the PNG as 'options' says.
+pub fn write_image_with<P: AsRef<Path>>(path: P, pixels: &[u8], bounds: PixelBounds, color: ColorType,
+                                        options: PngOptions) -> Result<(), std::io::Error> {
+        let output = File::create(path)?;
+        encode_png_with(output, pixels, bounds, color, options)
+}
+
+/// Write the PNG encoding of 'pixels' to 'output', as `write_image`
+/// describes. 'output' can be anything that implements `Write`, such as
+/// a file or a `Vec<u8>` in memory.
+pub fn encode_png<W: Write>(output: W, pixels: &[u8], bounds: PixelBounds, color: ColorType)
+    -> Result<(), std::io::Error> {
+        encode_png_with(output, pixels, bounds, color, PngOptions::default())
+}
+
+/// `encode_png`, compressing and filtering as 'options' says. Neither
+/// the `image` nor the `png` crate lets either be chosen, so the rows
+/// are filtered and compressed here and handed to `png` as a finished
+/// `IDAT` chunk.
+pub fn encode_png_with<W: Write>(output: W, pixels: &[u8], bounds: PixelBounds, color: ColorType,
+                                 options: PngOptions) -> Result<(), std::io::Error> {
+    let (png_color, depth, channels) = match color {
+        ColorType::Gray(depth) => (png::ColorType::Grayscale, depth, 1),
+        ColorType::RGB(depth) => (png::ColorType::RGB, depth, 3),
+        ColorType::GrayA(depth) => (png::ColorType::GrayscaleAlpha, depth, 2),
+        ColorType::RGBA(depth) => (png::ColorType::RGBA, depth, 4),
+        ColorType::Palette(_) => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "use `encode_indexed_png` for indexed color"));
+        }
+    };
+    let bit_depth = png::BitDepth::from_u8(depth).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("unsupported bit depth {}", depth))
+    })?;
+    let row_len = (bounds.width * channels * depth as usize).div_ceil(8);
+    if bounds.height == 0 || row_len == 0 || pixels.len() < row_len * bounds.height {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not enough image data"));
+    }
+
+    let mut encoder = png::Encoder::new(output, bounds.width as u32, bounds.height as u32);
+    encoder.set(png_color).set(bit_depth);
+    let mut writer = encoder.write_header()?;
+    let compression = match options.compression {
+        PngCompression::Fast => deflate::Compression::Fast,
+        PngCompression::Default => deflate::Compression::Default,
+        PngCompression::Best => deflate::Compression::Best,
+    };
+    let mut zlib = deflate::write::ZlibEncoder::new(Vec::new(), compression);
+    // Filters work a whole pixel apart, or a byte apart below 8 bits.
+    let bytes_per_pixel = (channels * depth as usize).div_ceil(8);
+    let mut previous = vec![0; row_len];
+    for row in pixels[.. row_len * bounds.height].chunks(row_len) {
+        let (filter, filtered) = filter_row(options.filter, bytes_per_pixel, &previous, row);
+        zlib.write_all(&[filter])?;
+        zlib.write_all(&filtered)?;
+        previous.copy_from_slice(row);
+    }
+    writer.write_chunk(png::chunk::IDAT, &zlib.finish()?)?;
+    Ok(())
+}
+
+/// Filter 'row' for a PNG, the row above it being 'previous' (all zeros
+/// for the first), returning the filter type byte the row starts with
+/// and the filtered bytes.
+fn filter_row(filter: PngFilter, bytes_per_pixel: usize, previous: &[u8], row: &[u8]) -> (u8, Vec<u8>) {
+    let filtered = |filter: u8| -> Vec<u8> {
+        (0 .. row.len()).map(|i| {
+            let left = if i >= bytes_per_pixel { row[i - bytes_per_pixel] } else { 0 };
+            let above = previous[i];
+            let above_left = if i >= bytes_per_pixel { previous[i - bytes_per_pixel] } else { 0 };
+            let prediction = match filter {
+                0 => 0,
+                1 => left,
+                2 => above,
+                3 => ((left as u16 + above as u16) / 2) as u8,
+                _ => paeth(left, above, above_left),
+            };
+            row[i].wrapping_sub(prediction)
+        }).collect()
+    };
+    match filter {
+        PngFilter::None => (0, filtered(0)),
+        PngFilter::Sub => (1, filtered(1)),
+        PngFilter::Up => (2, filtered(2)),
+        PngFilter::Average => (3, filtered(3)),
+        PngFilter::Paeth => (4, filtered(4)),
+        PngFilter::Adaptive => (0 ..= 4)
+            .map(|filter| (filter, filtered(filter)))
+            .min_by_key(|(_, bytes)| bytes.iter().map(|&byte| (byte as i8).unsigned_abs() as u64).sum::<u64>())
+            .unwrap(),
+    }
+}
+
+/// The Paeth predictor of PNG's filter type 4: whichever of 'left',
+/// 'above' and 'above_left' is closest to left + above - above left.
+fn paeth(left: u8, above: u8, above_left: u8) -> u8 {
+    let estimate = left as i16 + above as i16 - above_left as i16;
+    let (to_left, to_above, to_above_left) = ((estimate - left as i16).abs(),
+                                              (estimate - above as i16).abs(),
+                                              (estimate - above_left as i16).abs());
+    if to_left <= to_above && to_left <= to_above_left {
+        left
+    } else if to_above <= to_above_left {
+        above
+    } else {
+        above_left
+    }
+}
+
+/// Write an indexed-color PNG to 'output': 'indices' holds one byte per
+/// pixel, the position in 'entries' of its color. The `image` crate's
+/// encoder has no way to pass the palette along, so this goes through
+/// `png` directly. Only as many colors as 'entries' has are stored,
+/// which with few of them makes for a much smaller file than RGB.
+pub fn encode_indexed_png<W: Write>(output: W, indices: &[u8], bounds: PixelBounds,
+                                    entries: &[[u8; 3]]) -> io::Result<()> {
+    assert!(!entries.is_empty() && entries.len() <= 256);
+    let mut encoder = png::Encoder::new(output, bounds.width as u32, bounds.height as u32);
+    encoder.set(png::ColorType::Indexed).set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let plte: Vec<u8> = entries.iter().flat_map(|entry| entry.iter().cloned()).collect();
+    writer.write_chunk(png::chunk::PLTE, &plte)?;
+    writer.write_image_data(indices)?;
+    Ok(())
+}
+
+/// The order of the two bytes of a 16-bit sample.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    /// Least significant byte first, as on x86 and most ARM machines.
+    Little,
+    /// Most significant byte first. PNG always stores 16-bit samples
+    /// this way.
+    Big,
+}
+
+/// Lay 16-bit 'samples' out as bytes in the order 'endian' says.
+///
+/// PNG requires big-endian samples, so 16-bit PNGs always go through
+/// this with `Endian::Big`; only raw dumps, which carry no header to say
+/// which order they are in, let the order be chosen.
+pub fn encode_samples(samples: &[u16], endian: Endian) -> Vec<u8> {
+    samples.iter()
+        .flat_map(|&sample| match endian {
+            Endian::Little => sample.to_le_bytes(),
+            Endian::Big => sample.to_be_bytes(),
+        })
+        .collect()
+}
+
+/// Encode 'pixels' as a PNG and return it as a `data:` URI, ready to
+/// paste into a web page or notebook.
+pub fn png_data_uri(pixels: &[u8], bounds: PixelBounds, color: ColorType) -> io::Result<String> {
+    let mut png = Vec::new();
+    encode_png(&mut png, pixels, bounds, color)?;
+    Ok(data_uri(&png))
+}
+
+/// The `data:` URI of the already encoded PNG 'png'.
+pub fn data_uri(png: &[u8]) -> String {
+    format!("data:image/png;base64,{}", base64_encode(png))
+}
+
+/// Pack a buffer of one gray byte per pixel into the one bit per pixel
+/// that `ColorType::Gray(1)` expects: a pixel's bit is set (white) if its
+/// byte is 128 or more, and clear (black) otherwise. Bits fill each byte
+/// from the most significant end, and every row starts on a fresh byte,
+/// so the bits left over at the end of a row are padding.
+pub fn pack_bits(pixels: &[u8], bounds: PixelBounds) -> Vec<u8> {
+    assert!(pixels.len() == bounds.pixel_count());
+
+    let row_bytes = bounds.width.div_ceil(8);
+    let mut packed = vec![0; row_bytes * bounds.height];
+    for (row, row_pixels) in pixels.chunks(bounds.width.max(1)).enumerate() {
+        for (column, &pixel) in row_pixels.iter().enumerate() {
+            if pixel >= 128 {
+                packed[row * row_bytes + column / 8] |= 0x80 >> (column % 8);
+            }
+        }
+    }
+    packed
+}
+
+/// Pack 'levels', one per pixel of an image of size 'bounds' and each
+/// below `2^bits`, into 'bits' bits apiece, 'bits' from 1 to 8. As for
+/// `pack_bits`, levels fill each byte from the most significant end and
+/// every row starts on a fresh byte, so a row takes `width * bits / 8`
+/// bytes, rounded up.
+pub fn pack_samples(levels: &[u8], bits: u8, bounds: PixelBounds) -> Vec<u8> {
+    assert!(levels.len() == bounds.pixel_count());
+    assert!((1..=8).contains(&bits));
+
+    let bits = bits as usize;
+    let row_bytes = (bounds.width * bits).div_ceil(8);
+    let mut packed = vec![0; row_bytes * bounds.height];
+    for (row, row_levels) in levels.chunks(bounds.width.max(1)).enumerate() {
+        for (column, &level) in row_levels.iter().enumerate() {
+            let bit = column * bits;
+            // Unless 'bits' divides 8, a level can straddle two bytes, so
+            // it is shifted into place in a window of two.
+            let window = ((level as u16) << (16 - bits)) >> (bit % 8);
+            let at = row * row_bytes + bit / 8;
+            packed[at] |= (window >> 8) as u8;
+            if window & 0xff != 0 {
+                packed[at + 1] |= window as u8;
+            }
+        }
+    }
+    packed
+}
+
+/// The levels `pack_samples` packed at 'bits' bits apiece into 'packed',
+/// for an image of size 'bounds'.
+pub fn unpack_samples(packed: &[u8], bits: u8, bounds: PixelBounds) -> Vec<u8> {
+    assert!((1..=8).contains(&bits));
+
+    let bits = bits as usize;
+    let row_bytes = (bounds.width * bits).div_ceil(8);
+    assert!(packed.len() == row_bytes * bounds.height);
+    let mask = (1u16 << bits) - 1;
+    let mut levels = Vec::with_capacity(bounds.pixel_count());
+    for row in packed.chunks(row_bytes.max(1)).take(bounds.height) {
+        for column in 0..bounds.width {
+            let bit = column * bits;
+            let window = (row[bit / 8] as u16) << 8 | *row.get(bit / 8 + 1).unwrap_or(&0) as u16;
+            levels.push((window >> (16 - bits - bit % 8) & mask) as u8);
+        }
+    }
+    levels
+}
+
+/// Whether 'path' names a Netpbm file, written by `write_ppm` instead of
+/// as a PNG.
+pub fn is_netpbm(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => ["ppm", "pgm", "pnm"].contains(&extension.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+/// Where to save the thumbnail of an image saved to 'path': `_thumb`
+/// goes before the extension, so `out.png` becomes `out_thumb.png`.
+pub fn thumbnail_path(path: &Path) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push("_thumb");
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// Whether 'path' names an SVG file, which gets the traced outline of
+/// the set instead of pixels.
+pub fn is_svg(path: &Path) -> bool {
+    path.extension().and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"))
+}
+
+/// Save 'pixels' to 'path': as Netpbm if `is_netpbm` says so, otherwise
+/// as a PNG laid out as 'color' says. The image is encoded in memory
+/// first, so a failure to encode it (`RenderError::Encode`) is told
+/// apart from a failure to write the file (`RenderError::Io`), and
+/// leaves no half-written file behind. The file is replaced atomically,
+/// as `write_atomically` describes, so it can be saved over and over
+/// with partly rendered pixels while someone watches it.
+pub fn save_image<P: AsRef<Path>>(path: P, pixels: &[u8], bounds: PixelBounds, color: ColorType)
+    -> Result<(), RenderError> {
+    save_image_with(path, pixels, bounds, color, PngOptions::default())
+}
+
+/// `save_image`, encoding a PNG as 'options' says.
+pub fn save_image_with<P: AsRef<Path>>(path: P, pixels: &[u8], bounds: PixelBounds, color: ColorType,
+                                       options: PngOptions) -> Result<(), RenderError> {
+    let path = path.as_ref();
+    let mut encoded = Vec::new();
+    if is_netpbm(path) {
+        encode_ppm(&mut encoded, pixels, bounds)
+    } else {
+        encode_png_with(&mut encoded, pixels, bounds, color, options)
+    }.map_err(RenderError::Encode)?;
+
+    write_atomically(path, &encoded)?;
+    Ok(())
+}
+
+/// Write 'bytes' to 'path' so that anyone reading it sees either the
+/// old file or the new one in full, never a mixture: they are written to
+/// a temporary file beside it first, which is then renamed over it.
+pub fn write_atomically<P: AsRef<Path>>(path: P, bytes: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}.tmp", process::id()));
+    let temporary = PathBuf::from(temporary);
+
+    fs::write(&temporary, bytes)?;
+    fs::rename(&temporary, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary);
+    })
+}
+
+/// Save 'indices' to 'path' as an indexed-color PNG of the colors
+/// 'entries', as `encode_indexed_png` describes. Like `save_image`, the
+/// image is encoded in memory first.
+pub fn save_indexed_png<P: AsRef<Path>>(path: P, indices: &[u8], bounds: PixelBounds, entries: &[[u8; 3]])
+    -> Result<(), RenderError> {
+    let mut encoded = Vec::new();
+    encode_indexed_png(&mut encoded, indices, bounds, entries).map_err(RenderError::Encode)?;
+    write_atomically(path, &encoded)?;
+    Ok(())
+}
+
+/// Encode 'values', one per pixel in row order, as an OpenEXR image with
+/// a single 32-bit float channel `Y`. The values are stored exactly as
+/// given, with no tone mapping, for tools that do their own analysis or
+/// coloring in floating point.
+pub fn encode_exr(values: &[f32], bounds: PixelBounds) -> io::Result<Vec<u8>> {
+    use exr::prelude::{Image, SpecificChannels, WritableImage};
+
+    assert!(values.len() == bounds.pixel_count());
+    let channels = SpecificChannels::build()
+        .with_channel("Y")
+        .with_pixel_fn(|position| (values[position.y() * bounds.width + position.x()],));
+    let mut encoded = Vec::new();
+    Image::from_channels((bounds.width, bounds.height), channels)
+        .write()
+        .to_buffered(Cursor::new(&mut encoded))
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+    Ok(encoded)
+}
+
+/// Save 'values' to 'path' as `encode_exr` describes. Like `save_image`,
+/// the image is encoded in memory first and the file replaced atomically.
+pub fn save_exr<P: AsRef<Path>>(path: P, values: &[f32], bounds: PixelBounds)
+    -> Result<(), RenderError> {
+    let encoded = encode_exr(values, bounds).map_err(RenderError::Encode)?;
+    write_atomically(path, &encoded)?;
+    Ok(())
+}
+
+/// The size of the image at 'path', for rendering another to match it.
+pub fn image_bounds(path: &Path) -> ImageResult<PixelBounds> {
+    let (width, height) = image::open(path)?.dimensions();
+    Ok(PixelBounds::new(width as usize, height as usize))
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode 'bytes' as standard, padded base64 (RFC 4648).
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate()
+            .fold(0u32, |group, (i, &byte)| group | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Write 'pixels' to 'path' as a binary Netpbm image, without going
+/// through the `image` crate: a P5 (grayscale) file for one byte per
+/// pixel, or a P6 (RGB) file for three. Any other buffer length is an
+/// `InvalidInput` error.
+pub fn write_ppm<P: AsRef<Path>>(path: P, pixels: &[u8], bounds: PixelBounds) -> io::Result<()> {
+    let mut output = io::BufWriter::new(File::create(path)?);
+    encode_ppm(&mut output, pixels, bounds)?;
+    output.flush()
+}
+
+/// Write the Netpbm encoding of 'pixels' to 'output', as `write_ppm`
+/// describes.
+pub fn encode_ppm<W: Write>(output: &mut W, pixels: &[u8], bounds: PixelBounds) -> io::Result<()> {
+    let magic = if pixels.len() == bounds.pixel_count() {
+        "P5"
+    } else if pixels.len() == bounds.pixel_count() * 3 {
+        "P6"
+    } else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("{} bytes of pixels don't match a {}x{} image",
+                    pixels.len(), bounds.width, bounds.height)));
+    };
+
+    write!(output, "{}\n{} {}\n255\n", magic, bounds.width, bounds.height)?;
+    output.write_all(pixels)
+}
+
+/// Work out the absolute path the image named 'filename' should be
+/// written to.
+///
+/// A bare filename (one with no directory part) goes into 'output_dir'
+/// if one is given. Otherwise it goes into the current directory, unless
+/// that isn't writable, in which case it goes into the system temp
+/// directory instead. Filenames with a directory part are used as given.
+///
+/// A leading `~` in 'filename' or 'output_dir' stands for the home
+/// directory, for paths quoted or joined to a flag where the shell
+/// wouldn't expand it. Paths are never converted to strings, so spaces
+/// and names that aren't valid UTF-8 come through as they are.
+pub fn resolve_output_path<P: AsRef<Path>>(filename: P, output_dir: Option<&Path>)
+    -> io::Result<PathBuf> {
+    let home = env::var_os("HOME");
+    let path = expand_home(filename.as_ref(), home.as_deref());
+    let cwd = env::current_dir()?;
+    let is_bare = path.parent().is_none_or(|parent| parent.as_os_str().is_empty());
+
+    let path = match output_dir {
+        Some(dir) if is_bare => expand_home(dir, home.as_deref()).join(path),
+        None if is_bare && !is_writable_dir(&cwd) => env::temp_dir().join(path),
+        _ => path,
+    };
+
+    Ok(if path.is_absolute() { path } else { cwd.join(path) })
+}
+
+/// 'path' with a leading `~` component replaced by 'home'. Without a
+/// home directory, or a `~` to replace, 'path' is returned as it is;
+/// `~user` isn't expanded.
+fn expand_home(path: &Path, home: Option<&OsStr>) -> PathBuf {
+    match (path.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) => Path::new(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Check whether files can be created in 'dir' by creating, and then
+/// removing, an empty one. Permission bits alone don't tell the whole
+/// story (read-only mounts, running as root, ACLs).
+fn is_writable_dir(dir: &Path) -> bool {
+    let probe = dir.join(format!(".mandelbrot-write-test-{}", process::id()));
+    match OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// base64_encode test
+#[test]
+fn test_base64_encode() {
+    // Test vectors from RFC 4648.
+    for &(input, output) in &[("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"),
+                              ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")] {
+        assert_eq!(base64_encode(input.as_bytes()), output);
+    }
+}
+
+/// png_data_uri test
+#[test]
+fn test_png_data_uri() {
+    use image::GenericImage;
+
+    fn base64_decode(encoded: &str) -> Vec<u8> {
+        let mut bits = 0u32;
+        let mut count = 0;
+        let mut decoded = Vec::new();
+        for byte in encoded.bytes().filter(|&byte| byte != b'=') {
+            let value = BASE64_ALPHABET.iter().position(|&c| c == byte).unwrap() as u32;
+            bits = bits << 6 | value;
+            count += 6;
+            if count >= 8 {
+                count -= 8;
+                decoded.push((bits >> count) as u8);
+            }
+        }
+        decoded
+    }
+
+    let bounds = PixelBounds::new(5, 3);
+    let pixels: Vec<u8> = (0..15).collect();
+    let uri = png_data_uri(&pixels, bounds, ColorType::Gray(8)).unwrap();
+
+    let prefix = "data:image/png;base64,";
+    assert!(uri.starts_with(prefix));
+    let image = image::load_from_memory(&base64_decode(&uri[prefix.len()..])).unwrap();
+    assert_eq!(image.dimensions(), (5, 3));
+    assert_eq!(image.raw_pixels(), pixels);
+}
+
+/// OutputFormat test
+#[test]
+fn test_output_format() {
+    let formats = [(OutputFormat::Gray1, 1, ColorType::Gray(1)),
+                   (OutputFormat::Gray8, 1, ColorType::Gray(8)),
+                   (OutputFormat::Gray16, 2, ColorType::Gray(16)),
+                   (OutputFormat::Rgb8, 3, ColorType::RGB(8)),
+                   (OutputFormat::Rgba8, 4, ColorType::RGBA(8))];
+    let bounds = PixelBounds::new(10, 3);
+    for &(format, bytes, color) in &formats {
+        assert_eq!(format.bytes_per_pixel(), bytes, "{:?}", format);
+        assert_eq!(format.color_type(), color);
+        if format == OutputFormat::Gray1 {
+            assert_eq!(format.encoded_len(bounds), 2 * 3);
+            assert_eq!(pack_bits(&[0; 30], bounds).len(), format.encoded_len(bounds));
+        } else {
+            assert_eq!(format.encoded_len(bounds), bounds.pixel_count() * bytes);
+        }
+    }
+}
+
+/// pack_bits test
+#[test]
+fn test_pack_bits() {
+    use crate::field::render_field;
+    use crate::render::LIMIT;
+    use crate::view::ComplexView;
+    use num::Complex;
+
+    // 11 columns: each row takes two bytes, five bits of them padding.
+    let bounds = PixelBounds::new(11, 5);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let field = render_field(bounds, view, LIMIT);
+    let pixels: Vec<u8> = field.iter().map(|escape| if escape.is_interior() { 0 } else { 255 }).collect();
+
+    let packed = pack_bits(&pixels, bounds);
+    assert_eq!(packed.len(), 2 * bounds.height);
+    for row in 0..bounds.height {
+        for column in 0..16 {
+            let bit = packed[row * 2 + column / 8] & (0x80 >> (column % 8)) != 0;
+            if column < bounds.width {
+                assert_eq!(bit, !field[row * bounds.width + column].is_interior());
+            } else {
+                assert!(!bit);
+            }
+        }
+    }
+    assert!(field.iter().any(|escape| escape.is_interior()));
+    assert!(packed.iter().any(|&byte| byte != 0));
+
+    assert_eq!(pack_bits(&[0, 255, 127, 128, 255, 0, 0, 0, 200], PixelBounds::new(9, 1)),
+               vec![0b0101_1000, 0b1000_0000]);
+}
+
+/// is_netpbm test
+#[test]
+fn test_is_netpbm() {
+    assert!(is_netpbm(Path::new("out.ppm")));
+    assert!(is_netpbm(Path::new("dir/out.PGM")));
+    assert!(!is_netpbm(Path::new("out.png")));
+    assert!(!is_netpbm(Path::new("ppm")));
+    assert!(is_svg(Path::new("dir/out.SVG")));
+    assert!(!is_svg(Path::new("out.png")));
+}
+
+/// thumbnail_path test
+#[test]
+fn test_thumbnail_path() {
+    assert_eq!(thumbnail_path(Path::new("renders/out.png")), PathBuf::from("renders/out_thumb.png"));
+    assert_eq!(thumbnail_path(Path::new("my set.pgm")), PathBuf::from("my set_thumb.pgm"));
+    assert_eq!(thumbnail_path(Path::new("out")), PathBuf::from("out_thumb"));
+}
+
+/// save_image test
+#[test]
+fn test_save_image() {
+    let bounds = PixelBounds::new(2, 1);
+    let path = env::temp_dir().join(format!("mandelbrot-save-test-{}.pgm", process::id()));
+    save_image(&path, &[0, 255], bounds, ColorType::Gray(8)).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"P5\n2 1\n255\n\x00\xff".to_vec());
+
+    // Four bytes can't be a 2x1 Netpbm image, and nothing is written.
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(save_image(&path, &[0; 4], bounds, ColorType::Gray(8)),
+                     Err(RenderError::Encode(_))));
+    assert!(!path.exists());
+
+    let missing = Path::new("/nonexistent/mandelbrot/dir/out.png");
+    assert!(matches!(save_image(missing, &[0, 255], bounds, ColorType::Gray(8)),
+                     Err(RenderError::Io(_))));
+}
+
+/// image_bounds test: an image rendered at a reference's bounds comes
+/// out the same size.
+#[test]
+fn test_image_bounds() {
+    use num::Complex;
+    use crate::render::{Coloring, LIMIT, Shading, Supersampling, Threading, render_image};
+    use crate::view::ComplexView;
+
+    let reference = env::temp_dir().join(format!("mandelbrot-bounds-ref-{}.png", process::id()));
+    save_image(&reference, &[128; 7 * 5], PixelBounds::new(7, 5), ColorType::Gray(8)).unwrap();
+    let bounds = image_bounds(&reference).unwrap();
+    assert_eq!(bounds, PixelBounds::new(7, 5));
+
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let pixels = render_image(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                              Supersampling::none(), None, Threading::new(1)).unwrap();
+    let output = env::temp_dir().join(format!("mandelbrot-bounds-out-{}.png", process::id()));
+    save_image(&output, &pixels, bounds, ColorType::Gray(8)).unwrap();
+    assert_eq!(image_bounds(&output).unwrap(), image_bounds(&reference).unwrap());
+
+    fs::remove_file(&reference).unwrap();
+    fs::remove_file(&output).unwrap();
+    assert!(image_bounds(&reference).is_err());
+}
+
+/// encode_ppm test
+#[test]
+fn test_encode_ppm() {
+    let bounds = PixelBounds::new(2, 1);
+
+    let mut gray = Vec::new();
+    encode_ppm(&mut gray, &[0, 255], bounds).unwrap();
+    assert_eq!(gray, b"P5\n2 1\n255\n\x00\xff".to_vec());
+
+    let mut rgb = Vec::new();
+    encode_ppm(&mut rgb, &[1, 2, 3, 4, 5, 6], bounds).unwrap();
+    assert_eq!(&rgb[..11], b"P6\n2 1\n255\n");
+    assert_eq!(&rgb[11..], &[1, 2, 3, 4, 5, 6]);
+
+    let error = encode_ppm(&mut Vec::new(), &[0; 4], bounds).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+}
+
+/// resolve_output_path test
+#[test]
+fn test_resolve_output_path() {
+    let resolved = resolve_output_path("out.png", None).unwrap();
+    assert!(resolved.is_absolute());
+    assert!(resolved.ends_with("out.png"));
+
+    let dir = env::temp_dir();
+    assert_eq!(resolve_output_path("out.png", Some(&dir)).unwrap(), dir.join("out.png"));
+
+    // Only bare filenames are redirected.
+    assert_eq!(resolve_output_path("renders/out.png", Some(&dir)).unwrap(),
+               env::current_dir().unwrap().join("renders/out.png"));
+}
+
+/// expand_home test
+#[test]
+fn test_expand_home() {
+    let home = Some(OsStr::new("/home/someone"));
+    assert_eq!(expand_home(Path::new("~/renders/out.png"), home),
+               PathBuf::from("/home/someone/renders/out.png"));
+    assert_eq!(expand_home(Path::new("~"), home), PathBuf::from("/home/someone"));
+    assert_eq!(expand_home(Path::new("~/out.png"), None), PathBuf::from("~/out.png"));
+    for unchanged in &["out.png", "~someone/out.png", "renders/~/out.png"] {
+        assert_eq!(expand_home(Path::new(unchanged), home), PathBuf::from(unchanged));
+    }
+}
+
+/// write_image test: paths with spaces, and on Unix ones that aren't
+/// valid UTF-8, are written to exactly as named.
+#[test]
+fn test_write_image_unusual_paths() {
+    let dir = env::temp_dir().join(format!("mandelbrot write test {}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("my render.png");
+    write_image(&path, &[0, 255], PixelBounds::new(2, 1), ColorType::Gray(8)).unwrap();
+    assert_eq!(image_bounds(&path).unwrap(), PixelBounds::new(2, 1));
+    assert_eq!(resolve_output_path("my render.png", Some(&dir)).unwrap(), path);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let path = dir.join(OsStr::from_bytes(b"caf\xe9.png"));
+        save_image(&path, &[0, 255], PixelBounds::new(2, 1), ColorType::Gray(8)).unwrap();
+        assert!(path.exists());
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// is_writable_dir test
+#[test]
+fn test_is_writable_dir() {
+    assert!(is_writable_dir(&env::temp_dir()));
+    assert!(!is_writable_dir(Path::new("/nonexistent/mandelbrot/dir")));
+}
+
+/// encode_indexed_png test: a palettized PNG of no more than the colors
+/// given, smaller than the same image as RGB.
+#[test]
+fn test_encode_indexed_png() {
+    use crate::palette::{self, indexed_entries, palette_color};
+    use crate::postprocess::quantize;
+    use std::collections::HashSet;
+
+    // A smoothly shaded RGB image, quantized to 16 colors.
+    let bounds = PixelBounds::new(64, 48);
+    let rainbow = palette::named("rainbow").unwrap();
+    let rgb: Vec<u8> = (0 .. bounds.pixel_count())
+        .flat_map(|i| palette_color(&rainbow, (i % 64) as f64 + (i / 64) as f64 / 48.0, 64, 1).to_vec())
+        .collect();
+    let entries = indexed_entries(Some(&rainbow), 16);
+    let indices = quantize(&rgb, 3, &entries);
+
+    let mut indexed = Vec::new();
+    encode_indexed_png(&mut indexed, &indices, bounds, &entries).unwrap();
+    // IHDR comes first, right after the signature; its color type is 3,
+    // indexed. PLTE follows with three bytes per color.
+    assert_eq!(&indexed[12 .. 16], b"IHDR");
+    assert_eq!(indexed[25], 3);
+    assert_eq!(&indexed[37 .. 41], b"PLTE");
+    assert_eq!(&indexed[33 .. 37], &(3 * 16u32).to_be_bytes());
+
+    let image = image::load_from_memory(&indexed).unwrap().to_rgb();
+    assert_eq!(image.dimensions(), (64, 48));
+    let colors: HashSet<&[u8]> = image.as_ref().chunks(3).collect();
+    assert!(colors.len() <= 16, "{} colors", colors.len());
+    for (pixel, &index) in image.as_ref().chunks(3).zip(&indices) {
+        assert_eq!(pixel, &entries[index as usize][..]);
+    }
+
+    let mut full = Vec::new();
+    encode_png(&mut full, &rgb, bounds, ColorType::RGB(8)).unwrap();
+    assert!(indexed.len() < full.len());
+}
+
+/// save_image test with a partly rendered buffer, as `--preview-interval`
+/// writes it: a valid PNG of the full size, black below the rows done.
+#[test]
+fn test_save_image_partial() {
+    use num::Complex;
+    use crate::render::{Coloring, LIMIT, Shading, Supersampling, render_rows};
+    use crate::view::ComplexView;
+
+    let bounds = PixelBounds::new(12, 8);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut pixels = vec![0; bounds.pixel_count() * 3];
+    let colors = [[255, 255, 0], [0, 255, 255]];
+    let coloring = Coloring::Palette { colors: &colors, repeat: 1 };
+    render_rows(&mut pixels[.. 3 * 12 * 3], bounds, view, 0, Shading::EscapeTime, LIMIT, coloring,
+                Supersampling::none(), None);
+
+    let path = env::temp_dir().join(format!("mandelbrot-preview-test-{}.png", process::id()));
+    save_image(&path, &pixels, bounds, ColorType::RGB(8)).unwrap();
+    let image = image::open(&path).unwrap().to_rgb();
+    assert_eq!(image.dimensions(), (12, 8));
+    assert_eq!(image.into_raw(), pixels);
+    assert!(pixels[.. 3 * 12 * 3].iter().any(|&byte| byte != 0));
+
+    // Saving again replaces it, and leaves no temporary file behind.
+    save_image(&path, &vec![0; bounds.pixel_count() * 3], bounds, ColorType::RGB(8)).unwrap();
+    assert!(image::open(&path).unwrap().raw_pixels().iter().all(|&byte| byte == 0));
+    let leftovers = fs::read_dir(env::temp_dir()).unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy()
+            .starts_with(&format!("mandelbrot-preview-test-{}.png.", process::id())))
+        .count();
+    assert_eq!(leftovers, 0);
+    fs::remove_file(&path).unwrap();
+}
+
+/// encode_samples test: raw dumps follow the order asked for, and 16-bit
+/// PNGs are big-endian.
+#[test]
+fn test_encode_samples() {
+    assert_eq!(encode_samples(&[0x1234, 0xabcd], Endian::Little), vec![0x34, 0x12, 0xcd, 0xab]);
+    assert_eq!(encode_samples(&[0x1234, 0xabcd], Endian::Big), vec![0x12, 0x34, 0xab, 0xcd]);
+
+    // Decode a 16-bit PNG with `png` itself, which hands back the bytes
+    // as stored, and so in the file's order.
+    let bounds = PixelBounds::new(2, 1);
+    let mut encoded = Vec::new();
+    encode_png(&mut encoded, &encode_samples(&[0x1234, 0xabcd], Endian::Big), bounds,
+               ColorType::Gray(16)).unwrap();
+    let mut decoder = png::Decoder::new(&encoded[..]);
+    decoder.set(png::TRANSFORM_IDENTITY);
+    let (info, mut reader) = decoder.read_info().unwrap();
+    assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+    let mut stored = vec![0; info.buffer_size()];
+    reader.next_frame(&mut stored).unwrap();
+    assert_eq!(stored, vec![0x12, 0x34, 0xab, 0xcd]);
+}
+
+/// pack_samples test: 4-bit levels take half a byte each, rows padded to
+/// whole bytes, and unpack to the levels `tone_map_bits` gave.
+#[test]
+fn test_pack_samples() {
+    use crate::field::{Escape, tone_map_bits};
+
+    let field = [Escape::Escaped(0), Escape::Interior, Escape::Escaped(100),
+                 Escape::Escaped(255), Escape::Escaped(16), Escape::Interior];
+    let levels = tone_map_bits(&field, 256, 4);
+    assert_eq!(levels, vec![15, 0, 10, 1, 15, 0]);
+
+    let bounds = PixelBounds::new(3, 2);
+    let packed = pack_samples(&levels, 4, bounds);
+    assert_eq!(packed.len(), 4);
+    assert_eq!(packed, vec![0xf0, 0xa0, 0x1f, 0x00]);
+    assert_eq!(unpack_samples(&packed, 4, bounds), levels);
+
+    // Widths that leave levels straddling bytes, and every level.
+    for bits in 1..=8 {
+        let bounds = PixelBounds::new(7, 3);
+        let levels: Vec<u8> = (0..21u32).map(|i| (i * 37 % (1 << bits)) as u8).collect();
+        let packed = pack_samples(&levels, bits, bounds);
+        assert_eq!(packed.len(), (7 * bits as usize).div_ceil(8) * 3);
+        assert_eq!(unpack_samples(&packed, bits, bounds), levels, "{} bits", bits);
+    }
+}
+
+/// encode_exr test: the floats read back exactly as written.
+#[test]
+fn test_encode_exr() {
+    use exr::prelude::{ReadChannels, ReadLayers, ReadSpecificChannel, read};
+
+    let bounds = PixelBounds::new(5, 3);
+    let values: Vec<f32> = (0 .. bounds.pixel_count())
+        .map(|i| if i % 4 == 0 { 0.0 } else { 1.0 / (i as f32 + 0.3) })
+        .collect();
+    let encoded = encode_exr(&values, bounds).unwrap();
+
+    let image = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .specific_channels()
+        .required("Y")
+        .collect_pixels(
+            |resolution, _| vec![f32::NAN; resolution.width() * resolution.height()],
+            |pixels: &mut Vec<f32>, position, (value,): (f32,)| {
+                pixels[position.y() * 5 + position.x()] = value;
+            })
+        .first_valid_layer()
+        .all_attributes()
+        .from_buffered(Cursor::new(encoded))
+        .unwrap();
+    assert_eq!(image.attributes.display_window.size.width(), 5);
+    assert_eq!(image.attributes.display_window.size.height(), 3);
+    assert_eq!(image.layer_data.channel_data.pixels, values);
+}
+
+/// encode_png_with test
+#[test]
+fn test_encode_png_with() {
+    // Smooth bands, as around the set.
+    let bounds = PixelBounds::new(64, 48);
+    let pixels: Vec<u8> = (0..bounds.height)
+        .flat_map(|row| (0..bounds.width).map(move |column| ((column * column + row * row) / 20) as u8))
+        .collect();
+
+    // The default is what the `image` crate's own encoder writes.
+    let mut default = Vec::new();
+    encode_png(&mut default, &pixels, bounds, ColorType::Gray(8)).unwrap();
+    let mut reference = Vec::new();
+    image::png::PNGEncoder::new(&mut reference)
+        .encode(&pixels, bounds.width as u32, bounds.height as u32, ColorType::Gray(8)).unwrap();
+    assert_eq!(default, reference);
+
+    let filters = [PngFilter::None, PngFilter::Sub, PngFilter::Up, PngFilter::Average,
+                   PngFilter::Paeth, PngFilter::Adaptive];
+    for &compression in &[PngCompression::Fast, PngCompression::Default, PngCompression::Best] {
+        for &filter in &filters {
+            let mut encoded = Vec::new();
+            encode_png_with(&mut encoded, &pixels, bounds, ColorType::Gray(8),
+                            PngOptions { compression, filter }).unwrap();
+            let decoded = image::load_from_memory(&encoded).unwrap().raw_pixels();
+            assert_eq!(decoded, pixels, "{:?} {:?}", compression, filter);
+        }
+    }
+
+    let mut best = Vec::new();
+    encode_png_with(&mut best, &pixels, bounds, ColorType::Gray(8),
+                    PngOptions { compression: PngCompression::Best, filter: PngFilter::Adaptive })
+        .unwrap();
+    assert!(best.len() <= default.len(), "{} > {}", best.len(), default.len());
+
+    // Rows of whole bytes holding several pixels, and pixels of several bytes.
+    let mut encoded = Vec::new();
+    encode_png_with(&mut encoded, &[0b1010_0000, 0b0101_0000], PixelBounds::new(4, 2),
+                    ColorType::Gray(1), PngOptions { compression: PngCompression::Best,
+                                                     filter: PngFilter::Paeth }).unwrap();
+    assert_eq!(image::load_from_memory(&encoded).unwrap().to_luma().into_raw(),
+               vec![255, 0, 255, 0, 0, 255, 0, 255]);
+    let rgb = [10, 20, 30, 40, 50, 60, 70, 80, 90, 100, 110, 120];
+    let mut encoded = Vec::new();
+    encode_png_with(&mut encoded, &rgb, PixelBounds::new(2, 2), ColorType::RGB(8),
+                    PngOptions { compression: PngCompression::Default, filter: PngFilter::Average })
+        .unwrap();
+    assert_eq!(image::load_from_memory(&encoded).unwrap().raw_pixels(), rgb.to_vec());
+
+    assert!(encode_png_with(Vec::new(), &pixels[..10], bounds, ColorType::Gray(8),
+                            PngOptions::default()).is_err());
+}
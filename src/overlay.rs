@@ -0,0 +1,339 @@
+use crate::render::Coloring;
+use crate::view::{ComplexView, PixelBounds};
+
+/// A corner of the image, where the overlay goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    /// Parse a corner name as given on the command line: `top-left`,
+    /// `top-right`, `bottom-left` or `bottom-right`.
+    pub fn from_name(name: &str) -> Option<Corner> {
+        match name {
+            "top-left" => Some(Corner::TopLeft),
+            "top-right" => Some(Corner::TopRight),
+            "bottom-left" => Some(Corner::BottomLeft),
+            "bottom-right" => Some(Corner::BottomRight),
+            _ => None,
+        }
+    }
+}
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+/// The 5x7 bitmap of 'c', one row per byte with the leftmost pixel in
+/// bit 4, for the few characters the overlay uses. Anything else is
+/// drawn as a blank.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c {
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        'e' => [0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110],
+        'c' => [0b00000, 0b00000, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110],
+        'w' => [0b00000, 0b00000, 0b10001, 0b10001, 0b10101, 0b10101, 0b01010],
+        _ => [0; GLYPH_HEIGHT],
+    }
+}
+
+/// A monochrome drawing, built up before it is composited onto the image.
+struct Canvas {
+    width: usize,
+    height: usize,
+    lit: Vec<bool>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas { width, height, lit: vec![false; width * height] }
+    }
+
+    fn fill(&mut self, left: usize, top: usize, width: usize, height: usize) {
+        for row in top .. (top + height).min(self.height) {
+            for column in left .. (left + width).min(self.width) {
+                self.lit[row * self.width + column] = true;
+            }
+        }
+    }
+
+    /// Draw 'text' with its upper left corner at `(left, top)`, every
+    /// font pixel 'scale' pixels square.
+    fn text(&mut self, left: usize, top: usize, scale: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            let x = left + i * (GLYPH_WIDTH + 1) * scale;
+            for (y, bits) in glyph(c).iter().enumerate() {
+                for dx in 0 .. GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - dx)) != 0 {
+                        self.fill(x + dx * scale, top + y * scale, scale, scale);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The width 'text' takes when drawn at 'scale'.
+fn text_width(text: &str, scale: usize) -> usize {
+    text.chars().count() * (GLYPH_WIDTH + 1) * scale
+}
+
+/// The longest length of the form 1, 2 or 5 times a power of ten that is
+/// no longer than 'most'.
+fn round_length(most: f64) -> f64 {
+    let power = 10f64.powf(most.log10().floor());
+    [5.0, 2.0, 1.0].iter()
+        .map(|step| step * power)
+        .find(|&length| length <= most)
+        .unwrap_or(power)
+}
+
+/// Burn a scale bar and a label into a corner of the rendered image.
+///
+/// The label gives the center of 'view' (`c re,im`) and its width on the
+/// real axis (`w ...`). Under it, a bar of a round length (1, 2 or 5
+/// times a power of ten) no longer than a quarter of the image width is
+/// labelled with that length. Everything is drawn in white with a black
+/// outline so it reads on light and dark areas alike, and is kept to
+/// 'corner', out of the way of the middle of the image where the
+/// interesting part usually is. 'pixels' holds 'channels' bytes per
+/// pixel; every byte of a pixel is set, so an alpha byte turns opaque.
+pub fn draw_overlay(pixels: &mut [u8], bounds: PixelBounds, channels: usize, view: ComplexView,
+                    corner: Corner) {
+    assert!(pixels.len() == bounds.pixel_count() * channels);
+    if bounds.width == 0 || bounds.height == 0 {
+        return;
+    }
+
+    let view_width = (view.lower_right.re - view.upper_left.re).abs();
+    let center = (view.upper_left + view.lower_right) / 2.0;
+    // Enough decimals to tell this view's center from its neighbours'.
+    let decimals = (3.0 - view_width.log10()).ceil().max(0.0) as usize;
+    let center_label = format!("c {:.*},{:.*}", decimals, center.re, decimals, center.im);
+    let width_label = format!("w {:.3e}", view_width);
+
+    let pixel_width = view_width / bounds.width as f64;
+    let bar_length = round_length(view_width / 4.0);
+    let bar_pixels = ((bar_length / pixel_width).round() as usize).max(1);
+    let bar_label = format!("{:e}", bar_length);
+
+    // Lay the overlay out on a canvas of its own, with room for the
+    // outline all around.
+    let scale = (bounds.width / 500).max(1);
+    let (margin, line) = (2 * scale, (GLYPH_HEIGHT + 2) * scale);
+    let bar_height = 2 * scale;
+    let width = margin * 2 + text_width(&center_label, scale)
+        .max(text_width(&width_label, scale))
+        .max(bar_pixels + 2 * scale + text_width(&bar_label, scale));
+    let height = margin * 2 + 3 * line;
+    let mut canvas = Canvas::new(width, height);
+    canvas.text(margin, margin, scale, &center_label);
+    canvas.text(margin, margin + line, scale, &width_label);
+    let bar_top = margin + 2 * line + (GLYPH_HEIGHT * scale - bar_height) / 2;
+    canvas.fill(margin, bar_top, bar_pixels, bar_height);
+    // End ticks, so the bar's length reads exactly.
+    canvas.fill(margin, bar_top - scale, scale, bar_height + 2 * scale);
+    canvas.fill(margin + bar_pixels - scale.min(bar_pixels), bar_top - scale, scale,
+                bar_height + 2 * scale);
+    canvas.text(margin + bar_pixels + 2 * scale, margin + 2 * line, scale, &bar_label);
+
+    // Where the canvas's upper left corner goes in the image. The canvas
+    // is clipped if the image is too small for it.
+    let left = match corner {
+        Corner::TopLeft | Corner::BottomLeft => 0,
+        Corner::TopRight | Corner::BottomRight => bounds.width.saturating_sub(width),
+    };
+    let top = match corner {
+        Corner::TopLeft | Corner::TopRight => 0,
+        Corner::BottomLeft | Corner::BottomRight => bounds.height.saturating_sub(height),
+    };
+
+    let lit = |column: isize, row: isize| {
+        column >= 0 && row >= 0 && (column as usize) < width && (row as usize) < height
+            && canvas.lit[row as usize * width + column as usize]
+    };
+    let reach = scale as isize;
+    for row in 0 .. height.min(bounds.height - top) {
+        for column in 0 .. width.min(bounds.width - left) {
+            let (x, y) = (column as isize, row as isize);
+            let value = if lit(x, y) {
+                255
+            } else if (-reach ..= reach).any(|dy| (-reach ..= reach).any(|dx| lit(x + dx, y + dy))) {
+                0
+            } else {
+                continue;
+            };
+            let offset = ((top + row) * bounds.width + left + column) * channels;
+            for byte in &mut pixels[offset .. offset + channels] {
+                *byte = value;
+            }
+        }
+    }
+}
+
+/// The size of the image `legend` draws.
+pub const LEGEND_WIDTH: usize = 320;
+pub const LEGEND_HEIGHT: usize = 84;
+
+/// Margin around everything in the legend, and the height of its bar.
+const LEGEND_MARGIN: usize = 8;
+const LEGEND_BAR_HEIGHT: usize = 24;
+
+/// How many spans the ticks under the legend's bar split it into.
+const LEGEND_TICK_SPANS: u32 = 4;
+
+/// Draw a key to 'coloring' for figures, as a separate RGB image of
+/// `LEGEND_WIDTH` by `LEGEND_HEIGHT` pixels, black on white: a bar
+/// running from the color of count 0 on the left to that of 'limit' on
+/// the right, as `Coloring::count_color` gives them, with escape counts
+/// labelled under ticks below it, and under those the corners of 'view',
+/// the upper left one to the left and the lower right one to the right.
+pub fn legend(coloring: Coloring, limit: u32, view: ComplexView) -> (Vec<u8>, PixelBounds) {
+    let bounds = PixelBounds::new(LEGEND_WIDTH, LEGEND_HEIGHT);
+    let mut pixels = vec![255; bounds.pixel_count() * 3];
+    let bar_width = LEGEND_WIDTH - 2 * LEGEND_MARGIN;
+    for column in 0 .. bar_width {
+        let count = column as f64 * limit as f64 / (bar_width - 1) as f64;
+        let [red, green, blue, _] = coloring.count_color(count, limit);
+        let gray = if coloring.channels() == 1 { Some(red) } else { None };
+        let color = [red, gray.unwrap_or(green), gray.unwrap_or(blue)];
+        for row in LEGEND_MARGIN .. LEGEND_MARGIN + LEGEND_BAR_HEIGHT {
+            let offset = (row * LEGEND_WIDTH + LEGEND_MARGIN + column) * 3;
+            pixels[offset .. offset + 3].copy_from_slice(&color);
+        }
+    }
+
+    let mut canvas = Canvas::new(LEGEND_WIDTH, LEGEND_HEIGHT);
+    let tick_top = LEGEND_MARGIN + LEGEND_BAR_HEIGHT;
+    let label_top = tick_top + 6;
+    for tick in 0 ..= LEGEND_TICK_SPANS {
+        let count = (limit as u64 * tick as u64 / LEGEND_TICK_SPANS as u64).to_string();
+        let x = LEGEND_MARGIN + (bar_width - 1) * tick as usize / LEGEND_TICK_SPANS as usize;
+        canvas.fill(x, tick_top, 1, 4);
+        // Centered under its tick, but kept inside the image.
+        let label_left = x.saturating_sub(text_width(&count, 1) / 2)
+            .clamp(LEGEND_MARGIN / 2, LEGEND_WIDTH - LEGEND_MARGIN / 2 - text_width(&count, 1));
+        canvas.text(label_left, label_top, 1, &count);
+    }
+
+    let view_width = (view.lower_right.re - view.upper_left.re).abs();
+    // As for the overlay's center: enough decimals to tell neighbours apart.
+    let decimals = (3.0 - view_width.log10()).ceil().max(0.0) as usize;
+    let corner = |point: num::Complex<f64>| {
+        format!("{:.*},{:.*}", decimals, point.re, decimals, point.im)
+    };
+    let (upper_left, lower_right) = (corner(view.upper_left), corner(view.lower_right));
+    let line = GLYPH_HEIGHT + 4;
+    canvas.text(LEGEND_MARGIN, label_top + line + 2, 1, &upper_left);
+    canvas.text(LEGEND_WIDTH.saturating_sub(LEGEND_MARGIN + text_width(&lower_right, 1)),
+                label_top + 2 * line + 2, 1, &lower_right);
+
+    for (pixel, &lit) in pixels.chunks_mut(3).zip(&canvas.lit) {
+        if lit {
+            pixel.copy_from_slice(&[0, 0, 0]);
+        }
+    }
+    (pixels, bounds)
+}
+
+/// round_length test
+#[test]
+fn test_round_length() {
+    assert_eq!(round_length(0.75), 0.5);
+    assert_eq!(round_length(3.0), 2.0);
+    assert_eq!(round_length(1.0), 1.0);
+    assert!((round_length(1.9e-9) - 1e-9).abs() < 1e-24);
+    assert_eq!(round_length(999.0), 500.0);
+}
+
+/// draw_overlay test: only the chosen corner changes.
+#[test]
+fn test_draw_overlay() {
+    use num::Complex;
+
+    let bounds = PixelBounds::new(400, 300);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    for &corner in &[Corner::TopLeft, Corner::TopRight, Corner::BottomLeft, Corner::BottomRight] {
+        for &channels in &[1, 3] {
+            let mut pixels = vec![100; bounds.pixel_count() * channels];
+            draw_overlay(&mut pixels, bounds, channels, view, corner);
+
+            let mut changed = 0;
+            for (i, pixel) in pixels.chunks(channels).enumerate() {
+                if pixel.iter().all(|&byte| byte == 100) {
+                    continue;
+                }
+                changed += 1;
+                assert!(pixel.iter().all(|&byte| byte == 0) || pixel.iter().all(|&byte| byte == 255));
+                let (column, row) = (i % bounds.width, i / bounds.width);
+                let left = column < bounds.width / 2;
+                let upper = row < bounds.height / 2;
+                assert_eq!(left, corner == Corner::TopLeft || corner == Corner::BottomLeft,
+                           "{:?} changed pixel {:?}", corner, (column, row));
+                assert_eq!(upper, corner == Corner::TopLeft || corner == Corner::TopRight,
+                           "{:?} changed pixel {:?}", corner, (column, row));
+            }
+            assert!(changed > 100, "{:?} changed only {} pixels", corner, changed);
+        }
+    }
+
+    // Too small for the whole overlay: clipped, not a panic.
+    let mut tiny = vec![7; 10 * 4];
+    draw_overlay(&mut tiny, PixelBounds::new(10, 4), 1, view, Corner::BottomRight);
+    draw_overlay(&mut [], PixelBounds::new(0, 0), 1, view, Corner::BottomRight);
+}
+
+/// legend test
+#[test]
+fn test_legend() {
+    use num::Complex;
+    use crate::palette::{Interpolation, PaletteLut, named};
+
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.2 }, Complex { re: 1.0, im: -1.2 });
+    let colors = named("fire").unwrap();
+    let lut = PaletteLut::new(&colors, 255, 1, 16, Interpolation::Rgb);
+    for &coloring in &[Coloring::Gray, Coloring::Lut(&lut)] {
+        let (pixels, bounds) = legend(coloring, 255, view);
+        assert_eq!(bounds, PixelBounds::new(LEGEND_WIDTH, LEGEND_HEIGHT));
+        assert_eq!(pixels.len(), LEGEND_WIDTH * LEGEND_HEIGHT * 3);
+
+        // The ends of the bar, halfway down it, are the ends of the coloring.
+        let at = |column: usize| {
+            let offset = ((LEGEND_MARGIN + LEGEND_BAR_HEIGHT / 2) * LEGEND_WIDTH + column) * 3;
+            [pixels[offset], pixels[offset + 1], pixels[offset + 2]]
+        };
+        let end = |count: f64| {
+            let [red, green, blue, _] = coloring.count_color(count, 255);
+            if coloring.channels() == 1 { [red, red, red] } else { [red, green, blue] }
+        };
+        assert_eq!(at(LEGEND_MARGIN), end(0.0));
+        assert_eq!(at(LEGEND_WIDTH - LEGEND_MARGIN - 1), end(255.0));
+        // The margin stays white, and the labels are drawn in black.
+        assert_eq!(at(0), [255, 255, 255]);
+        assert!(pixels[(LEGEND_MARGIN + LEGEND_BAR_HEIGHT) * LEGEND_WIDTH * 3 ..]
+                    .chunks(3).any(|pixel| pixel == [0, 0, 0]));
+    }
+    // Which, for gray, runs from white to black, and for a palette from
+    // its first color.
+    let (gray, _) = legend(Coloring::Gray, 255, view);
+    let bar_row = (LEGEND_MARGIN + LEGEND_BAR_HEIGHT / 2) * LEGEND_WIDTH;
+    assert_eq!(gray[(bar_row + LEGEND_MARGIN) * 3], 255);
+    assert_eq!(gray[(bar_row + LEGEND_WIDTH - LEGEND_MARGIN - 1) * 3], 0);
+    let (fire, _) = legend(Coloring::Lut(&lut), 255, view);
+    assert_eq!(fire[(bar_row + LEGEND_MARGIN) * 3 ..][..3], colors[0]);
+}
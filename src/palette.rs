@@ -0,0 +1,564 @@
+//! Palettes for coloring escape counts.
+//!
+//! A palette is a list of evenly spaced RGB color stops. It is cyclic:
+//! after the last stop the colors blend back into the first, so it can
+//! be repeated across the escape-count range without a visible seam.
+
+/// Names accepted by `named`.
+pub const NAMES: &[&str] = &["rainbow", "fire", "ultra", "spectral", "viridis", "cividis"];
+
+/// The stops of Ultra Fractal's default gradient, dark blue through white
+/// and orange to black, as (position, color) with positions in `0..1`.
+/// They aren't evenly spaced, so `named` samples the gradient at
+/// `ULTRA_STEPS` evenly spaced positions instead, every stop among them.
+pub const ULTRA_STOPS: &[(f64, [u8; 3])] = &[
+    (0.0, [0, 7, 100]),
+    (0.16, [32, 107, 203]),
+    (0.42, [237, 255, 255]),
+    (0.6425, [255, 170, 0]),
+    (0.8575, [0, 2, 0]),
+];
+
+/// How many evenly spaced colors the `ultra` palette has: every stop
+/// position is a whole multiple of its reciprocal, so blending between
+/// them retraces the gradient exactly.
+pub const ULTRA_STEPS: usize = 400;
+
+/// The range of visible wavelengths, in nanometers, that the `spectral`
+/// palette runs through from the red end to the violet.
+pub const SPECTRAL_RANGE: (f64, f64) = (750.0, 380.0);
+
+/// How many colors the `spectral` palette has: one per nanometer.
+pub const SPECTRAL_STEPS: usize = 371;
+
+/// The wavelengths, in nanometers, over which `wavelength_to_srgb` trusts
+/// its fit of the color matching functions.
+const SPECTRAL_FIT_RANGE: (f64, f64) = (400.0, 650.0);
+
+/// Evenly spaced stops along matplotlib's viridis colormap, dark purple
+/// through teal to yellow. Its lightness rises steadily from end to end,
+/// so it reads the same to viewers with the common kinds of color
+/// blindness, and in grayscale.
+pub const VIRIDIS_STOPS: &[[u8; 3]] = &[
+    [68, 1, 84], [71, 45, 123], [59, 82, 139], [44, 114, 142], [33, 145, 140],
+    [40, 174, 128], [94, 201, 98], [173, 220, 48], [253, 231, 37],
+];
+
+/// Evenly spaced stops along cividis, navy through gray to yellow: like
+/// viridis, but built to look nearly the same with red-green color
+/// blindness as without.
+pub const CIVIDIS_STOPS: &[[u8; 3]] = &[
+    [0, 34, 78], [18, 53, 112], [59, 73, 108], [87, 93, 109], [112, 113, 115],
+    [138, 134, 120], [165, 156, 116], [195, 179, 105], [225, 204, 85], [254, 232, 56],
+];
+
+/// How many colors the `viridis` and `cividis` palettes have. With this
+/// many, the blend from the last back to the first, which every palette
+/// has, takes up only one step of the range.
+pub const RAMP_STEPS: usize = 256;
+
+/// The colors `--interior-period-color` gives the bulbs of the set, by
+/// the period of their attracting cycles from 1: hues far enough apart
+/// that bulbs which touch never look alike.
+pub const PERIOD_COLORS: &[[u8; 3]] = &[
+    [230, 25, 75], [60, 180, 75], [0, 130, 200], [255, 225, 25],
+    [145, 30, 180], [245, 130, 48], [70, 240, 240], [240, 50, 230],
+];
+
+/// Look up a built-in palette by name.
+pub fn named(name: &str) -> Option<Vec<[u8; 3]>> {
+    match name {
+        "rainbow" => Some(vec![[255, 0, 0], [255, 255, 0], [0, 255, 0],
+                               [0, 255, 255], [0, 0, 255], [255, 0, 255]]),
+        "fire" => Some(vec![[0, 0, 0], [128, 0, 0], [255, 64, 0],
+                            [255, 192, 0], [255, 255, 192]]),
+        "ultra" => Some(sample_gradient(ULTRA_STOPS, ULTRA_STEPS)),
+        "spectral" => Some(sample_spectrum(SPECTRAL_RANGE, SPECTRAL_STEPS)),
+        "viridis" => Some(sample_ramp(VIRIDIS_STOPS, RAMP_STEPS)),
+        "cividis" => Some(sample_ramp(CIVIDIS_STOPS, RAMP_STEPS)),
+        _ => None,
+    }
+}
+
+/// 'colors' in the opposite order, so the palette runs from its last
+/// color to its first.
+pub fn reverse(mut colors: Vec<[u8; 3]>) -> Vec<[u8; 3]> {
+    colors.reverse();
+    colors
+}
+
+/// 'colors' starting from the one 'steps' along instead of the first,
+/// with the ones before it moved round to the end. Rotating by a
+/// multiple of the palette's length leaves it as it is.
+pub fn rotate(mut colors: Vec<[u8; 3]>, steps: usize) -> Vec<[u8; 3]> {
+    if !colors.is_empty() {
+        let len = colors.len();
+        colors.rotate_left(steps % len);
+    }
+    colors
+}
+
+/// Sample the cyclic gradient through 'stops', whose positions must be
+/// increasing and in `0..1`, at 'steps' evenly spaced positions. As in a
+/// palette, the last stop blends back into the first.
+fn sample_gradient(stops: &[(f64, [u8; 3])], steps: usize) -> Vec<[u8; 3]> {
+    (0..steps).map(|step| {
+        let position = step as f64 / steps as f64;
+        let next = stops.iter().position(|&(at, _)| at > position).unwrap_or(stops.len());
+        let (from, from_color) = stops[(next + stops.len() - 1) % stops.len()];
+        let (to, to_color) = match stops.get(next) {
+            Some(&stop) => stop,
+            None => (stops[0].0 + 1.0, stops[0].1),
+        };
+        let from = if from > position { from - 1.0 } else { from };
+        lerp(from_color, to_color, (position - from) / (to - from))
+    }).collect()
+}
+
+/// Sample the ramp through evenly spaced 'stops' at 'steps' evenly spaced
+/// positions, the first and last of them on the first and last stops.
+/// Unlike `sample_gradient`, the ramp doesn't wrap around.
+fn sample_ramp(stops: &[[u8; 3]], steps: usize) -> Vec<[u8; 3]> {
+    (0..steps).map(|step| {
+        let position = step as f64 / (steps - 1) as f64 * (stops.len() - 1) as f64;
+        let from = (position.floor() as usize).min(stops.len() - 2);
+        lerp(stops[from], stops[from + 1], position - from as f64)
+    }).collect()
+}
+
+/// The colors of 'steps' evenly spaced wavelengths from 'range.0' to
+/// 'range.1' nanometers, as `wavelength_to_srgb` gives them.
+fn sample_spectrum(range: (f64, f64), steps: usize) -> Vec<[u8; 3]> {
+    (0..steps).map(|step| {
+        let t = step as f64 / (steps - 1) as f64;
+        wavelength_to_srgb(range.0 + (range.1 - range.0) * t)
+    }).collect()
+}
+
+/// The color of pure light of wavelength 'nanometers'. The CIE 1931
+/// color matching functions, by the multi-lobe Gaussian fit of Wyman,
+/// Sloan and Shirley (2013), give its XYZ, which is converted to sRGB.
+/// Spectral colors lie outside the sRGB gamut, so negative components
+/// are clipped; and the color is scaled to full brightness, since the
+/// eye's sensitivity falling away towards either end of the spectrum
+/// would otherwise fade those wavelengths to black.
+///
+/// Outside `SPECTRAL_FIT_RANGE` the fit strays from the CIE tables, while
+/// the true colors, clipped into sRGB, barely change: wavelengths there
+/// take the color at the nearer end of the range.
+pub fn wavelength_to_srgb(nanometers: f64) -> [u8; 3] {
+    let nanometers = nanometers.clamp(SPECTRAL_FIT_RANGE.0, SPECTRAL_FIT_RANGE.1);
+    // A Gaussian with different widths either side of its peak.
+    let lobe = |peak: f64, below: f64, above: f64| {
+        let width = if nanometers < peak { below } else { above };
+        (-0.5 * ((nanometers - peak) / width).powi(2)).exp()
+    };
+    let xyz = [1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+                   - 0.065 * lobe(501.1, 20.4, 26.2),
+               0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+               1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8)];
+    let linear = xyz_to_linear(xyz).map(|channel| channel.max(0.0));
+    let brightest = linear.iter().cloned().fold(0.0, f64::max);
+    if brightest == 0.0 {
+        return [0, 0, 0];
+    }
+    linear_to_srgb(linear.map(|channel| channel / brightest))
+}
+
+/// How colors between two palette stops are blended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Straight lines between the sRGB values. Cheap, but the lightness
+    /// changes unevenly along the way, which shows as bands.
+    Rgb,
+    /// Straight lines in CIELAB, where equal steps look about equally
+    /// different, converted back to sRGB.
+    Lab,
+}
+
+impl Interpolation {
+    /// The interpolation `--interp` calls 'name', if any.
+    pub fn from_name(name: &str) -> Option<Interpolation> {
+        match name {
+            "rgb" => Some(Interpolation::Rgb),
+            "lab" => Some(Interpolation::Lab),
+            _ => None,
+        }
+    }
+
+    /// The color a fraction 't' of the way from 'a' to 'b'.
+    pub fn blend(self, a: [u8; 3], b: [u8; 3], t: f64) -> [u8; 3] {
+        match self {
+            Interpolation::Rgb => lerp(a, b, t),
+            Interpolation::Lab => {
+                let (a, b) = (srgb_to_lab(a), srgb_to_lab(b));
+                lab_to_srgb([0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t))
+            }
+        }
+    }
+}
+
+/// Pick the color for an escape count out of 'palette'.
+///
+/// The palette is traversed 'repeat' times over the counts `0..limit`, so
+/// the position within it is `count * repeat / limit` palette lengths,
+/// taken modulo one full cycle. Colors between two stops are linearly
+/// interpolated, and the last stop blends into the first. 'count' may be
+/// fractional, as produced by smooth coloring.
+pub fn palette_color(palette: &[[u8; 3]], count: f64, limit: u32, repeat: u32) -> [u8; 3] {
+    palette_color_with(palette, count, limit, repeat, Interpolation::Rgb)
+}
+
+/// `palette_color`, but blending between stops by 'interpolation'.
+pub fn palette_color_with(palette: &[[u8; 3]], count: f64, limit: u32, repeat: u32,
+                          interpolation: Interpolation) -> [u8; 3] {
+    assert!(!palette.is_empty(), "palette has no colors");
+
+    let len = palette.len();
+    let position = count * repeat as f64 * len as f64 / limit as f64;
+    let position = position.rem_euclid(len as f64);
+    let index = (position.floor() as usize).min(len - 1);
+    let fraction = position - index as f64;
+
+    interpolation.blend(palette[index], palette[(index + 1) % len], fraction)
+}
+
+/// Steps per escape count a `PaletteLut` keeps unless told otherwise.
+pub const DEFAULT_LUT_RESOLUTION: u32 = 16;
+
+/// The most steps a `PaletteLut` tabulates, whatever the limit. Counts
+/// past them are rare, and each took more iterations than working its
+/// color out directly costs.
+pub const MAX_LUT_STEPS: u64 = 1 << 16;
+
+/// `palette_color_with` for one palette, limit, repeat and
+/// interpolation, looked up from a table made once instead of worked out
+/// for every pixel. Blending in CIELAB is slow, so this matters most
+/// there.
+///
+/// The table has 'resolution' entries per escape count over `0..=limit`,
+/// up to `MAX_LUT_STEPS` of them, so whole counts come out exactly as
+/// `palette_color_with` gives them, and fractional ones are rounded to
+/// the nearest step first. Counts outside the table, which smooth
+/// coloring can give just past either end and huge limits give past
+/// the cap, fall back to `palette_color_with`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaletteLut {
+    palette: Vec<[u8; 3]>,
+    limit: u32,
+    repeat: u32,
+    resolution: u32,
+    interpolation: Interpolation,
+    table: Vec<[u8; 3]>,
+}
+
+impl PaletteLut {
+    pub fn new(palette: &[[u8; 3]], limit: u32, repeat: u32, resolution: u32,
+               interpolation: Interpolation) -> PaletteLut {
+        assert!(resolution > 0, "LUT resolution must be at least one step per count");
+        let table = (0 ..= (limit as u64 * resolution as u64).min(MAX_LUT_STEPS))
+            .map(|step| {
+                palette_color_with(palette, step as f64 / resolution as f64, limit, repeat,
+                                   interpolation)
+            })
+            .collect();
+        PaletteLut { palette: palette.to_vec(), limit, repeat, resolution, interpolation, table }
+    }
+
+    /// The color for escape count 'count', as `palette_color_with` would
+    /// give it for 'count' rounded to the table's resolution.
+    pub fn color(&self, count: f64) -> [u8; 3] {
+        let step = (count * self.resolution as f64).round();
+        if step >= 0.0 && step < self.table.len() as f64 {
+            self.table[step as usize]
+        } else {
+            palette_color_with(&self.palette, count, self.limit, self.repeat, self.interpolation)
+        }
+    }
+}
+
+/// The 'size' colors an indexed image is quantized to: black, which the
+/// members of the set are, followed by `size - 1` colors evenly spaced
+/// around one cycle of 'palette'. Without a palette, for grayscale
+/// images, they are instead 'size' gray levels evenly spaced from black
+/// to white. 'size' must be from 2 to 256, the most an 8-bit index can
+/// pick between.
+pub fn indexed_entries(palette: Option<&[[u8; 3]]>, size: usize) -> Vec<[u8; 3]> {
+    assert!((2 ..= 256).contains(&size), "indexed palettes have 2 to 256 colors");
+
+    match palette {
+        Some(palette) => std::iter::once([0, 0, 0])
+            .chain((0 .. size - 1).map(|i| palette_color(palette, i as f64, size as u32 - 1, 1)))
+            .collect(),
+        None => (0 .. size)
+            .map(|i| {
+                let level = (i * 255 / (size - 1)) as u8;
+                [level; 3]
+            })
+            .collect(),
+    }
+}
+
+/// Blend from color 'a' (at t = 0) to color 'b' (at t = 1).
+fn lerp(a: [u8; 3], b: [u8; 3], t: f64) -> [u8; 3] {
+    let mut color = [0; 3];
+    for channel in 0..3 {
+        let value = a[channel] as f64 + (b[channel] as f64 - a[channel] as f64) * t;
+        color[channel] = value.round() as u8;
+    }
+
+    color
+}
+
+/// The reference white of CIELAB: the CIE XYZ of sRGB's D65 white.
+const WHITE: [f64; 3] = [0.950_47, 1.0, 1.088_83];
+
+/// Where CIELAB's cube root gives way to a straight line near black.
+const LAB_DELTA: f64 = 6.0 / 29.0;
+
+/// 'color' in CIELAB, as [L*, a*, b*].
+fn srgb_to_lab(color: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = color.map(|channel| {
+        let channel = channel as f64 / 255.0;
+        if channel <= 0.040_45 { channel / 12.92 } else { ((channel + 0.055) / 1.055).powf(2.4) }
+    });
+    let xyz = [0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b,
+               0.212_672_9 * r + 0.715_152_2 * g + 0.072_175_0 * b,
+               0.019_333_9 * r + 0.119_192_0 * g + 0.950_304_1 * b];
+    let [fx, fy, fz] = [0, 1, 2].map(|i| {
+        let t = xyz[i] / WHITE[i];
+        if t > LAB_DELTA.powi(3) { t.cbrt() } else { t / (3.0 * LAB_DELTA.powi(2)) + 4.0 / 29.0 }
+    });
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// The sRGB color nearest 'lab', clamped into the gamut.
+fn lab_to_srgb(lab: [f64; 3]) -> [u8; 3] {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let f = [fy + lab[1] / 500.0, fy, fy - lab[2] / 200.0];
+    let [x, y, z] = [0, 1, 2].map(|i| {
+        let t = if f[i] > LAB_DELTA {
+            f[i].powi(3)
+        } else {
+            3.0 * LAB_DELTA.powi(2) * (f[i] - 4.0 / 29.0)
+        };
+        t * WHITE[i]
+    });
+    linear_to_srgb(xyz_to_linear([x, y, z]))
+}
+
+/// The linear sRGB components of CIE XYZ color 'xyz'.
+fn xyz_to_linear(xyz: [f64; 3]) -> [f64; 3] {
+    let [x, y, z] = xyz;
+    [ 3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+     -0.969_266_0 * x + 1.876_010_8 * y + 0.041_556_0 * z,
+      0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z]
+}
+
+/// Gamma-encode linear sRGB components, clamped into `0..1`.
+fn linear_to_srgb(linear: [f64; 3]) -> [u8; 3] {
+    linear.map(|channel| {
+        let channel = channel.clamp(0.0, 1.0);
+        let encoded = if channel <= 0.003_130_8 {
+            12.92 * channel
+        } else {
+            1.055 * channel.powf(1.0 / 2.4) - 0.055
+        };
+        (encoded * 255.0).round() as u8
+    })
+}
+
+/// palette_color test
+#[test]
+fn test_palette_color_repeat() {
+    let palette = [[0, 0, 0], [200, 100, 50], [100, 200, 0], [50, 50, 250]];
+    let limit = 256;
+
+    // Halfway through the counts, one cycle is halfway through the
+    // palette while two cycles have come back around to the start.
+    assert_eq!(palette_color(&palette, 128.0, limit, 1), palette[2]);
+    assert_eq!(palette_color(&palette, 128.0, limit, 2), palette[0]);
+
+    // Between stops the colors are blended.
+    assert_eq!(palette_color(&palette, 16.0, limit, 2), [100, 50, 25]);
+
+    // Across the wrap the last stop blends into the first, with no jump.
+    assert_eq!(palette_color(&palette, 112.0, limit, 2), [25, 25, 125]);
+    assert_eq!(palette_color(&palette, 224.0, limit, 1), [25, 25, 125]);
+    assert_eq!(palette_color(&palette, 127.99, limit, 2), [0, 0, 0]);
+}
+
+/// Interpolation test: blending in CIELAB keeps the stops, and takes a
+/// different path between them than sRGB does.
+#[test]
+fn test_interpolation() {
+    for &color in &[[0, 0, 0], [255, 255, 255], [255, 0, 0], [32, 107, 203], [255, 170, 0]] {
+        assert_eq!(lab_to_srgb(srgb_to_lab(color)), color);
+        assert_eq!(Interpolation::Lab.blend(color, [0, 255, 0], 0.0), color);
+    }
+    let white = srgb_to_lab([255, 255, 255]);
+    assert!((white[0] - 100.0).abs() < 1e-3 && white[1].abs() < 1e-3 && white[2].abs() < 1e-3);
+
+    // Red to blue: straight through sRGB is a dark purple, while CIELAB
+    // keeps it about as light as the ends.
+    let (red, blue) = ([255, 0, 0], [0, 0, 255]);
+    assert_eq!(Interpolation::Rgb.blend(red, blue, 0.5), [128, 0, 128]);
+    let lab = Interpolation::Lab.blend(red, blue, 0.5);
+    assert!(lab != [128, 0, 128]);
+    assert!(srgb_to_lab(lab)[0] > srgb_to_lab([128, 0, 128])[0] + 10.0, "{:?}", lab);
+
+    let palette = [red, blue];
+    assert_eq!(palette_color_with(&palette, 1.0, 4, 1, Interpolation::Lab), lab);
+    assert_eq!(palette_color_with(&palette, 1.0, 4, 1, Interpolation::Rgb),
+               palette_color(&palette, 1.0, 4, 1));
+    assert_eq!(Interpolation::from_name("lab"), Some(Interpolation::Lab));
+    assert_eq!(Interpolation::from_name("hsv"), None);
+}
+
+/// PaletteLut test: the table gives what `palette_color` does.
+#[test]
+fn test_palette_lut() {
+    let palette = named("rainbow").unwrap();
+    let (limit, repeat) = (255, 3);
+
+    let lut = PaletteLut::new(&palette, limit, repeat, 1, Interpolation::Rgb);
+    for count in 0 ..= limit {
+        assert_eq!(lut.color(count as f64), palette_color(&palette, count as f64, limit, repeat));
+    }
+
+    // Fractional counts are exact at the table's steps, and otherwise
+    // off by no more than half a step's worth of color.
+    let resolution = DEFAULT_LUT_RESOLUTION;
+    let lut = PaletteLut::new(&palette, limit, repeat, resolution, Interpolation::Rgb);
+    let most_per_step = 255.0 * (palette.len() as u32 * repeat) as f64
+        / (limit * resolution) as f64;
+    for step in 0 .. limit * resolution * 4 {
+        let count = step as f64 / (4 * resolution) as f64;
+        let rounded = (count * resolution as f64).round() / resolution as f64;
+        assert_eq!(lut.color(count), palette_color(&palette, rounded, limit, repeat));
+        let direct = palette_color(&palette, count, limit, repeat);
+        for (a, b) in lut.color(count).iter().zip(&direct) {
+            assert!((*a as f64 - *b as f64).abs() <= most_per_step / 2.0 + 1.0,
+                    "count {}: {:?} vs {:?}", count, lut.color(count), direct);
+        }
+    }
+
+    // Past either end of the table, it's worked out directly.
+    assert_eq!(lut.color(-0.7), palette_color(&palette, -0.7, limit, repeat));
+    assert_eq!(lut.color(300.2), palette_color(&palette, 300.2, limit, repeat));
+
+    // A huge limit doesn't make a huge table; counts past it are still
+    // colored right.
+    let limit = 4_000_000;
+    let lut = PaletteLut::new(&palette, limit, repeat, resolution, Interpolation::Lab);
+    assert_eq!(lut.table.len() as u64, MAX_LUT_STEPS + 1);
+    for count in [0.0, 17.5, 3_999_999.0] {
+        assert_eq!(lut.color(count), palette_color_with(&palette, count, limit, repeat,
+                                                        Interpolation::Lab));
+    }
+}
+
+/// named palette test
+#[test]
+fn test_named_palettes() {
+    for name in NAMES {
+        assert!(named(name).is_some());
+    }
+    assert_eq!(named("nonexistent"), None);
+}
+
+/// palette reverse and rotate test
+#[test]
+fn test_reverse_rotate() {
+    let fire = named("fire").unwrap();
+    assert_eq!(reverse(reverse(fire.clone())), fire);
+    assert_eq!(rotate(fire.clone(), fire.len()), fire);
+    assert_eq!(rotate(fire.clone(), 0), fire);
+
+    assert_eq!(reverse(fire.clone())[0], fire[4]);
+    assert_eq!(rotate(fire.clone(), 2)[0], fire[2]);
+    assert_eq!(rotate(fire.clone(), 2)[4], fire[1]);
+    assert_eq!(rotate(fire.clone(), 7), rotate(fire.clone(), 2));
+    // Reversed then rotated: start from the second to last color, backwards.
+    assert_eq!(rotate(reverse(fire.clone()), 1)[..2], [fire[3], fire[2]]);
+    assert_eq!(rotate(Vec::new(), 3), Vec::<[u8; 3]>::new());
+}
+
+/// ultra palette test: the stops come out where they're defined.
+#[test]
+fn test_ultra_palette() {
+    let palette = named("ultra").unwrap();
+    assert_eq!(palette.len(), ULTRA_STEPS);
+
+    // With the palette spread over `0..limit` once, a stop at position p
+    // is the color at count p * limit.
+    let limit = ULTRA_STEPS as u32;
+    for &(position, color) in ULTRA_STOPS {
+        let count = (position * limit as f64).round();
+        assert_eq!(palette_color(&palette, count, limit, 1), color, "stop at {}", position);
+        // Repeating it puts each stop there twice.
+        assert_eq!(palette_color(&palette, count / 2.0, limit, 2), color);
+        assert_eq!(palette_color(&palette, (count + limit as f64) / 2.0, limit, 2), color);
+    }
+
+    // Halfway between the first two stops, and across the wrap from the
+    // last back to the first.
+    assert_eq!(palette_color(&palette, 32.0, limit, 1), [16, 57, 152]);
+    assert_eq!(palette_color(&palette, 399.0, limit, 1), lerp([0, 2, 0], [0, 7, 100], 56.0 / 57.0));
+}
+
+/// spectral palette test: low counts are red, high counts violet.
+#[test]
+fn test_spectral_palette() {
+    let palette = named("spectral").unwrap();
+    assert_eq!(palette.len(), SPECTRAL_STEPS);
+
+    // Spread over `0..limit` once, count 'i' is palette entry 'i'.
+    let limit = SPECTRAL_STEPS as u32;
+    let [r, g, b] = palette_color(&palette, 0.0, limit, 1);
+    assert!(r == 255 && g < 64 && b < 64, "low end is {:?}", [r, g, b]);
+    let [r, g, b] = palette_color(&palette, (limit - 1) as f64, limit, 1);
+    assert!(b == 255 && r > g && r < b, "high end is {:?}", [r, g, b]);
+
+    // In between, the spectrum passes through yellow, green and blue.
+    let [r, g, b] = wavelength_to_srgb(580.0);
+    assert!(r == 255 && g > 150 && b < 64, "580nm is {:?}", [r, g, b]);
+    let [r, g, b] = wavelength_to_srgb(530.0);
+    assert!(g == 255 && r < g && b < g, "530nm is {:?}", [r, g, b]);
+    let [r, g, b] = wavelength_to_srgb(460.0);
+    assert!(b == 255 && r < b && g < b, "460nm is {:?}", [r, g, b]);
+}
+
+/// viridis and cividis test: they run from their first stop to their
+/// last, getting lighter all the way.
+#[test]
+fn test_ramp_palettes() {
+    for &(name, stops) in &[("viridis", VIRIDIS_STOPS), ("cividis", CIVIDIS_STOPS)] {
+        let palette = named(name).unwrap();
+        assert_eq!(palette.len(), RAMP_STEPS);
+
+        // Spread over `0..limit` once, count 'i' is palette entry 'i'.
+        let limit = RAMP_STEPS as u32;
+        assert_eq!(palette_color(&palette, 0.0, limit, 1), stops[0]);
+        assert_eq!(palette_color(&palette, (limit - 1) as f64, limit, 1), stops[stops.len() - 1]);
+
+        for pair in palette.windows(2) {
+            let (lighter, darker) = (srgb_to_lab(pair[1])[0], srgb_to_lab(pair[0])[0]);
+            assert!(lighter >= darker, "{}: {:?} then {:?}", name, pair[0], pair[1]);
+        }
+    }
+}
+
+/// indexed_entries test
+#[test]
+fn test_indexed_entries() {
+    assert_eq!(indexed_entries(None, 2), vec![[0, 0, 0], [255, 255, 255]]);
+    assert_eq!(indexed_entries(None, 4), vec![[0; 3], [85; 3], [170; 3], [255; 3]]);
+
+    // Black, then the stops themselves when there's room for exactly them.
+    let palette = named("rainbow").unwrap();
+    let entries = indexed_entries(Some(&palette), palette.len() + 1);
+    assert_eq!(entries[0], [0, 0, 0]);
+    assert_eq!(&entries[1..], &palette[..]);
+    assert_eq!(indexed_entries(Some(&palette), 256).len(), 256);
+}
@@ -0,0 +1,128 @@
+use num::Complex;
+use std::str::FromStr;
+
+/// Parse a command-line string as a coordinate pair in multiple formats
+/// example: `"400x600"` , `"1.0,1.5"`
+/// 
+/// The string should be in the following format <left><sep><right>
+/// where <sep> is a seperator argument and left and right are both 
+/// strings that can be parsed by `T::from_str`.
+///
+/// The separator may also appear inside the numbers, like `-` in
+/// `"-1.5--2"`, so every place it appears is tried as the split. The
+/// pair is only returned if exactly one of them gives two numbers; if
+/// several do, as `e` can in `"1e2e3"`, the string is ambiguous and
+/// None is returned.
+pub fn parse_pair<T:FromStr>(s: &str, separator: char) -> Option<(T, T)> {
+    let mut pairs = s.match_indices(separator)
+        .filter_map(|(index, _)| {
+            match(T::from_str(&s[..index]), T::from_str(&s[index + separator.len_utf8()..])) {
+                (Ok(i), Ok(r)) => Some((i, r)),
+                _ => None
+            }
+        });
+    match (pairs.next(), pairs.next()) {
+        (Some(pair), None) => Some(pair),
+        _ => None
+    }
+}
+
+/// Parse a pair of floating-point numbers seperated by a comma as a complex number
+pub fn parse_complex(s: &str) -> Option<Complex<f64>> {
+    parse_pair(s, ',').map(|(re, im)| Complex { re, im })
+}
+
+/// Format 'c' as `parse_complex` reads it, `RE,IM`, with every digit it
+/// takes for the two parts to parse back to exactly the same `f64`s.
+/// Coordinates written this way reload identically however deep the zoom
+/// that found them, where the last digits decide what the view shows.
+/// The parts are in scientific notation, which stays short for the
+/// widths of deep zooms and the tiny coordinates near the real axis.
+pub fn format_complex(c: Complex<f64>) -> String {
+    format!("{:e},{:e}", c.re, c.im)
+}
+
+/// Parse a color given as three comma-separated components from 0 to
+/// 255, like `"255,128,0"`.
+pub fn parse_rgb(s: &str) -> Option<[u8; 3]> {
+    let mut components = s.split(',').map(u8::from_str);
+    match (components.next(), components.next(), components.next(), components.next()) {
+        (Some(Ok(red)), Some(Ok(green)), Some(Ok(blue)), None) => Some([red, green, blue]),
+        _ => None,
+    }
+}
+
+/// Parse a rectangle of pixels given as `X,Y,W,H`: the column and row of
+/// its top left corner, then its width and height, like `"10,20,300,200"`.
+pub fn parse_rect(s: &str) -> Option<(usize, usize, usize, usize)> {
+    let mut parts = s.split(',').map(usize::from_str);
+    match (parts.next(), parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(x)), Some(Ok(y)), Some(Ok(width)), Some(Ok(height)), None) =>
+            Some((x, y, width, height)),
+        _ => None,
+    }
+}
+
+/// parse_pair test
+#[test]
+fn	test_parse_pair() {
+    assert_eq!(parse_pair::<i32>("", ','), None);
+    assert_eq!(parse_pair::<i32>("10,",	','), None);
+    assert_eq!(parse_pair::<i32>(",10",	','), None);
+    assert_eq!(parse_pair::<i32>("10,20", ','),	Some((10,	20)));
+    assert_eq!(parse_pair::<i32>("10,20xy",	','), None);
+    assert_eq!(parse_pair::<f64>("0.5x", 'x'),	None);
+    assert_eq!(parse_pair::<f64>("0.5x1.5",	'x'), Some((0.5,	1.5)));
+    assert_eq!(parse_pair::<f64>("1e-3,2e-4", ','), Some((1e-3, 2e-4)));
+    assert_eq!(parse_pair::<f64>("1e-3x2e-4", 'x'), Some((1e-3, 2e-4)));
+
+    // A separator that also appears in the numbers splits where it
+    // leaves two of them, and nowhere if that's more than one place.
+    assert_eq!(parse_pair::<f64>("-1.5--2", '-'), Some((-1.5, -2.0)));
+    assert_eq!(parse_pair::<f64>("-1e-3-2", '-'), Some((-1e-3, 2.0)));
+    assert_eq!(parse_pair::<f64>("1.5.25", '.'), None);
+    assert_eq!(parse_pair::<f64>("1e2e3", 'e'), None);
+    assert_eq!(parse_pair::<i32>("1-2-3", '-'), None);
+}
+
+/// parse_complex test
+#[test]
+fn	test_parse_complex() {
+    assert_eq!(parse_complex("1.25,-0.0625"), Some(Complex{	re:	1.25,	im:	-0.0625	}));
+    assert_eq!(parse_complex(",-0.0625"),	None);
+}
+
+/// format_complex test: deep-zoom coordinates survive the round trip
+/// exactly, last bit included.
+#[test]
+fn test_format_complex() {
+    let deep: Complex<f64> = Complex { re: -0.743_643_887_037_158_7, im: 0.131_825_904_205_311_97 };
+    let nudged = Complex { re: f64::from_bits(deep.re.to_bits() + 1), im: deep.im };
+    for &c in &[deep, nudged, Complex { re: -2.0, im: 1e-300 }, Complex { re: 0.1 + 0.2, im: -0.0 }] {
+        let parsed = parse_complex(&format_complex(c)).unwrap();
+        assert_eq!((parsed.re.to_bits(), parsed.im.to_bits()), (c.re.to_bits(), c.im.to_bits()),
+                   "{}", format_complex(c));
+    }
+    assert_ne!(format_complex(deep), format_complex(nudged));
+    assert_eq!(format_complex(Complex { re: -2.0, im: 0.5 }), "-2e0,5e-1");
+}
+
+/// parse_rgb test
+#[test]
+fn test_parse_rgb() {
+    assert_eq!(parse_rgb("255,128,0"), Some([255, 128, 0]));
+    assert_eq!(parse_rgb("255,128"), None);
+    assert_eq!(parse_rgb("255,128,0,0"), None);
+    assert_eq!(parse_rgb("256,0,0"), None);
+    assert_eq!(parse_rgb("red"), None);
+}
+
+/// parse_rect test
+#[test]
+fn test_parse_rect() {
+    assert_eq!(parse_rect("10,20,300,200"), Some((10, 20, 300, 200)));
+    assert_eq!(parse_rect("10,20,300"), None);
+    assert_eq!(parse_rect("10,20,300,200,1"), None);
+    assert_eq!(parse_rect("-1,0,5,5"), None);
+    assert_eq!(parse_rect("a,b,c,d"), None);
+}
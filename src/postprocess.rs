@@ -0,0 +1,616 @@
+use image::{ImageBuffer, Luma, LumaA, Pixel, Rgb, Rgba};
+use image::imageops;
+
+use crate::field::Escape;
+use crate::view::PixelBounds;
+
+/// Find the smallest rectangle holding every non-background (non-black)
+/// pixel, as `(left, top, width, height)`. Returns None if the whole image
+/// is background. Each pixel is 'channels' bytes long, and is background
+/// only if all of them are zero.
+pub fn bounding_box(pixels: &[u8], bounds: PixelBounds, channels: usize)
+    -> Option<(usize, usize, usize, usize)> {
+    assert!(pixels.len() == bounds.pixel_count() * channels);
+
+    let mut found: Option<(usize, usize, usize, usize)> = None;
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let offset = (row * bounds.width + column) * channels;
+            if pixels[offset .. offset + channels].iter().all(|&byte| byte == 0) {
+                continue;
+            }
+            found = Some(match found {
+                None => (column, row, column, row),
+                Some((left, top, right, bottom)) =>
+                    (left.min(column), top.min(row), right.max(column), bottom.max(row)),
+            });
+        }
+    }
+
+    found.map(|(left, top, right, bottom)| (left, top, right - left + 1, bottom - top + 1))
+}
+
+/// What to do with an image that came out a single solid color, such as
+/// a view entirely inside or entirely outside the set, which is most
+/// likely a mistaken view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmptyView {
+    /// Write it as it is, without a word.
+    Ignore,
+    /// Write it as it is, but warn that it shows nothing.
+    Warn,
+    /// Warn, and write a single pixel of that color instead, which
+    /// costs next to nothing to encode.
+    Solid,
+}
+
+/// The color every pixel of 'pixels' has, 'channels' bytes apiece, or
+/// None if any two pixels differ or there are none. Stops at the first
+/// pixel that differs, so images with any structure are settled quickly.
+pub fn solid_color(pixels: &[u8], channels: usize) -> Option<&[u8]> {
+    let first = pixels.get(..channels)?;
+    if pixels.chunks(channels).all(|pixel| pixel == first) {
+        Some(first)
+    } else {
+        None
+    }
+}
+
+/// Crop the rendered image to the bounding box of its non-background
+/// pixels, returning the cropped pixels and their bounds. The image is
+/// returned unchanged if it has no background margins to remove, or if
+/// it is entirely background. 'channels' is 1 for grayscale pixels, 2
+/// for 16-bit grayscale, 3 for RGB and 4 for RGBA.
+pub fn autocrop(pixels: Vec<u8>, bounds: PixelBounds, channels: usize) -> (Vec<u8>, PixelBounds) {
+    let rect = match bounding_box(&pixels, bounds, channels) {
+        Some(rect) if rect != (0, 0, bounds.width, bounds.height) => rect,
+        _ => return (pixels, bounds),
+    };
+
+    let cropped = match channels {
+        1 => crop::<Luma<u8>>(pixels, bounds, rect),
+        // Both bytes of a 16-bit sample move together, as a pair would.
+        2 => crop::<LumaA<u8>>(pixels, bounds, rect),
+        3 => crop::<Rgb<u8>>(pixels, bounds, rect),
+        4 => crop::<Rgba<u8>>(pixels, bounds, rect),
+        _ => panic!("unsupported number of channels: {}", channels),
+    };
+
+    (cropped, PixelBounds::new(rect.2, rect.3))
+}
+
+/// Cut the rectangle `(left, top, width, height)` out of a buffer of
+/// pixels of type 'P'.
+fn crop<P>(pixels: Vec<u8>, bounds: PixelBounds, (left, top, width, height): (usize, usize, usize, usize))
+    -> Vec<u8>
+    where P: Pixel<Subpixel = u8> + 'static
+{
+    let mut image = ImageBuffer::<P, Vec<u8>>::from_raw(bounds.width as u32, bounds.height as u32, pixels)
+        .expect("pixel buffer does not match the image bounds");
+
+    imageops::crop(&mut image, left as u32, top as u32, width as u32, height as u32)
+        .to_image()
+        .into_raw()
+}
+
+/// Repeat the rendered image in a grid of 'rows' by 'columns' copies,
+/// returning the composite and its bounds, for checking that a view
+/// tiles seamlessly or for making wallpaper. 'channels' is as for
+/// `autocrop`.
+pub fn tile(pixels: Vec<u8>, bounds: PixelBounds, channels: usize, rows: usize, columns: usize)
+    -> (Vec<u8>, PixelBounds) {
+    let tiled = match channels {
+        1 => repeat::<Luma<u8>>(pixels, bounds, rows, columns),
+        2 => repeat::<LumaA<u8>>(pixels, bounds, rows, columns),
+        3 => repeat::<Rgb<u8>>(pixels, bounds, rows, columns),
+        4 => repeat::<Rgba<u8>>(pixels, bounds, rows, columns),
+        _ => panic!("unsupported number of channels: {}", channels),
+    };
+
+    (tiled, PixelBounds::new(bounds.width * columns, bounds.height * rows))
+}
+
+/// Composite 'rows' by 'columns' copies of a buffer of pixels of type
+/// 'P' into one image.
+fn repeat<P>(pixels: Vec<u8>, bounds: PixelBounds, rows: usize, columns: usize) -> Vec<u8>
+    where P: Pixel<Subpixel = u8> + 'static
+{
+    let image = ImageBuffer::<P, Vec<u8>>::from_raw(bounds.width as u32, bounds.height as u32, pixels)
+        .expect("pixel buffer does not match the image bounds");
+
+    let mut grid = ImageBuffer::<P, Vec<u8>>::new((bounds.width * columns) as u32,
+                                                  (bounds.height * rows) as u32);
+    for row in 0 .. rows {
+        for column in 0 .. columns {
+            imageops::replace(&mut grid, &image, (column * bounds.width) as u32,
+                              (row * bounds.height) as u32);
+        }
+    }
+
+    grid.into_raw()
+}
+
+/// Turn a height field, one height per pixel, into an RGB normal map.
+///
+/// The surface normal of each pixel comes from the slope of the heights
+/// across it, scaled by 'strength'. Slopes are central differences of
+/// the neighbouring heights, or one-sided differences on the border.
+/// The normal's x (rightwards), y (upwards) and z (out of the image)
+/// components, each from -1 to 1, are stored in red, green and blue as
+/// 0 to 255, so flat ground is (128, 128, 255).
+pub fn normal_map(heights: &[f64], bounds: PixelBounds, strength: f64) -> Vec<u8> {
+    assert!(heights.len() == bounds.pixel_count());
+
+    let height = |column: usize, row: usize| heights[row * bounds.width + column];
+    // The slope along one axis at 'i', of 'len' positions, given the
+    // height at each of them.
+    let slope = |i: usize, len: usize, at: &dyn Fn(usize) -> f64| {
+        let (before, after) = (i.saturating_sub(1), (i + 1).min(len - 1));
+        if before == after {
+            0.0
+        } else {
+            (at(after) - at(before)) / (after - before) as f64
+        }
+    };
+    let encode = |component: f64| ((component + 1.0) / 2.0 * 255.0).round() as u8;
+
+    let mut pixels = Vec::with_capacity(bounds.pixel_count() * 3);
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let dx = slope(column, bounds.width, &|column| height(column, row)) * strength;
+            let dy = slope(row, bounds.height, &|row| height(column, row)) * strength;
+            // Rows run downwards, so the slope upwards is -dy.
+            let length = (dx * dx + dy * dy + 1.0).sqrt();
+            pixels.extend_from_slice(&[encode(-dx / length), encode(dy / length), encode(1.0 / length)]);
+        }
+    }
+
+    pixels
+}
+
+/// Draw iso-escape-count contour lines over the rendered image.
+///
+/// 'field' holds the escape outcome of every pixel. A pixel is drawn in
+/// 'color' if one of its four neighbours has a count in a lower band of
+/// 'interval' iterations, that is, if a multiple of 'interval' lies
+/// between the neighbour's count (exclusive) and its own (inclusive).
+/// Only the higher side of each crossing is drawn, so the lines are one
+/// pixel wide. Interior pixels have no count and never take part. The
+/// color is reduced to its luma for grayscale images, and made opaque
+/// for RGBA ones.
+pub fn draw_contours(pixels: &mut [u8], bounds: PixelBounds, channels: usize, field: &[Escape],
+                     interval: u32, color: [u8; 3]) {
+    assert!(pixels.len() == bounds.pixel_count() * channels);
+    assert!(field.len() == bounds.pixel_count());
+    assert!(interval > 0, "contour interval must be at least one iteration");
+
+    let [red, green, blue] = color;
+    let luma = ((299 * red as u32 + 587 * green as u32 + 114 * blue as u32 + 500) / 1000) as u8;
+    let ink: &[u8] = match channels {
+        1 => &[luma],
+        3 => &[red, green, blue],
+        4 => &[red, green, blue, 255],
+        _ => panic!("unsupported number of channels: {}", channels),
+    };
+
+    let band = |column: usize, row: usize| {
+        field[row * bounds.width + column].count().map(|count| count / interval)
+    };
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let own = match band(column, row) {
+                Some(own) => own,
+                None => continue,
+            };
+            let neighbours = [
+                (column.checked_sub(1), Some(row)),
+                (Some(column + 1).filter(|&column| column < bounds.width), Some(row)),
+                (Some(column), row.checked_sub(1)),
+                (Some(column), Some(row + 1).filter(|&row| row < bounds.height)),
+            ];
+            let crossed = neighbours.iter().any(|&neighbour| match neighbour {
+                (Some(column), Some(row)) => band(column, row).is_some_and(|other| other < own),
+                _ => false,
+            });
+            if crossed {
+                let offset = (row * bounds.width + column) * channels;
+                pixels[offset .. offset + channels].copy_from_slice(ink);
+            }
+        }
+    }
+}
+
+/// The magnitude of the Sobel gradient of a height field, one height
+/// per pixel, at every pixel.
+///
+/// Each pixel's gradient is taken over the 3x3 block around it with the
+/// usual Sobel weights. On the border, positions past the edge take the
+/// height of the nearest pixel inside, so a flat field gives zero all
+/// the way to the edges.
+pub fn sobel(heights: &[f64], bounds: PixelBounds) -> Vec<f64> {
+    assert!(heights.len() == bounds.pixel_count());
+
+    let height = |column: usize, row: usize, dx: isize, dy: isize| {
+        let column = (column as isize + dx).clamp(0, bounds.width as isize - 1) as usize;
+        let row = (row as isize + dy).clamp(0, bounds.height as isize - 1) as usize;
+        heights[row * bounds.width + column]
+    };
+
+    let mut magnitudes = Vec::with_capacity(bounds.pixel_count());
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let at = |dx, dy| height(column, row, dx, dy);
+            let gx = at(1, -1) + 2.0 * at(1, 0) + at(1, 1) - at(-1, -1) - 2.0 * at(-1, 0) - at(-1, 1);
+            let gy = at(-1, 1) + 2.0 * at(0, 1) + at(1, 1) - at(-1, -1) - 2.0 * at(0, -1) - at(1, -1);
+            magnitudes.push((gx * gx + gy * gy).sqrt());
+        }
+    }
+
+    magnitudes
+}
+
+/// Brighten the rendered image where the escape counts change fastest,
+/// so the filigree around the set stands out.
+///
+/// 'magnitudes' holds the `sobel` response of every pixel. Each is taken
+/// relative to the largest in the image and multiplied by 'strength',
+/// and the pixel's color bytes are moved that fraction of the way to
+/// white; the strongest edges turn fully white at a strength of one. An
+/// alpha byte is left as it is.
+pub fn emphasize_edges(pixels: &mut [u8], bounds: PixelBounds, channels: usize, magnitudes: &[f64],
+                       strength: f64) {
+    assert!(pixels.len() == bounds.pixel_count() * channels);
+    assert!(magnitudes.len() == bounds.pixel_count());
+
+    let largest = magnitudes.iter().cloned().fold(0.0, f64::max);
+    if largest == 0.0 {
+        return;
+    }
+    for (pixel, &magnitude) in pixels.chunks_mut(channels).zip(magnitudes) {
+        let weight = (magnitude / largest * strength).clamp(0.0, 1.0);
+        for byte in pixel.iter_mut().take(3) {
+            *byte += ((255 - *byte) as f64 * weight).round() as u8;
+        }
+    }
+}
+
+/// Darken the rendered image towards its corners, like the falloff of a
+/// camera lens. Each pixel's color bytes are scaled by one less
+/// 'strength' times the square of its distance from the center, as a
+/// fraction of the distance to a corner, so the corners lose 'strength'
+/// of their brightness and the center none; at a strength of one or more
+/// they are black. An alpha byte is left as it is.
+pub fn vignette(pixels: &mut [u8], bounds: PixelBounds, channels: usize, strength: f64) {
+    assert!(pixels.len() == bounds.pixel_count() * channels);
+
+    // Distances are measured between pixel centers.
+    let center = ((bounds.width as f64 - 1.0) / 2.0, (bounds.height as f64 - 1.0) / 2.0);
+    let corner = center.0 * center.0 + center.1 * center.1;
+    if strength == 0.0 || corner == 0.0 {
+        return;
+    }
+    for (i, pixel) in pixels.chunks_mut(channels).enumerate() {
+        let (dx, dy) = ((i % bounds.width) as f64 - center.0, (i / bounds.width) as f64 - center.1);
+        let scale = (1.0 - strength * (dx * dx + dy * dy) / corner).clamp(0.0, 1.0);
+        for byte in pixel.iter_mut().take(3) {
+            *byte = (*byte as f64 * scale).round() as u8;
+        }
+    }
+}
+
+/// Soften the rendered image with a Gaussian blur of standard deviation
+/// 'radius' pixels, for thumbnails or a hazier look. A radius of 0
+/// returns the pixels as they are. 'channels' is 1 for grayscale, 3 for
+/// RGB and 4 for RGBA, whose alpha is blurred along with the colors.
+pub fn blur(pixels: Vec<u8>, bounds: PixelBounds, channels: usize, radius: f32) -> Vec<u8> {
+    if radius == 0.0 {
+        return pixels;
+    }
+
+    match channels {
+        1 => gaussian::<Luma<u8>>(pixels, bounds, radius),
+        3 => gaussian::<Rgb<u8>>(pixels, bounds, radius),
+        4 => gaussian::<Rgba<u8>>(pixels, bounds, radius),
+        _ => panic!("unsupported number of channels: {}", channels),
+    }
+}
+
+/// Blur a buffer of pixels of type 'P' with `imageops::blur`.
+fn gaussian<P>(pixels: Vec<u8>, bounds: PixelBounds, radius: f32) -> Vec<u8>
+    where P: Pixel<Subpixel = u8> + 'static
+{
+    let image = ImageBuffer::<P, Vec<u8>>::from_raw(bounds.width as u32, bounds.height as u32, pixels)
+        .expect("pixel buffer does not match the image bounds");
+
+    imageops::blur(&image, radius).into_raw()
+}
+
+/// Shrink the rendered image 'divisor' times in each direction by
+/// averaging every 'divisor' by 'divisor' block of pixels (a box filter),
+/// for a thumbnail that is quicker and smoother than rendering small.
+/// Blocks cut short by the right or bottom edge average the pixels they
+/// have. Each of the 'channels' bytes of a pixel is averaged on its own.
+/// Returns the thumbnail and its bounds.
+pub fn downscale(pixels: &[u8], bounds: PixelBounds, channels: usize, divisor: usize)
+    -> (Vec<u8>, PixelBounds) {
+    assert!(pixels.len() == bounds.pixel_count() * channels && divisor > 0);
+
+    let small = PixelBounds::new(bounds.width.div_ceil(divisor), bounds.height.div_ceil(divisor));
+    let mut shrunk = Vec::with_capacity(small.pixel_count() * channels);
+    for top in (0 .. bounds.height).step_by(divisor) {
+        let rows = top .. (top + divisor).min(bounds.height);
+        for left in (0 .. bounds.width).step_by(divisor) {
+            let columns = left .. (left + divisor).min(bounds.width);
+            let count = (rows.len() * columns.len()) as u32;
+            for channel in 0 .. channels {
+                let sum: u32 = rows.clone()
+                    .flat_map(|row| columns.clone().map(move |column| row * bounds.width + column))
+                    .map(|pixel| pixels[pixel * channels + channel] as u32)
+                    .sum();
+                shrunk.push(((sum + count / 2) / count) as u8);
+            }
+        }
+    }
+
+    (shrunk, small)
+}
+
+/// Map every pixel to the nearest of the colors 'entries', by straight
+/// distance in RGB, and return the index of that entry for each pixel.
+/// Pixels are one gray byte ('channels' 1) or three RGB bytes
+/// ('channels' 3). Ties go to the earlier entry.
+pub fn quantize(pixels: &[u8], channels: usize, entries: &[[u8; 3]]) -> Vec<u8> {
+    assert!(!entries.is_empty() && entries.len() <= 256);
+
+    let distance = |a: [u8; 3], b: [u8; 3]| {
+        a.iter().zip(&b).map(|(&a, &b)| (a as i32 - b as i32).pow(2)).sum::<i32>()
+    };
+    pixels.chunks(channels)
+        .map(|pixel| {
+            let color = match *pixel {
+                [gray] => [gray; 3],
+                [red, green, blue] => [red, green, blue],
+                _ => panic!("unsupported number of channels: {}", channels),
+            };
+            (0 .. entries.len()).min_by_key(|&i| distance(color, entries[i])).unwrap() as u8
+        })
+        .collect()
+}
+
+/// normal_map test
+#[test]
+fn test_normal_map() {
+    let bounds = PixelBounds::new(4, 3);
+    let flat = normal_map(&[7.5; 12], bounds, 1.0);
+    assert!(flat.chunks(3).all(|pixel| pixel == [128, 128, 255]));
+
+    // Rising to the right tilts every normal, border pixels included, to
+    // the left: (-1, 0, 1) / sqrt(2).
+    let ramp: Vec<f64> = (0..12).map(|i| (i % 4) as f64).collect();
+    let tilted = normal_map(&ramp, bounds, 1.0);
+    assert!(tilted.chunks(3).all(|pixel| pixel == [37, 128, 218]), "{:?}", tilted);
+
+    // Rising down the image tilts the normals upwards, and more steeply
+    // with a higher strength.
+    let ramp: Vec<f64> = (0..12).map(|i| (i / 4) as f64).collect();
+    assert!(normal_map(&ramp, bounds, 1.0).chunks(3).all(|pixel| pixel == [128, 218, 218]));
+    let steep = normal_map(&ramp, bounds, 3.0);
+    assert!(steep[1] > 218 && steep[2] < 218);
+
+    // A single pixel has no neighbours to slope towards.
+    assert_eq!(normal_map(&[1.0], PixelBounds::new(1, 1), 1.0), vec![128, 128, 255]);
+}
+
+/// solid_color test: a view inside the main cardioid is one color, and
+/// one that crosses the boundary isn't.
+#[test]
+fn test_solid_color() {
+    use num::Complex;
+    use crate::render::{Coloring, Shading, Supersampling, Threading, render_image};
+    use crate::view::ComplexView;
+
+    let bounds = PixelBounds::new(16, 12);
+    let render = |upper_left, lower_right| {
+        render_image(bounds, ComplexView::new(upper_left, lower_right), Shading::EscapeTime, 255,
+                     Coloring::Gray, Supersampling::none(), None, Threading::new(1)).unwrap()
+    };
+    let interior = render(Complex { re: -0.1, im: 0.1 }, Complex { re: 0.1, im: -0.1 });
+    assert_eq!(solid_color(&interior, 1), Some(&[0][..]));
+    let boundary = render(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert_eq!(solid_color(&boundary, 1), None);
+
+    assert_eq!(solid_color(&[1, 2, 3, 1, 2, 3], 3), Some(&[1, 2, 3][..]));
+    assert_eq!(solid_color(&[1, 2, 3, 1, 2, 4], 3), None);
+    assert_eq!(solid_color(&[], 3), None);
+}
+
+/// autocrop test
+#[test]
+fn test_autocrop() {
+    // A 6x5 black image with a non-black 3x2 rectangle at (2, 1).
+    let bounds = PixelBounds::new(6, 5);
+    let mut pixels = vec![0; bounds.pixel_count()];
+    for row in 1..3 {
+        for column in 2..5 {
+            pixels[row * bounds.width + column] = 10 * row as u8 + column as u8;
+        }
+    }
+
+    assert_eq!(bounding_box(&pixels, bounds, 1), Some((2, 1, 3, 2)));
+    let (cropped, cropped_bounds) = autocrop(pixels, bounds, 1);
+    assert_eq!(cropped_bounds, PixelBounds::new(3, 2));
+    assert_eq!(cropped, vec![12, 13, 14, 22, 23, 24]);
+
+    // Nothing to crop when the fractal fills the image, or is absent.
+    let small = PixelBounds::new(3, 2);
+    let full = vec![7; 6];
+    assert_eq!(autocrop(full.clone(), small, 1), (full, small));
+    let empty = vec![0; 6];
+    assert_eq!(autocrop(empty.clone(), small, 1), (empty, small));
+
+    // An RGB pixel is background only if all three channels are zero.
+    let rgb = vec![0, 0, 0, 0, 5, 0, 0, 0, 0,
+                   0, 0, 0, 1, 2, 3, 0, 0, 0];
+    assert_eq!(autocrop(rgb, small, 3), (vec![0, 5, 0, 1, 2, 3], PixelBounds::new(1, 2)));
+}
+
+/// draw_contours test
+#[test]
+fn test_draw_contours() {
+    // Counts rising by 3 along each row of a 5x2 image, with an interior
+    // pixel at the end of the second.
+    let bounds = PixelBounds::new(5, 2);
+    let field = [Escape::Escaped(0), Escape::Escaped(3), Escape::Escaped(6), Escape::Escaped(9),
+                 Escape::Escaped(12),
+                 Escape::Escaped(1), Escape::Escaped(4), Escape::Escaped(7), Escape::Escaped(10),
+                 Escape::Interior];
+
+    // Every 5: 3 | 6 straddles 5 and 9 | 12 straddles 10 in the first
+    // row, and 4 | 7 and 7 | 10 in the second. 9 over 10 is a crossing
+    // too, but 10 is already drawn.
+    let mut pixels = vec![100; bounds.pixel_count()];
+    draw_contours(&mut pixels, bounds, 1, &field, 5, [255, 255, 255]);
+    assert_eq!(pixels, vec![100, 100, 255, 100, 255,
+                            100, 100, 255, 255, 100]);
+
+    // Every 10 only 9 | 12 and 9 | 10; the interior pixel never counts.
+    let mut pixels = vec![0; bounds.pixel_count() * 4];
+    draw_contours(&mut pixels, bounds, 4, &field, 10, [10, 20, 30]);
+    let drawn: Vec<usize> = pixels.chunks(4).enumerate()
+        .filter(|&(_, pixel)| pixel != [0, 0, 0, 0])
+        .map(|(i, pixel)| {
+            assert_eq!(pixel, [10, 20, 30, 255]);
+            i
+        })
+        .collect();
+    assert_eq!(drawn, vec![4, 8]);
+
+    // An interval of one marks every step up between neighbours.
+    let mut pixels = vec![0; bounds.pixel_count() * 3];
+    draw_contours(&mut pixels, bounds, 3, &field, 1, [0, 0, 255]);
+    assert_eq!(pixels.chunks(3).filter(|&pixel| pixel == [0, 0, 255]).count(), 8);
+}
+
+/// sobel and emphasize_edges test
+#[test]
+fn test_sobel() {
+    // A flat field has no edges, even on the border.
+    let bounds = PixelBounds::new(5, 4);
+    assert!(sobel(&[42.0; 20], bounds).iter().all(|&magnitude| magnitude == 0.0));
+    assert_eq!(sobel(&[1.0], PixelBounds::new(1, 1)), vec![0.0]);
+
+    // A step from 0 to 10 between the second and third columns shows up
+    // on both sides of it, and nowhere else.
+    let step: Vec<f64> = (0..20).map(|i| if i % 5 < 2 { 0.0 } else { 10.0 }).collect();
+    let magnitudes = sobel(&step, bounds);
+    for (i, &magnitude) in magnitudes.iter().enumerate() {
+        if i % 5 == 1 || i % 5 == 2 {
+            assert_eq!(magnitude, 40.0);
+        } else {
+            assert_eq!(magnitude, 0.0);
+        }
+    }
+
+    // The edge is brightened, fully at strength one, and alpha kept.
+    let mut pixels = vec![100; bounds.pixel_count() * 4];
+    emphasize_edges(&mut pixels, bounds, 4, &magnitudes, 1.0);
+    assert_eq!(&pixels[4 .. 8], &[255, 255, 255, 100]);
+    assert_eq!(&pixels[0 .. 4], &[100, 100, 100, 100]);
+    let mut gray = vec![100; bounds.pixel_count()];
+    emphasize_edges(&mut gray, bounds, 1, &magnitudes, 0.5);
+    assert_eq!(&gray[0 .. 5], &[100, 178, 178, 100, 100]);
+}
+
+/// vignette test
+#[test]
+fn test_vignette() {
+    let bounds = PixelBounds::new(5, 3);
+    let gray = vec![200; bounds.pixel_count()];
+    let mut darkened = gray.clone();
+    vignette(&mut darkened, bounds, 1, 0.5);
+    assert_eq!(darkened[7], 200);
+    assert_eq!(darkened[0], 100);
+    assert_eq!(darkened[14], 100);
+    assert!(darkened[2] < 200 && darkened[2] > 100);
+
+    // No strength changes nothing; the alpha byte is never touched.
+    let mut unchanged = gray.clone();
+    vignette(&mut unchanged, bounds, 1, 0.0);
+    assert_eq!(unchanged, gray);
+    let mut rgba = vec![200; bounds.pixel_count() * 4];
+    vignette(&mut rgba, bounds, 4, 2.0);
+    assert_eq!(rgba[..4], [0, 0, 0, 200]);
+    assert_eq!(rgba[7 * 4 .. 8 * 4], [200, 200, 200, 200]);
+}
+
+/// blur test
+#[test]
+fn test_blur() {
+    let bounds = PixelBounds::new(8, 8);
+    let checkerboard: Vec<u8> = (0 .. 64).map(|i| if (i % 8 + i / 8) % 2 == 0 { 0 } else { 255 }).collect();
+    assert_eq!(blur(checkerboard.clone(), bounds, 1, 0.0), checkerboard);
+
+    // A blurred checkerboard pulls towards its average gray.
+    let blurred = blur(checkerboard.clone(), bounds, 1, 1.5);
+    assert_eq!(blurred.len(), checkerboard.len());
+    assert_ne!(blurred, checkerboard);
+    assert!(blurred[3 * 8 + 3] > 0 && blurred[3 * 8 + 4] < 255);
+
+    let rgb: Vec<u8> = checkerboard.iter().flat_map(|&v| vec![v, 0, 255 - v]).collect();
+    let blurred = blur(rgb.clone(), bounds, 3, 1.5);
+    assert_eq!(blurred.len(), rgb.len());
+    assert_ne!(blurred, rgb);
+}
+
+/// downscale test: each 2x2 block of a 4x4 image becomes its average.
+#[test]
+fn test_downscale() {
+    let pixels = vec![  0,  10,  20,  20,
+                       30,  40,  20,  20,
+                      255, 255,   1,   2,
+                      255, 254,   3,   4];
+    let (small, bounds) = downscale(&pixels, PixelBounds::new(4, 4), 1, 2);
+    assert_eq!(bounds, PixelBounds::new(2, 2));
+    assert_eq!(small, vec![20, 20, 255, 3]);
+
+    // Channels are averaged apart, and a ragged edge averages what's there.
+    let rgb: Vec<u8> = (0 .. 3 * 2 * 3).map(|i| i as u8).collect();
+    let (small, bounds) = downscale(&rgb, PixelBounds::new(3, 2), 3, 2);
+    assert_eq!(bounds, PixelBounds::new(2, 1));
+    assert_eq!(small, vec![6, 7, 8, 11, 12, 13]);
+    assert_eq!(downscale(&pixels, PixelBounds::new(4, 4), 1, 1).0, pixels);
+}
+
+/// quantize test
+#[test]
+fn test_quantize() {
+    let entries = [[0, 0, 0], [255, 0, 0], [0, 0, 255]];
+    assert_eq!(quantize(&[10, 10, 10, 200, 30, 0, 20, 0, 130, 150, 0, 110], 3, &entries),
+               vec![0, 1, 2, 1]);
+
+    let grays = [[0; 3], [128; 3], [255; 3]];
+    assert_eq!(quantize(&[0, 60, 70, 200, 250], 1, &grays), vec![0, 0, 1, 2, 2]);
+}
+
+/// tile test
+#[test]
+fn test_tile() {
+    let bounds = PixelBounds::new(3, 2);
+    let source: Vec<u8> = (0 .. 18).collect();
+    let (tiled, tiled_bounds) = tile(source.clone(), bounds, 3, 2, 2);
+    assert_eq!(tiled_bounds, PixelBounds::new(6, 4));
+    assert_eq!(tiled.len(), tiled_bounds.pixel_count() * 3);
+
+    // Every quadrant is the source image.
+    for &(left, top) in &[(0, 0), (3, 0), (0, 2), (3, 2)] {
+        for row in 0 .. 2 {
+            let start = ((top + row) * 6 + left) * 3;
+            assert_eq!(&tiled[start .. start + 9], &source[row * 9 .. (row + 1) * 9],
+                       "quadrant at {:?}, row {}", (left, top), row);
+        }
+    }
+
+    // A 1x1 grid is the image itself, and a 1x3 one repeats each row.
+    assert_eq!(tile(vec![1, 2], PixelBounds::new(2, 1), 1, 1, 1), (vec![1, 2], PixelBounds::new(2, 1)));
+    assert_eq!(tile(vec![1, 2], PixelBounds::new(2, 1), 1, 1, 3).0, vec![1, 2, 1, 2, 1, 2]);
+}
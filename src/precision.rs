@@ -0,0 +1,203 @@
+//! Iteration in double-double arithmetic, for views too deep for f64.
+//!
+//! A `DoubleDouble` holds a number as the unevaluated sum of two f64s,
+//! the second below half an ulp of the first, which gives about 106 bits
+//! of mantissa where f64 has 53. That is enough to tell apart the pixels
+//! of views some 10^15 times deeper than `view::exceeds_f64_resolution`
+//! allows, at several times the cost of iterating in f64.
+//!
+//! The corners of the view are still f64s, as parsed; only the points
+//! between them, and the orbits of those points, are worked out in the
+//! wider type.
+
+use num::Complex;
+use std::ops::{Add, Mul, Sub};
+
+use crate::escape::smooth_count;
+use crate::view::{ComplexView, PixelBounds};
+
+/// A number as the sum 'hi' + 'lo' of two f64s, with |'lo'| no more than
+/// half an ulp of 'hi'.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+impl DoubleDouble {
+    /// 'value' exactly.
+    pub fn from_f64(value: f64) -> DoubleDouble {
+        DoubleDouble { hi: value, lo: 0.0 }
+    }
+
+    /// The f64 nearest the number.
+    pub fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    /// The number divided by 'divisor', to within an ulp of the result.
+    pub fn div_f64(self, divisor: f64) -> DoubleDouble {
+        let first = self.hi / divisor;
+        let remainder = self - DoubleDouble::from_f64(divisor) * DoubleDouble::from_f64(first);
+        quick_two_sum(first, remainder.hi / divisor)
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: DoubleDouble) -> DoubleDouble {
+        let (sum, error) = two_sum(self.hi, other.hi);
+        quick_two_sum(sum, error + self.lo + other.lo)
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn sub(self, other: DoubleDouble) -> DoubleDouble {
+        self + DoubleDouble { hi: -other.hi, lo: -other.lo }
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        let product = self.hi * other.hi;
+        let error = self.hi.mul_add(other.hi, -product);
+        quick_two_sum(product, error + self.hi * other.lo + self.lo * other.hi)
+    }
+}
+
+/// 'a' + 'b' as a rounded sum and the exact error of the rounding.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_part = sum - a;
+    (sum, (a - (sum - b_part)) + (b - b_part))
+}
+
+/// `two_sum`, for 'a' known to be no smaller in magnitude than 'b'.
+fn quick_two_sum(a: f64, b: f64) -> DoubleDouble {
+    let sum = a + b;
+    DoubleDouble { hi: sum, lo: b - (sum - a) }
+}
+
+/// The point of the plane, as (re, im), at the top left corner of pixel
+/// 'pixel', a (column, row) pair, of an image of size 'bounds' covering
+/// 'view': `view::pixel_to_point` without rounding each point to f64.
+pub fn pixel_to_point(bounds: PixelBounds, pixel: (usize, usize), view: ComplexView)
+    -> (DoubleDouble, DoubleDouble)
+{
+    let (upper_left, lower_right) = (view.upper_left, view.lower_right);
+    let along = |from: f64, to: f64, step: usize, steps: usize| {
+        let from = DoubleDouble::from_f64(from);
+        let span = DoubleDouble::from_f64(to) - from;
+        from + (span * DoubleDouble::from_f64(step as f64)).div_f64(steps as f64)
+    };
+    (along(upper_left.re, lower_right.re, pixel.0, bounds.width),
+     along(upper_left.im, lower_right.im, pixel.1, bounds.height))
+}
+
+/// `escape::escape_time` for 'c', a point as (re, im), iterated in
+/// double-double arithmetic: Some(i) if its orbit leaves the circle of
+/// radius two at iteration 'i', or None if it is still inside after
+/// 'limit' iterations.
+pub fn escape_time(c: (DoubleDouble, DoubleDouble), limit: u32) -> Option<u32> {
+    escape(c, limit).map(|(count, _)| count)
+}
+
+/// `escape::smooth_escape_time` for 'c', a point as (re, im), iterated in
+/// double-double arithmetic until it escapes. Once out of the circle of
+/// radius two the orbit no longer depends on fine detail, so the 'extra'
+/// steps past escape are taken in f64.
+pub fn smooth_escape_time(c: (DoubleDouble, DoubleDouble), limit: u32, extra: u32) -> Option<f64> {
+    let (count, mut z) = escape(c, limit)?;
+    let c = Complex { re: c.0.to_f64(), im: c.1.to_f64() };
+    for _ in 0..extra {
+        z = z*z + c;
+    }
+    let smooth = smooth_count(count + extra, z, 2.0);
+    Some(if smooth.is_finite() { smooth } else { count as f64 })
+}
+
+/// The iteration at which the orbit of 'c' escapes, and where it has got
+/// to then, or None if it doesn't within 'limit' iterations.
+fn escape(c: (DoubleDouble, DoubleDouble), limit: u32) -> Option<(u32, Complex<f64>)> {
+    let (c_re, c_im) = c;
+    let (mut re, mut im) = (DoubleDouble::from_f64(0.0), DoubleDouble::from_f64(0.0));
+    for i in 0..limit {
+        let (re_sqr, im_sqr) = (re * re, im * im);
+        let re_im = re * im;
+        re = re_sqr - im_sqr + c_re;
+        im = re_im + re_im + c_im;
+        if (re * re + im * im).hi > 4.0 {
+            return Some((i, Complex { re: re.to_f64(), im: im.to_f64() }));
+        }
+    }
+
+    None
+}
+
+/// DoubleDouble test: sums and products keep what f64 would round away.
+#[test]
+fn test_double_double() {
+    let one = DoubleDouble::from_f64(1.0);
+    let tiny = DoubleDouble::from_f64(1e-20);
+    assert_eq!(((one + tiny) - one).to_f64(), 1e-20);
+    assert_eq!((1.0 + 1e-20) - 1.0, 0.0);
+
+    // (1 + 2^-40)^2 = 1 + 2^-39 + 2^-80, whose last term f64 drops.
+    let x = one + DoubleDouble::from_f64(2f64.powi(-40));
+    let square = x * x;
+    assert_eq!(square.hi, 1.0 + 2f64.powi(-39));
+    assert_eq!(square.lo, 2f64.powi(-80));
+
+    let third = one.div_f64(3.0);
+    assert!(((third * DoubleDouble::from_f64(3.0)) - one).to_f64().abs() < 1e-31);
+}
+
+/// precision escape_time test: the same counts as f64 where f64 is
+/// enough, and distinct points where it isn't.
+#[test]
+fn test_precision_escape_time() {
+    use crate::escape;
+
+    // Pixels an eighth apart, so f64 holds every point exactly.
+    let bounds = PixelBounds::new(32, 32);
+    let view = ComplexView::new(Complex { re: -2.0, im: 2.0 }, Complex { re: 2.0, im: -2.0 });
+    for row in 0..bounds.height {
+        for column in 0..bounds.width {
+            let point = pixel_to_point(bounds, (column, row), view);
+            let c = Complex { re: point.0.to_f64(), im: point.1.to_f64() };
+            assert_eq!(c, crate::view::pixel_to_point(bounds, (column, row), view));
+            assert_eq!(escape_time(point, 255), escape::escape_time(c, 255));
+            let (smooth, expected) = (smooth_escape_time(point, 255, 4),
+                                      escape::smooth_escape_time(c, 255, 4));
+            assert_eq!(smooth.is_some(), expected.is_some());
+            if let (Some(smooth), Some(expected)) = (smooth, expected) {
+                assert!((smooth - expected).abs() < 1e-9, "{} vs {}", smooth, expected);
+            }
+        }
+    }
+
+    // 1e-14 wide about a point of the boundary: f64 can only space the
+    // pixels by whole ulps, unevenly, while double-double spaces them
+    // evenly.
+    let (re, im) = (-0.743_643_887_037_151, 0.131_825_904_205_33);
+    let deep = ComplexView::new(Complex { re, im: im + 0.375e-14 },
+                                Complex { re: re + 1e-14, im: im - 0.375e-14 });
+    let bounds = PixelBounds::new(64, 48);
+    assert!(crate::view::exceeds_f64_resolution(bounds, deep));
+    let step = (re + 1e-14 - re) / 64.0;
+    let f64_points: Vec<f64> = (0..bounds.width)
+        .map(|column| crate::view::pixel_to_point(bounds, (column, 0), deep).re)
+        .collect();
+    assert!(f64_points.windows(2).any(|pair| (pair[1] - pair[0] - step).abs() > step / 10.0));
+    let points: Vec<DoubleDouble> = (0..bounds.width)
+        .map(|column| pixel_to_point(bounds, (column, 0), deep).0)
+        .collect();
+    for pair in points.windows(2) {
+        assert!(((pair[1] - pair[0]).to_f64() - step).abs() < step * 1e-12);
+    }
+}
@@ -0,0 +1,110 @@
+//! Sampling where a render spends its time, across all of its threads,
+//! with `pprof`, for `--profile`. Only built with the `profile` feature,
+//! and only useful on Unix-like systems, where pprof can sample.
+
+use pprof::{ProfilerGuard, ProfilerGuardBuilder, Report};
+use std::io;
+use std::path::Path;
+
+use crate::output::{is_svg, write_atomically};
+
+/// How many times a second a `Profile` samples the running threads. It
+/// isn't a round number, so the samples don't fall in step with work
+/// that repeats on a timer.
+pub const SAMPLE_FREQUENCY: i32 = 997;
+
+/// Libraries whose frames are left out of the samples: unwinding through
+/// them can crash, and they say nothing about the render.
+const BLOCKLIST: &[&str] = &["libc", "libgcc", "pthread", "vdso"];
+
+/// A running profile of the whole process, from `Profile::start` until
+/// it is saved.
+pub struct Profile {
+    guard: ProfilerGuard<'static>,
+}
+
+impl Profile {
+    /// Start sampling every thread of the process `SAMPLE_FREQUENCY`
+    /// times a second. Only one profile can run at a time.
+    pub fn start() -> io::Result<Profile> {
+        let guard = ProfilerGuardBuilder::default()
+            .frequency(SAMPLE_FREQUENCY)
+            .blocklist(BLOCKLIST)
+            .build()
+            .map_err(to_io)?;
+        Ok(Profile { guard })
+    }
+
+    /// Stop sampling and write the samples to 'path': as a flame graph
+    /// SVG if `is_svg` says so, and otherwise as folded stacks, a line
+    /// `thread;outer;...;inner count` for each call stack sampled, which
+    /// `inferno`, `flamegraph.pl` and speedscope all read. Like an image,
+    /// the file is replaced atomically.
+    pub fn save<P: AsRef<Path>>(self, path: P) -> io::Result<()> {
+        let report = self.guard.report().build().map_err(to_io)?;
+        let encoded = if is_svg(path.as_ref()) {
+            let mut svg = Vec::new();
+            report.flamegraph(&mut svg).map_err(to_io)?;
+            svg
+        } else {
+            folded(&report).into_bytes()
+        };
+        write_atomically(path, &encoded)
+    }
+}
+
+/// The folded stacks of 'report', as `Profile::save` describes, sorted
+/// so the same samples always give the same file.
+fn folded(report: &Report) -> String {
+    let mut lines: Vec<String> = report.data.iter()
+        .map(|(frames, count)| {
+            let mut stack = vec![frames.thread_name_or_id()];
+            // Frames run from the innermost call out, as do the symbols
+            // inlined into each.
+            for frame in frames.frames.iter().rev() {
+                stack.extend(frame.iter().rev().map(|symbol| symbol.to_string()));
+            }
+            format!("{} {}\n", stack.join(";"), count)
+        })
+        .collect();
+    lines.sort();
+    lines.concat()
+}
+
+fn to_io(error: pprof::Error) -> io::Error {
+    io::Error::other(error.to_string())
+}
+
+/// Profile test: profiling a render writes a non-empty flame graph and
+/// folded stacks that name the render.
+#[test]
+fn test_profile() {
+    use num::Complex;
+    use std::{env, fs, process};
+    use crate::render::{Coloring, Shading, Supersampling, Threading, render_image};
+    use crate::view::{ComplexView, PixelBounds};
+
+    let render = || {
+        let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+        render_image(PixelBounds::new(400, 300), view, Shading::EscapeTime, 2000, Coloring::Gray,
+                     Supersampling::none(), None, Threading::new(2)).unwrap()
+    };
+    let svg = env::temp_dir().join(format!("mandelbrot-profile-{}.svg", process::id()));
+    let stacks = env::temp_dir().join(format!("mandelbrot-profile-{}.folded", process::id()));
+
+    let profile = Profile::start().unwrap();
+    render();
+    profile.save(&svg).unwrap();
+    let profile = Profile::start().unwrap();
+    render();
+    profile.save(&stacks).unwrap();
+
+    let written = fs::read_to_string(&svg).unwrap();
+    assert!(written.contains("<svg"), "{}", &written[.. written.len().min(200)]);
+    let written = fs::read_to_string(&stacks).unwrap();
+    assert!(!written.is_empty());
+    assert!(written.lines().all(|line| line.rsplit(' ').next().unwrap().parse::<u64>().is_ok()));
+    assert!(written.contains("render"), "{}", written);
+    fs::remove_file(&svg).unwrap();
+    fs::remove_file(&stacks).unwrap();
+}
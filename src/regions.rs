@@ -0,0 +1,90 @@
+//! A catalog of famous regions of the Mandlebrot set, so they can be
+//! asked for by name instead of by their corners.
+//!
+//! Every region is 4:3, the shape of the README's example images, and
+//! centered on the feature it is named for.
+
+use num::Complex;
+
+use crate::view::ComplexView;
+
+/// Names accepted by `named`, with the center and the width on the real
+/// axis of each region.
+const CATALOG: &[(&str, (f64, f64), f64)] = &[
+    ("whole-set", (-0.75, 0.0), 3.0),
+    ("seahorse-valley", (-0.745, 0.113), 0.04),
+    ("elephant-valley", (0.28, 0.008), 0.04),
+    ("triple-spiral", (-0.088, 0.654), 0.02),
+    ("scepter-valley", (-1.36, 0.005), 0.04),
+    ("mini-mandelbrot", (-1.7549, 0.0), 0.04),
+    ("double-spiral", (-0.7435, 0.1314), 0.004),
+];
+
+/// Names of the regions in the catalog, in the order they are listed.
+pub fn names() -> Vec<&'static str> {
+    CATALOG.iter().map(|&(name, _, _)| name).collect()
+}
+
+/// Look up a region of the catalog by name.
+pub fn named(name: &str) -> Option<ComplexView> {
+    CATALOG.iter()
+        .find(|&&(candidate, _, _)| candidate == name)
+        .map(|&(_, center, width)| view_about(center, width))
+}
+
+/// The center and the width on the real axis of the view
+/// `--benchmark-view` renders: deep in Seahorse Valley, on a spiral
+/// whose boundary stays intricate however far in, so most pixels run
+/// long before they escape or reach the limit.
+pub const BENCHMARK_CENTER: (f64, f64) = (-0.743_643_887_037_151, 0.131_825_904_205_33);
+pub const BENCHMARK_WIDTH: f64 = 1e-5;
+
+/// The iteration limit `--benchmark-view` renders at.
+pub const BENCHMARK_LIMIT: u32 = 5000;
+
+/// The fixed view `--benchmark-view` renders, 4:3 like the catalog's, so
+/// renders timed anywhere do the same work.
+pub fn benchmark() -> ComplexView {
+    view_about(BENCHMARK_CENTER, BENCHMARK_WIDTH)
+}
+
+/// The 4:3 view centered on 'center' and 'width' wide.
+fn view_about((re, im): (f64, f64), width: f64) -> ComplexView {
+    let center = Complex { re, im };
+    let half = Complex { re: width / 2.0, im: width * 3.0 / 8.0 };
+    ComplexView::new(center + Complex { re: -half.re, im: half.im },
+                     center + Complex { re: half.re, im: -half.im })
+}
+
+/// named region test
+#[test]
+fn test_named() {
+    assert_eq!(named("whole-set"),
+               Some(ComplexView::new(Complex { re: -2.25, im: 1.125 },
+                                     Complex { re: 0.75, im: -1.125 })));
+    let seahorse = named("seahorse-valley").unwrap();
+    assert!((seahorse.upper_left.re - -0.765).abs() < 1e-12);
+    assert!((seahorse.upper_left.im - 0.128).abs() < 1e-12);
+    assert!((seahorse.lower_right.re - -0.725).abs() < 1e-12);
+    assert!((seahorse.lower_right.im - 0.098).abs() < 1e-12);
+
+    for name in names() {
+        let view = named(name).unwrap();
+        let (width, height) = (view.lower_right.re - view.upper_left.re,
+                               view.upper_left.im - view.lower_right.im);
+        assert!((width / height - 4.0 / 3.0).abs() < 1e-9, "{}", name);
+    }
+    assert_eq!(named("nonexistent"), None);
+}
+
+/// benchmark test: the documented fixed view.
+#[test]
+fn test_benchmark() {
+    let view = benchmark();
+    let center = (view.upper_left + view.lower_right) / 2.0;
+    assert!((center.re - -0.743_643_887_037_151).abs() < 1e-15);
+    assert!((center.im - 0.131_825_904_205_33).abs() < 1e-15);
+    assert!((view.lower_right.re - view.upper_left.re - 1e-5).abs() < 1e-15);
+    assert!((view.upper_left.im - view.lower_right.im - 0.75e-5).abs() < 1e-15);
+    assert_eq!(BENCHMARK_LIMIT, 5000);
+}
@@ -0,0 +1,2309 @@
+use image::{Rgb, RgbImage};
+use num::Complex;
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "threads")]
+use std::sync::{Condvar, Mutex};
+#[cfg(feature = "threads")]
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::RenderError;
+use crate::field::{Escape, escape_range, render_field, render_field_mariani_silver};
+use crate::fractal::{IterMap, escape_time_generic};
+use crate::mask::Mask;
+use crate::escape::{MAX_PERIOD, attractor_period, escape_derivative, escape_time, lyapunov, potential,
+                    smooth_escape_time};
+use crate::palette::{PaletteLut, palette_color};
+#[cfg(feature = "high-precision")]
+use crate::precision;
+use crate::rng;
+use crate::view::{ComplexView, PixelBounds, Projection, column_step, pixel_to_point, row_points,
+                  subpixel_to_point};
+
+/// Iteration limit used unless another is asked for.
+pub const LIMIT: u32 = 255;
+
+/// Seed for the supersampling jitter unless `--supersample-seed` says
+/// otherwise.
+pub const DEFAULT_SUPERSAMPLE_SEED: u64 = 0x6d61_6e64_6c65;
+
+/// How escape times are measured before being turned into colors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shading {
+    /// Shade by the integer escape count.
+    EscapeTime,
+    /// Shade by `smooth_escape_time` with the given number of extra steps.
+    Smooth { extra: u32 },
+    /// Shade every point, inside the set or out, by its `lyapunov`
+    /// exponent: stable orbits are dark, divergent ones bright.
+    Lyapunov,
+    /// Light the boundary of the set as a surface, from a light at
+    /// 'azimuth' radians counterclockwise from the positive real axis and
+    /// 'elevation' radians above the plane, using the normal the distance
+    /// estimator gives (see `escape_derivative`). Brighter is lit more.
+    Lit { azimuth: f64, elevation: f64 },
+    /// Shade by the exterior `potential`, dark where it vanishes at the
+    /// boundary and brightening outward. Unlike a smooth count it doesn't
+    /// depend on 'limit' beyond deciding membership.
+    Potential,
+}
+
+/// The potential `Shading::Potential` shows as full brightness: ln 2,
+/// about that of the circle of radius two around the set.
+const POTENTIAL_WHITE: f64 = std::f64::consts::LN_2;
+
+/// The power `Shading::Potential` raises the potential to, as a fraction
+/// of `POTENTIAL_WHITE`. The potential halves with every further step an
+/// orbit takes to escape, so a low power keeps the filaments near the
+/// boundary from all going black.
+const POTENTIAL_GAMMA: f64 = 0.25;
+
+/// How far the lighting normal stands up out of the plane, relative to
+/// its sideways part of length one. Lower values give a deeper relief.
+const LIGHT_NORMAL_HEIGHT: f64 = 1.5;
+
+impl Shading {
+    /// The escape count of 'point' as measured by this shading with at
+    /// most 'limit' iterations, or None if the point seems to be a member
+    /// of the set. `Lyapunov` shading never returns None; it maps its
+    /// exponent onto the same `0..limit` scale instead, with an exponent
+    /// of zero halfway along it. Neither does `Lit`, which puts unlit
+    /// points at 'limit' and fully lit ones at 0; members of the set have
+    /// no normal and are lit as flat ground. `Potential` puts the
+    /// boundary at 'limit' and `POTENTIAL_WHITE` and beyond at 0.
+    fn count(self, point: Complex<f64>, limit: u32) -> Option<f64> {
+        match self {
+            Shading::EscapeTime => escape_time(point, limit).map(|count| count as f64),
+            Shading::Smooth { extra } => smooth_escape_time(point, limit, extra),
+            Shading::Lyapunov => {
+                let exponent = lyapunov(point, limit);
+                Some(limit as f64 / (1.0 + exponent.exp()))
+            }
+            Shading::Lit { azimuth, elevation } => {
+                let light = [elevation.cos() * azimuth.cos(), elevation.cos() * azimuth.sin(),
+                             elevation.sin()];
+                let normal = match escape_derivative(point, limit) {
+                    Some((z, derivative)) => {
+                        let away = z / derivative;
+                        let away = away / away.norm();
+                        let length = (1.0 + LIGHT_NORMAL_HEIGHT * LIGHT_NORMAL_HEIGHT).sqrt();
+                        [away.re / length, away.im / length, LIGHT_NORMAL_HEIGHT / length]
+                    }
+                    None => [0.0, 0.0, 1.0],
+                };
+                let lambert = normal.iter().zip(&light).map(|(n, l)| n * l).sum::<f64>().max(0.0);
+                Some(limit as f64 * (1.0 - lambert))
+            }
+            Shading::Potential => potential(point, limit).map(|potential| {
+                limit as f64 * (1.0 - (potential / POTENTIAL_WHITE).min(1.0).powf(POTENTIAL_GAMMA))
+            }),
+        }
+    }
+}
+
+/// A part of the image: the members of the set or everything else.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    Interior,
+    Exterior,
+}
+
+/// How pixels are colored, which also decides how many bytes each takes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Coloring<'a> {
+    /// One gray byte per pixel, as produced by `render`.
+    Gray,
+    /// Three RGB bytes per pixel, as produced by `render_rgb`.
+    Palette { colors: &'a [[u8; 3]], repeat: u32 },
+    /// Three RGB bytes per pixel, like `Palette` but looked up from a
+    /// table made ahead of the render.
+    Lut(&'a PaletteLut),
+    /// Four RGBA bytes per pixel: the color 'base' gives (with gray
+    /// spread over red, green and blue), made fully transparent on the
+    /// pixels in 'clear' and opaque elsewhere.
+    Alpha { base: &'a Coloring<'a>, clear: Region },
+    /// The color 'base' gives a count out of 'limit', whatever the limit
+    /// the image is iterated to, so the same count gets the same color in
+    /// renders iterated further. Counts past 'limit' go on round a palette
+    /// and stay black in gray.
+    Anchored { base: &'a Coloring<'a>, limit: u32 },
+    /// Three RGB bytes per pixel: outside the set the color 'base' gives
+    /// (with gray spread over red, green and blue), and inside it the
+    /// color of 'colors' for the period of the cycle the point's orbit is
+    /// attracted to, `attractor_period`, the first for period 1, and
+    /// round again past the end. Each bulb then stands out in its own
+    /// color. Points whose period isn't found get the color 'base' gives
+    /// the interior.
+    InteriorPeriod { base: &'a Coloring<'a>, colors: &'a [[u8; 3]] },
+}
+
+impl<'a> Coloring<'a> {
+    /// Number of bytes each pixel takes.
+    pub fn channels(&self) -> usize {
+        match self {
+            Coloring::Gray => 1,
+            Coloring::Palette { .. } | Coloring::Lut(_) | Coloring::InteriorPeriod { .. } => 3,
+            Coloring::Alpha { .. } => 4,
+            Coloring::Anchored { base, .. } => base.channels(),
+        }
+    }
+
+    /// Whether this coloring needs to know which samples are members of
+    /// the set, apart from their counts.
+    fn needs_membership(&self) -> bool {
+        match self {
+            Coloring::Alpha { .. } | Coloring::InteriorPeriod { .. } => true,
+            Coloring::Anchored { base, .. } => base.needs_membership(),
+            _ => false,
+        }
+    }
+
+    /// Whether this coloring needs the period of the attracting cycle of
+    /// members of the set.
+    fn needs_period(&self) -> bool {
+        match self {
+            Coloring::InteriorPeriod { .. } => true,
+            Coloring::Alpha { base, .. } | Coloring::Anchored { base, .. } => base.needs_period(),
+            _ => false,
+        }
+    }
+
+    /// The color this coloring gives a point outside the set whose count
+    /// is 'count' out of 'limit', whatever shading made it, as for a key
+    /// to the colors. Only the first `channels()` bytes are meaningful.
+    pub fn count_color(&self, count: f64, limit: u32) -> [u8; 4] {
+        self.paint(Some(count), false, None, limit)
+    }
+
+    /// The color of a single sample at 'point', iterated at most 'limit'
+    /// times. Only the first `channels()` bytes are meaningful.
+    fn color(&self, point: Complex<f64>, shading: Shading, limit: u32) -> [u8; 4] {
+        let count = shading.count(point, limit);
+        if shading == Shading::EscapeTime {
+            add_iterations(count.map_or(limit as u64, |count| count as u64 + 1));
+        }
+        let interior = match shading {
+            // `Lyapunov` and `Lit` counts say nothing about membership,
+            // so only look it up when the alpha needs it.
+            Shading::Lyapunov | Shading::Lit { .. } if self.needs_membership() =>
+                escape_time(point, limit).is_none(),
+            _ => count.is_none(),
+        };
+        let period = if interior && self.needs_period() {
+            attractor_period(point, limit, MAX_PERIOD)
+        } else {
+            None
+        };
+        self.paint(count, interior, period, limit)
+    }
+
+    /// The color of a sample whose count `Shading::count` gave as
+    /// 'count' out of 'limit', and which is a member of the set if
+    /// 'interior', attracted to a cycle of 'period' if that is known.
+    fn paint(&self, count: Option<f64>, interior: bool, period: Option<u32>, limit: u32) -> [u8; 4] {
+        match (*self, count) {
+            (Coloring::Alpha { base, clear }, _) => {
+                let [red, green, blue, _] = base.paint(count, interior, period, limit);
+                let region = if interior { Region::Interior } else { Region::Exterior };
+                [red, green, blue, if region == clear { 0 } else { 255 }]
+            }
+            (Coloring::Anchored { base, limit }, _) => base.paint(count, interior, period, limit),
+            (Coloring::InteriorPeriod { base, colors }, _) => match period {
+                Some(period) if interior => {
+                    let [red, green, blue] = colors[(period as usize - 1) % colors.len()];
+                    [red, green, blue, 255]
+                }
+                _ => base.paint(count, interior, period, limit),
+            },
+            (_, None) => [0, 0, 0, 255],
+            (Coloring::Gray, Some(count)) => {
+                let gray = (255.0 - count * 255.0 / limit as f64).round().clamp(0.0, 255.0) as u8;
+                [gray, gray, gray, 255]
+            }
+            (Coloring::Palette { colors, repeat }, Some(count)) => {
+                let [red, green, blue] = palette_color(colors, count, limit, repeat);
+                [red, green, blue, 255]
+            }
+            (Coloring::Lut(lut), Some(count)) => {
+                let [red, green, blue] = lut.color(count);
+                [red, green, blue, 255]
+            }
+        }
+    }
+
+    /// Color one sample per point of 'points' into 'row_pixels'.
+    // Kept out of line: inlined into `render_rows` alongside the
+    // supersampling loop, the escape loop compiled noticeably slower.
+    #[inline(never)]
+    fn color_row(&self, points: &[Complex<f64>], shading: Shading, limit: u32,
+                 row_pixels: &mut [u8]) {
+        let channels = self.channels();
+        for (pixel, &point) in row_pixels.chunks_mut(channels).zip(points) {
+            pixel.copy_from_slice(&self.color(point, shading, limit)[..channels]);
+        }
+    }
+
+    /// Like `color_row`, but only for the pixels 'covered' says to; the
+    /// rest are set to zero.
+    #[inline(never)]
+    fn color_row_masked(&self, points: &[Complex<f64>], shading: Shading, limit: u32,
+                        row_pixels: &mut [u8], covered: &[bool]) {
+        let channels = self.channels();
+        for ((pixel, &point), &covered) in row_pixels.chunks_mut(channels).zip(points).zip(covered) {
+            if covered {
+                pixel.copy_from_slice(&self.color(point, shading, limit)[..channels]);
+            } else {
+                pixel.iter_mut().for_each(|byte| *byte = 0);
+            }
+        }
+    }
+}
+
+/// Stratified supersampling (antialiasing) settings.
+///
+/// With a grid of 'grid_x' by 'grid_y', each pixel is split into that
+/// many columns and rows of cells and one sample is taken at a jittered
+/// position inside every cell; the pixel gets the average color. The two
+/// needn't be equal, for output that will be stretched one way, and
+/// either being 0 counts as 1. The jitter is a hash of 'seed' and the
+/// pixel's position in the whole image, never of anything that changes
+/// from run to run, so the same seed gives byte-identical output however
+/// the image is split into bands or tiles.
+///
+/// With 'agree' of K, a pixel whose first K samples all come out the same
+/// color is taken to be uniform and gets that color without the rest
+/// being taken. Flat areas in and far from the set then cost K samples a
+/// pixel, not the whole grid, and come out just as they would fully
+/// sampled; pixels on an edge disagree early and are sampled in full. A
+/// small feature missed by all of the first K samples is missed in the
+/// pixel too. Below 2 it never stops early.
+///
+/// With a 'roi' of `(left, top, width, height)`, only the pixels in that
+/// rectangle of the image are supersampled; the rest take one sample, as
+/// `none` does, so a large render can be sharp where it matters without
+/// paying for the grid everywhere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Supersampling {
+    pub grid_x: u32,
+    pub grid_y: u32,
+    pub seed: u64,
+    pub agree: u32,
+    pub roi: Option<(usize, usize, usize, usize)>,
+}
+
+impl Supersampling {
+    /// One sample per pixel, at the pixel's corner, as `pixel_to_point`
+    /// gives it.
+    pub fn none() -> Supersampling {
+        Supersampling { grid_x: 1, grid_y: 1, seed: DEFAULT_SUPERSAMPLE_SEED, agree: 0, roi: None }
+    }
+
+    /// Supersampling on a square grid of 'grid' by 'grid' cells.
+    pub fn square(grid: u32, seed: u64) -> Supersampling {
+        Supersampling { grid_x: grid, grid_y: grid, seed, agree: 0, roi: None }
+    }
+
+    /// How many samples each pixel takes, or those in 'roi' if there is one.
+    pub fn samples(&self) -> u64 {
+        self.grid_x.max(1) as u64 * self.grid_y.max(1) as u64
+    }
+
+    /// The color of the pixel at `(column, row)` of an image of size
+    /// 'bounds' covering 'view', iterated at most 'limit' times.
+    fn pixel_color(&self, bounds: PixelBounds, view: ComplexView, (column, row): (usize, usize),
+                   shading: Shading, limit: u32, coloring: Coloring) -> [u8; 4] {
+        let samples = self.samples();
+        let inside = self.roi.is_none_or(|(left, top, width, height)| {
+            (left .. left + width).contains(&column) && (top .. top + height).contains(&row)
+        });
+        if samples == 1 || !inside {
+            return coloring.color(pixel_to_point(bounds, (column, row), view), shading, limit);
+        }
+
+        // Cells are numbered across each row of the grid, then down, but
+        // visited in steps of `cell_stride` so that the first few, which
+        // decide whether to stop early, are spread over the pixel. The sum
+        // is the same in any order.
+        let (grid_x, grid_y) = (self.grid_x.max(1) as u64, self.grid_y.max(1) as u64);
+        let stride = cell_stride(samples);
+        let mut sums = [0u64; 4];
+        let mut first = None;
+        let mut uniform = true;
+        for visit in 0 .. samples {
+            let cell = visit * stride % samples;
+            let jitter = rng::hash(self.seed, &[column as u64, row as u64, cell]);
+            let x = (cell % grid_x) as f64 + rng::unit_float(jitter);
+            let y = (cell / grid_x) as f64 + rng::unit_float(rng::mix(jitter));
+            let subpixel = (column as f64 + x / grid_x as f64, row as f64 + y / grid_y as f64);
+
+            let color = coloring.color(subpixel_to_point(bounds, subpixel, view), shading, limit);
+            uniform &= *first.get_or_insert(color) == color;
+            if uniform && self.agree >= 2 && visit + 1 == self.agree as u64 {
+                return color;
+            }
+            for channel in 0..4 {
+                sums[channel] += color[channel] as u64;
+            }
+        }
+
+        let mut color = [0; 4];
+        for channel in 0..4 {
+            color[channel] = ((sums[channel] + samples / 2) / samples) as u8;
+        }
+        color
+    }
+}
+
+/// A step through the 'samples' cells of a supersampling grid that visits
+/// each once: the first number past 'samples' over the golden ratio that
+/// shares no factor with it, so successive cells land far apart.
+fn cell_stride(samples: u64) -> u64 {
+    let gcd = |mut a: u64, mut b: u64| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    let mut stride = ((samples as f64 / 1.618_034) as u64).max(1);
+    while gcd(stride, samples) != 1 {
+        stride += 1;
+    }
+    stride
+}
+
+/// Render some rows of an image of the Mandelbrot set.
+///
+/// The whole image has the size 'bounds' and covers 'view'; 'pixels'
+/// holds just the rows starting at row 'top', with `coloring.channels()`
+/// bytes per pixel. Every pixel is computed from its position in the
+/// whole image, so rendering the image in pieces gives exactly the same
+/// pixels as rendering it at once.
+///
+/// Each sample is iterated at most 'limit' times, and its count is
+/// colored on a scale running up to 'limit'. If there is a 'mask', only
+/// the pixels it covers are computed, and the rest are left as zero
+/// bytes. Returns the number of pixels computed.
+#[allow(clippy::too_many_arguments)]
+pub fn render_rows(pixels: &mut [u8],
+        bounds: PixelBounds,
+        view: ComplexView,
+        top: usize,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        mask: Option<&Mask>) -> usize
+{
+    let channels = coloring.channels();
+    let row_len = bounds.width * channels;
+    assert!(pixels.len().is_multiple_of(row_len));
+    assert!(top + pixels.len() / row_len <= bounds.height);
+    assert!(mask.is_none_or(|mask| mask.bounds() == bounds));
+
+    // Without supersampling, each pixel is a single sample at a point of
+    // its row, and those are quicker to work out all together. The points
+    // go in this thread's scratch buffer rather than a new one, since with
+    // small chunks this runs for only a row or two at a time.
+    ROW_POINTS.with(|points| {
+        let mut points = points.borrow_mut();
+        points.clear();
+        if sampling.samples() == 1 {
+            points.resize(bounds.width, Complex { re: 0.0, im: 0.0 });
+        }
+        render_rows_with(pixels, bounds, view, top, shading, limit, coloring, sampling, mask,
+                         &mut points)
+    })
+}
+
+thread_local! {
+    /// Iterations run on this thread since the last `take_iterations`.
+    static ITERATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// The iterations run for `Shading::EscapeTime` samples by renders on
+/// this thread since the last call, which starts the count again. Those
+/// of the threads a render here spreads its rows over are counted too,
+/// once the render is done, and so is every sample of a supersampled
+/// pixel. Other shadings, and the renders built on `field`, such as
+/// `render_image_mariani_silver`, aren't counted.
+pub fn take_iterations() -> u64 {
+    ITERATIONS.with(|iterations| iterations.replace(0))
+}
+
+/// Count 'iterations' more for `take_iterations`.
+fn add_iterations(iterations: u64) {
+    ITERATIONS.with(|counted| counted.set(counted.get() + iterations));
+}
+
+thread_local! {
+    /// Scratch space for the points of a row, kept for the life of each
+    /// thread so that `render_rows` allocates it once per thread instead
+    /// of once per call.
+    static ROW_POINTS: RefCell<Vec<Complex<f64>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// `render_rows`, with 'points' holding a point for every column, or
+/// empty to supersample instead.
+#[allow(clippy::too_many_arguments)]
+fn render_rows_with(pixels: &mut [u8],
+        bounds: PixelBounds,
+        view: ComplexView,
+        top: usize,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        mask: Option<&Mask>,
+        points: &mut [Complex<f64>]) -> usize
+{
+    let channels = coloring.channels();
+    let row_len = bounds.width * channels;
+    let mut computed = 0;
+    for (i, row_pixels) in pixels.chunks_mut(row_len).enumerate() {
+        let row = top + i;
+        let covered = mask.map(|mask| mask.row(row));
+        computed += covered.map_or(bounds.width,
+                                   |covered| covered.iter().filter(|&&covered| covered).count());
+
+        if points.is_empty() {
+            for column in 0 .. bounds.width {
+                let pixel = &mut row_pixels[column * channels .. (column + 1) * channels];
+                if covered.is_none_or(|covered| covered[column]) {
+                    let color = sampling.pixel_color(bounds, view, (column, row), shading, limit,
+                                                     coloring);
+                    pixel.copy_from_slice(&color[..channels]);
+                } else {
+                    pixel.iter_mut().for_each(|byte| *byte = 0);
+                }
+            }
+        } else {
+            row_points(bounds, row, view, points);
+            match covered {
+                Some(covered) =>
+                    coloring.color_row_masked(points, shading, limit, row_pixels, covered),
+                None => coloring.color_row(points, shading, limit, row_pixels),
+            }
+        }
+    }
+    computed
+}
+
+/// Render a rectangle of the Mandelbrot set into a buffer of pixels.
+///
+/// The 'bounds' argument gives the width and height of the buffer
+/// 'pixels', which holds one grayscale pixel per byte. 'view' is the
+/// area of the complex plane the buffer covers.
+pub fn render(pixels: &mut [u8],
+        bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading)
+{
+    assert!(pixels.len() == bounds.pixel_count());
+    render_blocked(pixels, bounds, view, shading, LIMIT, Coloring::Gray);
+}
+
+/// Side of the square blocks `render_blocked` works through.
+pub const BLOCK_SIZE: usize = 64;
+
+/// Render a whole image like `render_rows`, without supersampling or a
+/// mask, but a `BLOCK_SIZE`-pixel square at a time instead of a row at a
+/// time.
+///
+/// This is meant for very wide images, where a full row of points and
+/// pixels may not fit in the cache next to everything else while a
+/// block's worth does. How much that pays off depends on the machine;
+/// `cargo bench --bench blocked` compares the two on a wide image. Points
+/// are still added up along each row from column 0, as `row_points` does,
+/// carrying each row's running point from one block to the next, so the
+/// pixels are exactly those `render_rows` gives. Projected views aren't
+/// evenly spaced, so they are rendered row by row instead.
+pub fn render_blocked(pixels: &mut [u8],
+        bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring)
+{
+    let channels = coloring.channels();
+    assert!(pixels.len() == bounds.pixel_count() * channels);
+    if view.projection != Projection::Plane {
+        render_rows(pixels, bounds, view, 0, shading, limit, coloring, Supersampling::none(), None);
+        return;
+    }
+
+    let step = column_step(bounds, view);
+    let mut cursors = Vec::with_capacity(BLOCK_SIZE);
+    for top in (0 .. bounds.height).step_by(BLOCK_SIZE) {
+        let bottom = (top + BLOCK_SIZE).min(bounds.height);
+        cursors.clear();
+        cursors.extend((top .. bottom).map(|row| pixel_to_point(bounds, (0, row), view)));
+
+        for left in (0 .. bounds.width).step_by(BLOCK_SIZE) {
+            let right = (left + BLOCK_SIZE).min(bounds.width);
+            for (row, point) in (top .. bottom).zip(cursors.iter_mut()) {
+                let start = (row * bounds.width + left) * channels;
+                let block_row = &mut pixels[start .. start + (right - left) * channels];
+                for pixel in block_row.chunks_mut(channels) {
+                    pixel.copy_from_slice(&coloring.color(*point, shading, limit)[..channels]);
+                    point.re += step;
+                }
+            }
+        }
+    }
+}
+
+/// Like `render`, but color each pixel through 'palette', cycled 'repeat'
+/// times over the escape counts. 'pixels' holds three bytes (red, green,
+/// blue) per pixel. Members of the set are black.
+pub fn render_rgb(pixels: &mut [u8],
+        bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        palette: &[[u8; 3]],
+        repeat: u32)
+{
+    assert!(pixels.len() == bounds.pixel_count() * 3);
+    let coloring = Coloring::Palette { colors: palette, repeat };
+    render_rows(pixels, bounds, view, 0, shading, LIMIT, coloring, Supersampling::none(), None);
+}
+
+/// How `render_parallel` divides its work between threads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Threading {
+    /// Number of threads rendering at once.
+    pub threads: usize,
+    /// Number of rows in each unit of work. Threads take the next unit
+    /// as they finish one, so smaller units balance the load better when
+    /// some rows are much slower than others, at the cost of a little
+    /// more coordination. None means one band per thread.
+    pub chunk_rows: Option<usize>,
+}
+
+impl Threading {
+    /// One band of rows per thread.
+    pub fn new(threads: usize) -> Threading {
+        Threading { threads, chunk_rows: None }
+    }
+
+    /// The number of rows per unit of work for an image 'height' rows tall.
+    /// Bands are as even as whole rows allow: when 'height' isn't a
+    /// multiple of the thread count the last band is shorter, and when it
+    /// is, every thread gets exactly its share.
+    fn rows_per_chunk(&self, height: usize) -> usize {
+        self.chunk_rows.unwrap_or_else(|| height.div_ceil(self.threads.max(1))).max(1)
+    }
+
+    /// Check, before any work is split up, that the rows of an image of
+    /// size 'bounds' and 'channels' bytes a pixel can be: that the chunks
+    /// of rows, one for each thread, and the bytes of each chunk can be
+    /// counted without overflowing. Absurd `chunk_rows` or thread counts
+    /// fail here with `RenderError::InvalidThreading` rather than wrapping
+    /// around mid-render.
+    pub fn check(&self, bounds: PixelBounds, channels: usize) -> Result<(), RenderError> {
+        let rows = self.rows_per_chunk(bounds.height);
+        rows.checked_mul(self.threads)
+            .and_then(|_| rows.checked_mul(bounds.width))
+            .and_then(|row_pixels| row_pixels.checked_mul(channels))
+            .map(|_| ())
+            .ok_or(RenderError::InvalidThreading(*self))
+    }
+}
+
+/// The row chunk 'index' of 'rows_per_chunk' rows starts at, counting
+/// from 'top', or None if it is past what a `usize` can count.
+#[cfg(feature = "threads")]
+fn chunk_top(top: usize, rows_per_chunk: usize, index: usize) -> Option<usize> {
+    rows_per_chunk.checked_mul(index).and_then(|offset| top.checked_add(offset))
+}
+
+/// Render a whole image into 'pixels' like `render_rows`, splitting its
+/// rows into chunks that are rendered on several threads at once, as
+/// 'threading' describes.
+#[cfg(feature = "threads")]
+#[allow(clippy::too_many_arguments)]
+pub fn render_parallel(pixels: &mut [u8],
+        bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        mask: Option<&Mask>,
+        threading: Threading)
+{
+    assert!(pixels.len() == bounds.height * bounds.width * coloring.channels());
+    render_parallel_rows(pixels, bounds, view, 0, shading, limit, coloring, sampling, mask, threading,
+                         None);
+}
+
+/// `render_parallel` for just the rows of 'pixels', starting at row 'top'
+/// of the image, as `render_rows` takes them. With a 'pause', every
+/// thread waits for it before taking each chunk.
+#[cfg(feature = "threads")]
+#[allow(clippy::too_many_arguments)]
+fn render_parallel_rows(pixels: &mut [u8],
+        bounds: PixelBounds,
+        view: ComplexView,
+        top: usize,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        mask: Option<&Mask>,
+        threading: Threading,
+        pause: Option<&Pause>)
+{
+    let row_len = bounds.width * coloring.channels();
+    let rows_per_chunk = threading.rows_per_chunk(pixels.len() / row_len.max(1));
+    let chunk_len = rows_per_chunk.checked_mul(row_len).expect("chunk too large; see `Threading::check`");
+    let chunks = Mutex::new(pixels.chunks_mut(chunk_len.max(1)).enumerate());
+    let iterations = AtomicU64::new(0);
+
+    crossbeam::scope(|spawner| {
+        for _ in 0 .. threading.threads {
+            let (chunks, iterations) = (&chunks, &iterations);
+            spawner.spawn(move || {
+                loop {
+                    if let Some(pause) = pause {
+                        pause.wait();
+                    }
+                    // Hold the lock only long enough to take the next chunk.
+                    let next = chunks.lock().unwrap().next();
+                    let (i, chunk) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let chunk_top = chunk_top(top, rows_per_chunk, i)
+                        .expect("chunk past the last row; see `Threading::check`");
+                    render_rows(chunk, bounds, view, chunk_top, shading, limit, coloring, sampling,
+                                mask);
+                }
+                iterations.fetch_add(take_iterations(), Ordering::Relaxed);
+            });
+        }
+    });
+    add_iterations(iterations.into_inner());
+}
+
+/// How much of a full render `auto_tune_threads` may spend probing: all
+/// probes together cover about this fraction of the image's pixels.
+/// `estimate_render_time` probes this much too.
+const PROBE_FRACTION: f64 = 1.0 / 16.0;
+
+/// Bounds of the same shape as 'bounds' with about 'fraction' of its
+/// pixels, and at least one. Scaling both sides down alike keeps the
+/// same mix of slow and fast rows as the full image.
+fn probe_bounds(bounds: PixelBounds, fraction: f64) -> PixelBounds {
+    let scale = fraction.sqrt();
+    PixelBounds::new(((bounds.width as f64 * scale) as usize).max(1),
+                     ((bounds.height as f64 * scale) as usize).max(1))
+}
+
+/// Estimate how long rendering the image described by the arguments
+/// would take, by timing a render of the whole of 'view' at a much lower
+/// resolution and scaling it up by the number of pixels. It's a rough
+/// guide: the probe is small enough that fixed costs such as starting
+/// threads weigh more in it.
+#[allow(clippy::too_many_arguments)]
+pub fn estimate_render_time(bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        threading: Threading) -> Duration
+{
+    let probe = probe_bounds(bounds, PROBE_FRACTION);
+    let mut pixels = vec![0; probe.pixel_count() * coloring.channels()];
+    let start = Instant::now();
+    render_parallel(&mut pixels, probe, view, shading, limit, coloring, sampling, None, threading);
+    start.elapsed().mul_f64(bounds.pixel_count() as f64 / probe.pixel_count() as f64)
+}
+
+/// Fraction of a time budget `fit_time_budget` plans to fill, leaving the
+/// rest for the estimate being off and for saving the image.
+pub const TIME_BUDGET_MARGIN: f64 = 0.8;
+
+/// `fit_time_budget` won't lower the iteration limit below this to make a
+/// render fit; it lowers the resolution instead.
+pub const TIME_BUDGET_MIN_LIMIT: u32 = 64;
+
+/// The resolution, iteration limit and supersampling of a render: the
+/// knobs `fit_time_budget` trades for time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quality {
+    pub bounds: PixelBounds,
+    pub limit: u32,
+    pub sampling: Supersampling,
+}
+
+impl Quality {
+    /// How long a render at this quality should take when every sample
+    /// costs 'per_iteration' for each iteration of the limit, as
+    /// `measure_speed` reports. Points that escape early cost less, so the
+    /// estimate errs long.
+    pub fn estimate(&self, per_iteration: Duration) -> Duration {
+        per_iteration.mul_f64(self.bounds.pixel_count() as f64 * self.sampling.samples() as f64
+                              * self.limit as f64)
+    }
+
+    /// The next quality down: first with one cell fewer each way of
+    /// supersampling, then with half the iterations down to
+    /// `TIME_BUDGET_MIN_LIMIT`, then at three quarters of the width and
+    /// height. None once it's a single point-sampled pixel.
+    fn lower(&self) -> Option<Quality> {
+        let mut lower = *self;
+        if self.sampling.samples() > 1 {
+            lower.sampling.grid_x = self.sampling.grid_x.max(2) - 1;
+            lower.sampling.grid_y = self.sampling.grid_y.max(2) - 1;
+        } else if self.limit > TIME_BUDGET_MIN_LIMIT {
+            lower.limit = (self.limit / 2).max(TIME_BUDGET_MIN_LIMIT);
+        } else if self.bounds.pixel_count() > 1 {
+            lower.bounds = PixelBounds::new((self.bounds.width * 3 / 4).max(1),
+                                            (self.bounds.height * 3 / 4).max(1));
+        } else {
+            return None;
+        }
+        Some(lower)
+    }
+}
+
+/// Time a quick render of 'view' to learn how fast this machine renders
+/// it, returned as the cost of one sample for each iteration of the limit,
+/// for `Quality::estimate`. The probe covers the whole view at a much
+/// lower resolution than 'quality' asks for, without supersampling and
+/// iterating no more than `TIME_BUDGET_MIN_LIMIT` times. Points that
+/// escape cost the same whatever the limit, so scaling up from the lowest
+/// limit the budget might choose overestimates rather than under.
+pub fn measure_speed(quality: Quality,
+        view: ComplexView,
+        shading: Shading,
+        coloring: Coloring,
+        threading: Threading) -> Duration
+{
+    let probe = Quality {
+        bounds: probe_bounds(quality.bounds, PROBE_FRACTION),
+        limit: quality.limit.clamp(1, TIME_BUDGET_MIN_LIMIT),
+        sampling: Supersampling { grid_x: 1, grid_y: 1, ..quality.sampling },
+    };
+    let mut pixels = vec![0; probe.bounds.pixel_count() * coloring.channels()];
+    let start = Instant::now();
+    render_parallel(&mut pixels, probe.bounds, view, shading, probe.limit, coloring, probe.sampling,
+                    None, threading);
+    start.elapsed().div_f64(probe.estimate(Duration::from_secs(1)).as_secs_f64())
+}
+
+/// The best quality no better than 'requested' whose render is estimated,
+/// at 'per_iteration' from `measure_speed`, to take no more than
+/// `TIME_BUDGET_MARGIN` of 'budget'. Supersampling goes first, then
+/// iterations, then resolution, as `Quality` steps down; if even a single
+/// pixel is estimated to take too long, that pixel is what's returned.
+pub fn fit_time_budget(requested: Quality, per_iteration: Duration, budget: Duration) -> Quality {
+    let target = budget.mul_f64(TIME_BUDGET_MARGIN);
+    let mut quality = requested;
+    while quality.estimate(per_iteration) > target {
+        match quality.lower() {
+            Some(lower) => quality = lower,
+            None => break,
+        }
+    }
+    quality
+}
+
+/// Pick the number of threads, from 1 to 'max_threads', that renders the
+/// image described by the other arguments fastest.
+///
+/// This renders the whole of 'view' at a much lower resolution once per
+/// candidate count (the powers of two below 'max_threads', and
+/// 'max_threads' itself), timing each, so probing costs only a small
+/// fraction of the full render. Ties go to the fewer threads. Measuring
+/// beats guessing from the number of cores on machines whose cores
+/// aren't all alike, or where hyperthreads share execution units.
+#[cfg(feature = "threads")]
+pub fn auto_tune_threads(bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        max_threads: usize) -> usize
+{
+    assert!(max_threads >= 1);
+    let mut candidates: Vec<usize> = std::iter::successors(Some(1), |threads| Some(threads * 2))
+        .take_while(|&threads| threads < max_threads)
+        .collect();
+    candidates.push(max_threads);
+
+    let probe = probe_bounds(bounds, PROBE_FRACTION / candidates.len() as f64);
+    let mut pixels = vec![0; probe.pixel_count() * coloring.channels()];
+
+    candidates.into_iter()
+        .min_by_key(|&threads| {
+            let start = Instant::now();
+            render_parallel(&mut pixels, probe, view, shading, limit, coloring, sampling, None,
+                            Threading::new(threads));
+            start.elapsed()
+        })
+        .unwrap()
+}
+
+/// Without the `threads` feature there is only the calling thread, so
+/// this renders the whole image on it and ignores 'threading'.
+#[cfg(not(feature = "threads"))]
+#[allow(clippy::too_many_arguments)]
+pub fn render_parallel(pixels: &mut [u8],
+        bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        mask: Option<&Mask>,
+        _threading: Threading)
+{
+    assert!(pixels.len() == bounds.height * bounds.width * coloring.channels());
+    render_rows(pixels, bounds, view, 0, shading, limit, coloring, sampling, mask);
+}
+
+/// Check that an image of size 'bounds' covering 'view' can be rendered,
+/// allocate a buffer for it and render it there with `render_parallel`.
+/// This is the entry point for callers that want an error rather than a
+/// panic when the parameters or the memory aren't there.
+#[allow(clippy::too_many_arguments)]
+pub fn render_image(bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        mask: Option<&Mask>,
+        threading: Threading) -> Result<Vec<u8>, RenderError>
+{
+    let mut pixels = allocate_image(bounds, view, coloring, mask)?;
+    render_parallel(&mut pixels, bounds, view, shading, limit, coloring, sampling, mask, threading);
+    Ok(pixels)
+}
+
+/// Like `render_image` without supersampling or a mask, but with every
+/// point and orbit worked out in `precision::DoubleDouble`, for views
+/// `view::exceeds_f64_resolution` finds too deep for f64. Escape counts
+/// are whole, as for `Shading::EscapeTime`, unless 'smooth_extra' is
+/// given, when they are smooth as for `Shading::Smooth` with that many
+/// extra steps. Rows are shared among threads as `render_parallel`
+/// shares them.
+#[cfg(feature = "high-precision")]
+pub fn render_image_deep(bounds: PixelBounds,
+        view: ComplexView,
+        smooth_extra: Option<u32>,
+        limit: u32,
+        coloring: Coloring,
+        threading: Threading) -> Result<Vec<u8>, RenderError>
+{
+    let mut pixels = allocate_image(bounds, view, coloring, None)?;
+
+    #[cfg(feature = "threads")]
+    {
+        let row_len = bounds.width * coloring.channels();
+        let rows_per_chunk = threading.rows_per_chunk(bounds.height);
+        let chunk_len = rows_per_chunk.checked_mul(row_len)
+            .expect("chunk too large; see `Threading::check`");
+        let chunks = Mutex::new(pixels.chunks_mut(chunk_len.max(1)).enumerate());
+        let iterations = AtomicU64::new(0);
+        crossbeam::scope(|spawner| {
+            for _ in 0 .. threading.threads {
+                let (chunks, iterations) = (&chunks, &iterations);
+                spawner.spawn(move || {
+                    loop {
+                        let next = chunks.lock().unwrap().next();
+                        let (i, chunk) = match next {
+                            Some(next) => next,
+                            None => break,
+                        };
+                        let top = chunk_top(0, rows_per_chunk, i)
+                            .expect("chunk past the last row; see `Threading::check`");
+                        render_rows_deep(chunk, bounds, view, top, smooth_extra, limit, coloring);
+                    }
+                    iterations.fetch_add(take_iterations(), Ordering::Relaxed);
+                });
+            }
+        });
+        add_iterations(iterations.into_inner());
+    }
+    #[cfg(not(feature = "threads"))]
+    {
+        let _ = threading;
+        render_rows_deep(&mut pixels, bounds, view, 0, smooth_extra, limit, coloring);
+    }
+
+    Ok(pixels)
+}
+
+/// `render_image_deep` for just the rows of 'pixels', starting at row
+/// 'top' of the image.
+#[cfg(feature = "high-precision")]
+fn render_rows_deep(pixels: &mut [u8],
+        bounds: PixelBounds,
+        view: ComplexView,
+        top: usize,
+        smooth_extra: Option<u32>,
+        limit: u32,
+        coloring: Coloring)
+{
+    let channels = coloring.channels();
+    for (i, row_pixels) in pixels.chunks_mut(bounds.width * channels).enumerate() {
+        for (column, pixel) in row_pixels.chunks_mut(channels).enumerate() {
+            let point = precision::pixel_to_point(bounds, (column, top + i), view);
+            let count = match smooth_extra {
+                Some(extra) => precision::smooth_escape_time(point, limit, extra),
+                None => {
+                    let count = precision::escape_time(point, limit);
+                    add_iterations(count.map_or(limit as u64, |count| count as u64 + 1));
+                    count.map(|count| count as f64)
+                }
+            };
+            let color = coloring.paint(count, count.is_none(), None, limit);
+            pixel.copy_from_slice(&color[..channels]);
+        }
+    }
+}
+
+/// Check, as `render_image` does, that an image of size 'bounds'
+/// covering 'view' can be rendered, and allocate a zeroed buffer for it.
+fn allocate_image(bounds: PixelBounds, view: ComplexView, coloring: Coloring, mask: Option<&Mask>)
+    -> Result<Vec<u8>, RenderError> {
+    let bytes = bounds.width.checked_mul(bounds.height)
+        .and_then(|pixels| pixels.checked_mul(coloring.channels()))
+        .filter(|&bytes| bytes > 0)
+        .ok_or(RenderError::InvalidBounds(bounds))?;
+
+    let corners = [view.upper_left.re, view.upper_left.im, view.lower_right.re, view.lower_right.im];
+    if !corners.iter().all(|corner| corner.is_finite())
+        || view.upper_left.re == view.lower_right.re
+        || view.upper_left.im == view.lower_right.im
+    {
+        return Err(RenderError::InvalidView(view));
+    }
+    if mask.is_some_and(|mask| mask.bounds() != bounds) {
+        return Err(RenderError::InvalidBounds(bounds));
+    }
+
+    let mut pixels = Vec::new();
+    pixels.try_reserve_exact(bytes).map_err(|_| RenderError::Allocation { bytes })?;
+    pixels.resize(bytes, 0);
+    Ok(pixels)
+}
+
+/// Like `render_image`, but render the image a band of rows at a time,
+/// and after each band, if at least 'interval' has passed since the
+/// last, hand the pixels so far to 'preview'. Rows not rendered yet are
+/// still zero bytes, which is black, or transparent for RGBA.
+///
+/// Each band is rendered on all the threads, and is as many rows as
+/// there are threads, or a sixty-fourth of the image if that's more, so
+/// the previews come often enough to watch without the threads running
+/// out of work. The pixels are the same as `render_image` gives.
+#[cfg(feature = "threads")]
+#[allow(clippy::too_many_arguments)]
+pub fn render_image_with_preview<F>(bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        mask: Option<&Mask>,
+        threading: Threading,
+        interval: Duration,
+        mut preview: F) -> Result<Vec<u8>, RenderError>
+    where F: FnMut(&[u8]) -> Result<(), RenderError>
+{
+    let mut pixels = allocate_image(bounds, view, coloring, mask)?;
+    let row_len = bounds.width * coloring.channels();
+    let band_rows = (bounds.height / 64).max(threading.threads).max(1);
+
+    let mut last = Instant::now();
+    for top in (0 .. bounds.height).step_by(band_rows) {
+        let bottom = (top + band_rows).min(bounds.height);
+        render_parallel_rows(&mut pixels[top * row_len .. bottom * row_len], bounds, view, top,
+                             shading, limit, coloring, sampling, mask, threading, None);
+        // Leave the finished image to the caller rather than previewing it.
+        if bottom < bounds.height && last.elapsed() >= interval {
+            preview(&pixels)?;
+            last = Instant::now();
+        }
+    }
+    Ok(pixels)
+}
+
+/// A switch for pausing a render from another thread, such as one
+/// reading keys. The rendering threads check it between chunks, and
+/// while it's on they sleep instead of spinning, freeing the CPU.
+#[cfg(feature = "threads")]
+#[derive(Debug, Default)]
+pub struct Pause {
+    paused: AtomicBool,
+    lock: Mutex<()>,
+    resumed: Condvar,
+}
+
+#[cfg(feature = "threads")]
+impl Pause {
+    pub fn new() -> Pause {
+        Pause::default()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Pause, or with 'paused' false, resume, waking every waiting thread.
+    pub fn set(&self, paused: bool) {
+        // Change the flag under the lock, so a thread that has just seen
+        // it on can't miss the wakeup before it starts waiting.
+        let _guard = self.lock.lock().unwrap();
+        self.paused.store(paused, Ordering::SeqCst);
+        if !paused {
+            self.resumed.notify_all();
+        }
+    }
+
+    /// Pause if running and resume if paused. Returns whether it is now
+    /// paused.
+    pub fn toggle(&self) -> bool {
+        let _guard = self.lock.lock().unwrap();
+        let paused = !self.paused.load(Ordering::SeqCst);
+        self.paused.store(paused, Ordering::SeqCst);
+        if !paused {
+            self.resumed.notify_all();
+        }
+        paused
+    }
+
+    /// Return at once if running, or otherwise once resumed.
+    pub fn wait(&self) {
+        if !self.is_paused() {
+            return;
+        }
+        let mut guard = self.lock.lock().unwrap();
+        while self.is_paused() {
+            guard = self.resumed.wait(guard).unwrap();
+        }
+    }
+}
+
+/// Like `render_image`, but the threads wait whenever 'pause' is on. The
+/// image is handed out a row at a time unless 'threading' says otherwise,
+/// so a pause takes hold within a row. The pixels are the same as
+/// `render_image` gives.
+#[cfg(feature = "threads")]
+#[allow(clippy::too_many_arguments)]
+pub fn render_image_pausable(bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        mask: Option<&Mask>,
+        threading: Threading,
+        pause: &Pause) -> Result<Vec<u8>, RenderError>
+{
+    let mut pixels = allocate_image(bounds, view, coloring, mask)?;
+    let threading = Threading { chunk_rows: threading.chunk_rows.or(Some(1)), ..threading };
+    render_parallel_rows(&mut pixels, bounds, view, 0, shading, limit, coloring, sampling, mask,
+                         threading, Some(pause));
+    Ok(pixels)
+}
+
+/// Render the image described by the arguments like `render_image`, then
+/// render it again with different threading and check that the two
+/// agree byte for byte. The second render is on a single thread in one
+/// band, or, if 'threading' already asks for that, on two threads taking
+/// a row at a time.
+///
+/// Every pixel depends only on its own position, so the split into
+/// threads and chunks must not change the result; a difference points to
+/// a data race or to rows going to the wrong place. This is a debugging
+/// tool that costs a second render. Returns the pixels if the renders
+/// agree, and otherwise `RenderError::Nondeterministic` with the first
+/// pixel that differs.
+#[allow(clippy::too_many_arguments)]
+pub fn render_verified(bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling,
+        mask: Option<&Mask>,
+        threading: Threading) -> Result<Vec<u8>, RenderError>
+{
+    let pixels = render_image(bounds, view, shading, limit, coloring, sampling, mask, threading)?;
+    let reference = match Threading::new(1) {
+        single if single == threading => Threading { threads: 2, chunk_rows: Some(1) },
+        single => single,
+    };
+    let again = render_image(bounds, view, shading, limit, coloring, sampling, mask, reference)?;
+
+    match pixels.iter().zip(&again).position(|(a, b)| a != b) {
+        Some(byte) => {
+            let pixel = byte / coloring.channels();
+            Err(RenderError::Nondeterministic { column: pixel % bounds.width, row: pixel / bounds.width })
+        }
+        None => Ok(pixels),
+    }
+}
+
+/// Like `render_image` with escape-time shading and no supersampling or
+/// mask, but on the calling thread by `render_field_mariani_silver`,
+/// which fills uniform areas instead of iterating every pixel in them.
+/// Also returns the number of pixels iterated.
+pub fn render_image_mariani_silver(bounds: PixelBounds,
+        view: ComplexView,
+        limit: u32,
+        coloring: Coloring) -> Result<(Vec<u8>, usize), RenderError>
+{
+    let mut pixels = allocate_image(bounds, view, coloring, None)?;
+    let (field, computed) = render_field_mariani_silver(bounds, view, limit);
+    paint_field(&mut pixels, &field, limit, coloring, |count| count as f64);
+    Ok((pixels, computed))
+}
+
+/// Like `render_image` with escape-time shading and no supersampling or
+/// mask, but on the calling thread, and with the coloring stretched from
+/// the lowest escape count in the image to the highest instead of from 0
+/// to 'limit', for the most contrast. Views far from the set, whose
+/// counts are all low, otherwise use only a sliver of the palette. With
+/// one count or none the exterior gets the color of count 0.
+pub fn render_image_auto_contrast(bounds: PixelBounds,
+        view: ComplexView,
+        limit: u32,
+        coloring: Coloring) -> Result<Vec<u8>, RenderError>
+{
+    let mut pixels = allocate_image(bounds, view, coloring, None)?;
+    let field = render_field(bounds, view, limit);
+    let (lowest, highest) = escape_range(&field).unwrap_or((0, 0));
+    let span = (highest - lowest).max(1) as f64;
+    paint_field(&mut pixels, &field, limit, coloring,
+                |count| (count - lowest) as f64 * limit as f64 / span);
+    Ok(pixels)
+}
+
+/// Color the pixels of 'field' into 'pixels' as `Coloring::paint` does,
+/// taking each escape count to the count 'scale' gives for it, on a
+/// scale up to 'limit'.
+fn paint_field(pixels: &mut [u8], field: &[Escape], limit: u32, coloring: Coloring,
+               scale: impl Fn(u32) -> f64) {
+    let channels = coloring.channels();
+    for (pixel, escape) in pixels.chunks_mut(channels).zip(field) {
+        let color = coloring.paint(escape.count().map(&scale), escape.is_interior(), None, limit);
+        pixel.copy_from_slice(&color[..channels]);
+    }
+}
+
+/// Like `render_image` with escape-time shading and no supersampling or
+/// mask, but iterating 'map' instead of the Mandlebrot map, on the
+/// calling thread.
+pub fn render_image_map<M: IterMap>(map: &M,
+        bounds: PixelBounds,
+        view: ComplexView,
+        limit: u32,
+        coloring: Coloring) -> Result<Vec<u8>, RenderError>
+{
+    let mut pixels = allocate_image(bounds, view, coloring, None)?;
+    let channels = coloring.channels();
+    // The points of each row as `render_rows` takes them, so that for the
+    // Mandlebrot map the pixels are exactly the same.
+    let mut points = vec![Complex { re: 0.0, im: 0.0 }; bounds.width];
+    for (row, row_pixels) in pixels.chunks_mut(bounds.width * channels).enumerate() {
+        row_points(bounds, row, view, &mut points);
+        for (pixel, &point) in row_pixels.chunks_mut(channels).zip(&points) {
+            let count = escape_time_generic(map, point, limit);
+            let color = coloring.paint(count.map(|count| count as f64), count.is_none(), None,
+                                       limit);
+            pixel.copy_from_slice(&color[..channels]);
+        }
+    }
+    Ok(pixels)
+}
+
+/// Render an image of size 'bounds' covering 'view' in gray, as `render`
+/// does but with at most 'limit' iterations, on the calling thread, and
+/// draw it onto 'canvas' with its top left corner at the column and row
+/// 'at', for compositing several views into one picture. The rest of the
+/// canvas is left as it was. Whatever falls off the right or bottom edge
+/// of the canvas is clipped.
+pub fn render_into(canvas: &mut RgbImage,
+        at: (u32, u32),
+        bounds: PixelBounds,
+        view: ComplexView,
+        limit: u32) -> Result<(), RenderError>
+{
+    let mut pixels = allocate_image(bounds, view, Coloring::Gray, None)?;
+    render_rows(&mut pixels, bounds, view, 0, Shading::EscapeTime, limit, Coloring::Gray,
+                Supersampling::none(), None);
+
+    let (left, top) = (at.0 as usize, at.1 as usize);
+    let width = bounds.width.min((canvas.width() as usize).saturating_sub(left));
+    let height = bounds.height.min((canvas.height() as usize).saturating_sub(top));
+    for row in 0..height {
+        for column in 0..width {
+            let gray = pixels[row * bounds.width + column];
+            canvas.put_pixel((left + column) as u32, (top + row) as u32, Rgb([gray, gray, gray]));
+        }
+    }
+    Ok(())
+}
+
+/// Render a whole image on the calling thread and return it as RGBA, four
+/// bytes per pixel, the layout a canvas's `ImageData` expects. Grayscale
+/// is spread over the three color bytes. Pixels are opaque unless
+/// 'coloring' is `Coloring::Alpha`, whose alpha is kept as it is.
+///
+/// This touches no threads or files, so it is the entry point to use from
+/// WebAssembly.
+pub fn render_rgba(bounds: PixelBounds,
+        view: ComplexView,
+        shading: Shading,
+        limit: u32,
+        coloring: Coloring,
+        sampling: Supersampling) -> Vec<u8>
+{
+    let channels = coloring.channels();
+    let mut pixels = vec![0; bounds.pixel_count() * channels];
+    render_rows(&mut pixels, bounds, view, 0, shading, limit, coloring, sampling, None);
+
+    let mut rgba = Vec::with_capacity(bounds.pixel_count() * 4);
+    for pixel in pixels.chunks(channels) {
+        match *pixel {
+            [r, g, b, a] => rgba.extend_from_slice(&[r, g, b, a]),
+            [r, g, b] => rgba.extend_from_slice(&[r, g, b, 255]),
+            _ => rgba.extend_from_slice(&[pixel[0], pixel[0], pixel[0], 255]),
+        }
+    }
+    rgba
+}
+
+/// Measurements of one finished render, for benchmarking harnesses and
+/// dashboards that track how fast renders run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderStats {
+    /// Wall-clock time the render took.
+    pub elapsed: Duration,
+    pub bounds: PixelBounds,
+    pub view: ComplexView,
+    pub limit: u32,
+    /// Samples each pixel took, as `Supersampling::samples` counts them.
+    pub samples: u64,
+    pub threads: usize,
+    /// Iterations the render ran, as `take_iterations` counts them:
+    /// every sample of every pixel rendered.
+    pub iterations: u64,
+}
+
+impl RenderStats {
+    /// 'iterations' divided by 'elapsed', or None if no time was measured.
+    pub fn iterations_per_second(&self) -> Option<f64> {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            Some(self.iterations as f64 / seconds)
+        } else {
+            None
+        }
+    }
+
+    /// These stats as a JSON object: `render_seconds`, `pixels`,
+    /// `iterations`, `threads` and `iterations_per_second` (null if no
+    /// time was measured), and under `parameters` the `width`, `height`,
+    /// `upper_left` and `lower_right` corners as `[re, im]`, `limit` and
+    /// `samples_per_pixel` the render was run with. Numbers are written
+    /// with every digit needed to read back exactly.
+    pub fn to_json(&self) -> String {
+        let corner = |point: Complex<f64>| format!("[{:e}, {:e}]", point.re, point.im);
+        let per_second = self.iterations_per_second()
+            .map_or_else(|| "null".to_string(), |rate| format!("{:e}", rate));
+        format!("{{\n  \"render_seconds\": {:e},\n  \"pixels\": {},\n  \"iterations\": {},\n  \
+                 \"threads\": {},\n  \"iterations_per_second\": {},\n  \"parameters\": {{\
+                 \"width\": {}, \"height\": {}, \"upper_left\": {}, \"lower_right\": {}, \
+                 \"limit\": {}, \"samples_per_pixel\": {}}}\n}}\n",
+                self.elapsed.as_secs_f64(), self.bounds.pixel_count(), self.iterations, self.threads,
+                per_second, self.bounds.width, self.bounds.height, corner(self.view.upper_left),
+                corner(self.view.lower_right), self.limit, self.samples)
+    }
+}
+
+/// render_blocked test: block by block gives the same bytes as row by row.
+#[test]
+fn test_render_blocked() {
+    let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+    let views = [
+        ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 }),
+        ComplexView::new(Complex { re: -1.7499, im: 0.0311 }, Complex { re: -1.7411, im: 0.0223 }),
+        ComplexView::new(Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+            .with_projection(Projection::Stereographic),
+    ];
+    // Sizes that are, aren't, and are less than whole blocks.
+    for &(width, height) in &[(128, 64), (201, 77), (13, 5)] {
+        let bounds = PixelBounds::new(width, height);
+        for &view in &views {
+            for &coloring in &[Coloring::Gray, Coloring::Palette { colors: &colors, repeat: 2 }] {
+                let channels = coloring.channels();
+                let mut rows = vec![0; bounds.pixel_count() * channels];
+                render_rows(&mut rows, bounds, view, 0, Shading::Smooth { extra: 3 }, LIMIT, coloring,
+                            Supersampling::none(), None);
+                let mut blocks = vec![0; bounds.pixel_count() * channels];
+                render_blocked(&mut blocks, bounds, view, Shading::Smooth { extra: 3 }, LIMIT,
+                               coloring);
+                assert!(blocks == rows, "{}x{} {:?}", width, height, view);
+            }
+        }
+    }
+}
+
+/// render_rows test: reusing the thread's scratch buffer across calls of
+/// different widths, with supersampling in between, changes nothing.
+#[test]
+fn test_render_rows_scratch() {
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let none = Supersampling::none();
+    let render = move |bounds: PixelBounds, sampling| {
+        let mut pixels = vec![0; bounds.pixel_count()];
+        render_rows(&mut pixels, bounds, view, 0, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                    sampling, None);
+        pixels
+    };
+    // A new thread starts with an empty buffer.
+    let fresh = |bounds: PixelBounds, sampling| std::thread::spawn(move || render(bounds, sampling))
+        .join().unwrap();
+
+    let (wide, narrow) = (PixelBounds::new(90, 20), PixelBounds::new(31, 17));
+    let sampled = Supersampling::square(2, 5);
+    assert_eq!(render(wide, none), fresh(wide, none));
+    assert_eq!(render(narrow, none), fresh(narrow, none));
+    assert_eq!(render(narrow, sampled), fresh(narrow, sampled));
+    assert_eq!(render(wide, none), fresh(wide, none));
+}
+
+/// Pause test: a paused render waits, and finishes once resumed.
+#[cfg(feature = "threads")]
+#[test]
+fn test_pause() {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    // A thread waiting on a pause is held until it's resumed.
+    let pause = Arc::new(Pause::new());
+    assert!(pause.toggle());
+    let passed = Arc::new(AtomicUsize::new(0));
+    let waiter = {
+        let (pause, passed) = (pause.clone(), passed.clone());
+        thread::spawn(move || {
+            pause.wait();
+            passed.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(passed.load(Ordering::SeqCst), 0);
+    assert!(!pause.toggle());
+    waiter.join().unwrap();
+    assert_eq!(passed.load(Ordering::SeqCst), 1);
+
+    // So is a render, which then gives the same pixels as ever.
+    let bounds = PixelBounds::new(80, 60);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let none = Supersampling::none();
+    pause.set(true);
+    let render = {
+        let pause = pause.clone();
+        thread::spawn(move || {
+            render_image_pausable(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray, none,
+                                  None, Threading::new(3), &pause).unwrap()
+        })
+    };
+    thread::sleep(Duration::from_millis(100));
+    assert!(!render.is_finished());
+    pause.set(false);
+    let expected = render_image(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray, none,
+                                None, Threading::new(3)).unwrap();
+    assert!(render.join().unwrap() == expected);
+    assert!(!pause.is_paused());
+}
+
+/// render_image_mariani_silver test
+#[test]
+fn test_render_image_mariani_silver() {
+    let bounds = PixelBounds::new(120, 90);
+    let view = ComplexView::new(Complex { re: -0.8, im: 0.6 }, Complex { re: 0.3, im: -0.6 });
+    let colors = [[255, 0, 0], [0, 0, 255]];
+    for &coloring in &[Coloring::Gray, Coloring::Palette { colors: &colors, repeat: 2 }] {
+        let (pixels, computed) = render_image_mariani_silver(bounds, view, 100, coloring).unwrap();
+        let expected = render_rgba(bounds, view, Shading::EscapeTime, 100, coloring,
+                                   Supersampling::none());
+        let channels = coloring.channels();
+        for (pixel, rgba) in pixels.chunks(channels).zip(expected.chunks(4)) {
+            assert_eq!(pixel, &rgba[..channels]);
+        }
+        assert!(computed < bounds.pixel_count());
+    }
+}
+
+/// render_image_auto_contrast test: counts in a narrow band are spread
+/// over every gray level
+#[test]
+fn test_render_image_auto_contrast() {
+    // Outside the set, where every point escapes within a few iterations.
+    let bounds = PixelBounds::new(40, 30);
+    let view = ComplexView::new(Complex { re: 0.5, im: 1.0 }, Complex { re: 1.5, im: 0.25 });
+    let plain = render_image(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                             Supersampling::none(), None, Threading::new(1)).unwrap();
+    assert!(*plain.iter().min().unwrap() > 200);
+
+    let stretched = render_image_auto_contrast(bounds, view, LIMIT, Coloring::Gray).unwrap();
+    assert_eq!(stretched.iter().min(), Some(&0));
+    assert_eq!(stretched.iter().max(), Some(&255));
+    // Brighter stays brighter.
+    for i in 1 .. plain.len() {
+        if plain[i - 1] < plain[i] {
+            assert!(stretched[i - 1] <= stretched[i], "at {}", i);
+        }
+    }
+}
+
+/// render_image_map test: the Mandlebrot map gives the usual image
+#[test]
+fn test_render_image_map() {
+    use crate::fractal::{Julia, Mandelbrot};
+
+    let bounds = PixelBounds::new(60, 40);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let colors = [[255, 0, 0], [0, 0, 255]];
+    for &coloring in &[Coloring::Gray, Coloring::Palette { colors: &colors, repeat: 1 }] {
+        let pixels = render_image_map(&Mandelbrot, bounds, view, 100, coloring).unwrap();
+        let expected = render_image(bounds, view, Shading::EscapeTime, 100, coloring,
+                                    Supersampling::none(), None, Threading::new(1)).unwrap();
+        assert!(pixels == expected);
+    }
+
+    // The Julia set of c = 0 is the unit disk: black inside it.
+    let disk = render_image_map(&Julia { c: Complex { re: 0.0, im: 0.0 } }, bounds,
+                                Julia::whole_view(bounds), 100, Coloring::Gray).unwrap();
+    assert_eq!(disk[20 * 60 + 30], 0);
+    assert!(disk[0] > 0);
+}
+
+/// render_into test
+#[test]
+fn test_render_into() {
+    let background = Rgb([10, 20, 30]);
+    let bounds = PixelBounds::new(8, 6);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let expected = render_rgba(bounds, view, Shading::EscapeTime, 100, Coloring::Gray,
+                               Supersampling::none());
+
+    // Into the bottom right corner, touching no other pixel.
+    let mut canvas = RgbImage::from_pixel(20, 15, background);
+    render_into(&mut canvas, (12, 9), bounds, view, 100).unwrap();
+    for (x, y, pixel) in canvas.enumerate_pixels() {
+        if x >= 12 && y >= 9 {
+            let offset = ((y - 9) as usize * bounds.width + (x - 12) as usize) * 4;
+            assert_eq!(pixel.data[..], expected[offset .. offset + 3]);
+        } else {
+            assert_eq!(*pixel, background, "{},{}", x, y);
+        }
+    }
+
+    // Hanging over the edge, and off it entirely, is clipped.
+    let mut clipped = RgbImage::from_pixel(20, 15, background);
+    render_into(&mut clipped, (16, 12), bounds, view, 100).unwrap();
+    assert_eq!(clipped.get_pixel(16, 12).data[..], expected[..3]);
+    assert_eq!(*clipped.get_pixel(15, 14), background);
+    let mut untouched = RgbImage::from_pixel(20, 15, background);
+    render_into(&mut untouched, (20, 0), bounds, view, 100).unwrap();
+    assert!(untouched.pixels().all(|&pixel| pixel == background));
+
+    assert!(render_into(&mut untouched, (0, 0), PixelBounds::new(0, 5), view, 100).is_err());
+}
+
+/// Supersampling test: a grid of two columns and one row averages two
+/// samples side by side in each pixel
+#[test]
+fn test_anamorphic_supersampling() {
+    let bounds = PixelBounds::new(30, 20);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let sampling = Supersampling { grid_x: 2, grid_y: 1, seed: 7, agree: 0, roi: None };
+    assert_eq!(sampling.samples(), 2);
+
+    for &(column, row) in &[(0, 0), (14, 9), (21, 13), (29, 19)] {
+        let mut sum = 0;
+        for cell in 0..2 {
+            let jitter = rng::hash(7, &[column as u64, row as u64, cell]);
+            let x = column as f64 + (cell as f64 + rng::unit_float(jitter)) / 2.0;
+            let y = row as f64 + rng::unit_float(rng::mix(jitter));
+            let point = subpixel_to_point(bounds, (x, y), view);
+            sum += Coloring::Gray.color(point, Shading::EscapeTime, LIMIT)[0] as u32;
+        }
+        let color = sampling.pixel_color(bounds, view, (column, row), Shading::EscapeTime, LIMIT,
+                                         Coloring::Gray);
+        assert_eq!(color[0] as u32, sum.div_ceil(2), "at {},{}", column, row);
+    }
+
+    // A square grid is the same whichever way it's written.
+    assert_eq!(Supersampling::square(3, 1),
+               Supersampling { grid_x: 3, grid_y: 3, seed: 1, agree: 0, roi: None });
+    assert_eq!(Supersampling { grid_x: 0, grid_y: 1, ..Supersampling::none() }.samples(), 1);
+}
+
+/// Supersampling test: only the pixels in the region of interest are
+/// averaged; the rest are single samples, as without supersampling.
+#[test]
+fn test_supersampling_roi() {
+    let bounds = PixelBounds::new(30, 20);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let full = Supersampling::square(3, 5);
+    let roi = Supersampling { roi: Some((10, 5, 12, 8)), ..full };
+    let mut pixels = vec![0; bounds.pixel_count()];
+    render_rows(&mut pixels, bounds, view, 0, Shading::EscapeTime, LIMIT, Coloring::Gray, roi, None);
+
+    let mut averaged = 0;
+    for row in 0 .. bounds.height {
+        for column in 0 .. bounds.width {
+            let pixel = pixels[row * bounds.width + column];
+            let sampled = full.pixel_color(bounds, view, (column, row), Shading::EscapeTime, LIMIT,
+                                           Coloring::Gray)[0];
+            let single = Coloring::Gray.color(pixel_to_point(bounds, (column, row), view),
+                                              Shading::EscapeTime, LIMIT)[0];
+            if (10 .. 22).contains(&column) && (5 .. 13).contains(&row) {
+                assert_eq!(pixel, sampled, "at {},{}", column, row);
+                averaged += (sampled != single) as usize;
+            } else {
+                assert_eq!(pixel, single, "at {},{}", column, row);
+            }
+        }
+    }
+    // The region straddles the edge of the set, where averaging shows.
+    assert!(averaged > 10, "{}", averaged);
+}
+
+/// Supersampling test: stopping once the first samples agree leaves flat
+/// areas exactly as full sampling colors them.
+#[test]
+fn test_supersampling_early_out() {
+    let bounds = PixelBounds::new(24, 16);
+    let full = Supersampling::square(4, 3);
+    let early = Supersampling { agree: 4, ..full };
+    let palette = [[255, 0, 0], [0, 0, 255]];
+    let coloring = Coloring::Palette { colors: &palette, repeat: 2 };
+    let render = |view, sampling| {
+        let mut pixels = vec![0; bounds.pixel_count() * 3];
+        render_rows(&mut pixels, bounds, view, 0, Shading::EscapeTime, LIMIT, coloring, sampling,
+                    None);
+        pixels
+    };
+
+    // Deep in the main cardioid, and far enough out that every point
+    // escapes at once: both uniform.
+    for view in [ComplexView::new(Complex { re: -0.3, im: 0.2 }, Complex { re: -0.1, im: 0.0 }),
+                 ComplexView::new(Complex { re: 3.0, im: 4.0 }, Complex { re: 5.0, im: 2.0 })] {
+        let expected = render(view, full);
+        assert!(expected.chunks(3).all(|pixel| pixel == &expected[..3]));
+        assert!(render(view, early) == expected, "{:?}", view);
+    }
+
+    // Asking for agreement across the whole grid is full sampling.
+    let whole = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert!(render(whole, Supersampling { agree: 16, ..full }) == render(whole, full));
+    // Across an edge the two needn't agree, but most pixels still do.
+    let (partial, expected) = (render(whole, early), render(whole, full));
+    let same = partial.chunks(3).zip(expected.chunks(3)).filter(|(a, b)| a == b).count();
+    assert!(same * 10 > bounds.pixel_count() * 9, "{} of {}", same, bounds.pixel_count());
+
+    // The order cells are visited in covers each exactly once.
+    for samples in 1 ..= 64 {
+        let stride = cell_stride(samples);
+        let mut cells: Vec<u64> = (0 .. samples).map(|visit| visit * stride % samples).collect();
+        cells.sort_unstable();
+        assert!(cells.into_iter().eq(0 .. samples), "{} samples", samples);
+    }
+}
+
+/// fit_time_budget test
+#[test]
+fn test_fit_time_budget() {
+    let requested = Quality {
+        bounds: PixelBounds::new(800, 600),
+        limit: 1000,
+        sampling: Supersampling::square(3, 1),
+    };
+    // 800×600 pixels × 9 samples × 1000 iterations at 10ns is 43.2s.
+    let per_iteration = Duration::from_nanos(10);
+    assert_eq!(requested.estimate(per_iteration), Duration::from_millis(43200));
+    assert_eq!(fit_time_budget(requested, per_iteration, Duration::from_secs(60)), requested);
+
+    let mut previous = requested;
+    for &seconds in &[30.0, 10.0, 2.0, 0.5, 0.05, 0.001] {
+        let budget = Duration::from_secs_f64(seconds);
+        let quality = fit_time_budget(requested, per_iteration, budget);
+        assert!(quality.estimate(per_iteration) <= budget.mul_f64(TIME_BUDGET_MARGIN),
+                "{:?} doesn't fit {}s", quality, seconds);
+        // Less time never buys more of anything.
+        assert!(quality.sampling.samples() <= previous.sampling.samples());
+        assert!(quality.limit <= previous.limit);
+        assert!(quality.bounds.pixel_count() <= previous.bounds.pixel_count());
+        assert!(quality.limit >= TIME_BUDGET_MIN_LIMIT);
+        previous = quality;
+    }
+
+    // Supersampling goes before iterations, iterations before resolution.
+    let quality = fit_time_budget(requested, per_iteration, Duration::from_secs(10));
+    assert_eq!((quality.sampling.samples(), quality.limit, quality.bounds), (1, 1000, requested.bounds));
+    let quality = fit_time_budget(requested, per_iteration, Duration::from_secs(2));
+    assert_eq!((quality.sampling.samples(), quality.bounds), (1, requested.bounds));
+    assert!(quality.limit < 1000);
+
+    // However hopeless the budget, the result is still a render.
+    let quality = fit_time_budget(requested, Duration::from_secs(1), Duration::from_nanos(1));
+    assert_eq!(quality.bounds, PixelBounds::new(1, 1));
+
+    let speed = measure_speed(requested, ComplexView::new(Complex { re: -2.0, im: 1.0 },
+                                                          Complex { re: 1.0, im: -1.0 }),
+                              Shading::EscapeTime, Coloring::Gray, Threading::new(1));
+    assert!(speed > Duration::ZERO);
+}
+
+/// render_rgb test
+#[test]
+fn test_render_rgb() {
+    // c = 0 is a member; c = 3 escapes at once, landing on the first stop.
+    let view = ComplexView::new(Complex { re: 0.0, im: 0.0 },
+                                Complex { re: 6.0, im: 0.0 });
+    let palette = [[10, 20, 30], [40, 50, 60]];
+    let mut pixels = vec![1; 6];
+    render_rgb(&mut pixels, PixelBounds::new(2, 1), view, Shading::EscapeTime, &palette, 1);
+    assert_eq!(pixels, vec![0, 0, 0, 10, 20, 30]);
+}
+
+/// render_parallel test
+#[test]
+fn test_render_parallel() {
+    let bounds = PixelBounds::new(37, 23);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 },
+                                Complex { re: 1.0, im: -1.0 });
+    let palette = [[255, 0, 0], [0, 0, 255]];
+    let none = Supersampling::none();
+
+    let mut single = vec![0; bounds.pixel_count()];
+    render(&mut single, bounds, view, Shading::EscapeTime);
+    let mut banded = vec![0; bounds.pixel_count()];
+    render_parallel(&mut banded, bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray, none,
+                    None, Threading::new(4));
+    assert!(single == banded);
+
+    let mut single = vec![0; bounds.pixel_count() * 3];
+    render_rgb(&mut single, bounds, view, Shading::EscapeTime, &palette, 2);
+    let mut banded = vec![0; bounds.pixel_count() * 3];
+    let coloring = Coloring::Palette { colors: &palette, repeat: 2 };
+    render_parallel(&mut banded, bounds, view, Shading::EscapeTime, LIMIT, coloring, none, None,
+                    Threading::new(4));
+    assert!(single == banded);
+}
+
+/// Threading test: when the height isn't a multiple of the thread count
+/// the bands still cover every row exactly once, with the short one last,
+/// and stitch into the single-threaded image.
+#[test]
+fn test_render_parallel_uneven_bands() {
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 },
+                                Complex { re: 1.0, im: -1.0 });
+    let none = Supersampling::none();
+
+    for &(width, height, threads) in &[(4, 750, 8), (13, 23, 4), (9, 10, 8), (9, 8, 8), (11, 1, 3),
+                                       (6, 17, 16), (10, 99, 7)] {
+        let bounds = PixelBounds::new(width, height);
+        let threading = Threading::new(threads);
+        let rows = threading.rows_per_chunk(height);
+        let bands = height.div_ceil(rows);
+        assert!(bands <= threads, "{} rows in {} bands of {}", height, bands, rows);
+        let last = height - rows * (bands - 1);
+        assert!(last >= 1 && last <= rows);
+        if height % threads == 0 {
+            assert_eq!((bands, last), (threads, rows));
+        }
+
+        let mut single = vec![0; bounds.pixel_count()];
+        render(&mut single, bounds, view, Shading::EscapeTime);
+        let mut banded = vec![0; bounds.pixel_count()];
+        render_parallel(&mut banded, bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray, none,
+                        None, threading);
+        assert!(single == banded, "{}x{} on {} threads", width, height, threads);
+
+        // The views of bands rendered on their own, from
+        // `ComplexView::rows`, join without a row duplicated or skipped,
+        // short last band included. They differ from the whole view in
+        // the last bits, so an image stitched from them isn't exact.
+        let mut covered = 0;
+        for top in (0 .. height).step_by(rows) {
+            let band_height = rows.min(height - top);
+            let band = PixelBounds::new(width, band_height);
+            let band_view = view.rows(bounds, top, band_height);
+            for row in 0 .. band_height {
+                let point = pixel_to_point(band, (width - 1, row), band_view);
+                let expected = pixel_to_point(bounds, (width - 1, top + row), view);
+                assert!((point - expected).norm() < 1e-12, "row {} of the band at {}", row, top);
+            }
+            covered += band_height;
+        }
+        assert_eq!(covered, height);
+    }
+}
+
+/// Lut coloring test: whole escape counts color exactly as `Palette`.
+#[test]
+fn test_render_lut() {
+    let bounds = PixelBounds::new(40, 30);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 },
+                                Complex { re: 1.0, im: -1.0 });
+    let palette = [[255, 0, 0], [0, 200, 0], [0, 0, 255]];
+    let none = Supersampling::none();
+
+    let mut direct = vec![0; bounds.pixel_count() * 3];
+    let coloring = Coloring::Palette { colors: &palette, repeat: 3 };
+    render_rows(&mut direct, bounds, view, 0, Shading::EscapeTime, LIMIT, coloring, none, None);
+    let lut = PaletteLut::new(&palette, LIMIT, 3, 1, crate::palette::Interpolation::Rgb);
+    let mut looked_up = vec![0; bounds.pixel_count() * 3];
+    render_rows(&mut looked_up, bounds, view, 0, Shading::EscapeTime, LIMIT, Coloring::Lut(&lut),
+                none, None);
+    assert!(direct == looked_up);
+}
+
+/// Threading test: the chunk size changes how work is shared out, never
+/// the pixels.
+#[test]
+fn test_render_parallel_chunk_rows() {
+    let bounds = PixelBounds::new(29, 31);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 },
+                                Complex { re: 1.0, im: -1.0 });
+    let shading = Shading::Smooth { extra: 3 };
+    let none = Supersampling::none();
+
+    let mut expected = vec![0; bounds.pixel_count()];
+    render(&mut expected, bounds, view, shading);
+
+    for &(threads, chunk_rows) in &[(1, None), (3, Some(1)), (3, Some(4)), (5, Some(7)),
+                                    (2, Some(31)), (4, Some(100))] {
+        let mut pixels = vec![0; bounds.pixel_count()];
+        let threading = Threading { threads, chunk_rows };
+        render_parallel(&mut pixels, bounds, view, shading, LIMIT, Coloring::Gray, none, None,
+                        threading);
+        assert!(pixels == expected, "{:?} rendered differently", threading);
+    }
+}
+
+/// Supersampling test: separately rendered tiles stitch into exactly the
+/// image rendered in one go, and the jitter depends only on the seed.
+#[test]
+fn test_supersampling_tiles_stitch() {
+    let bounds = PixelBounds::new(40, 30);
+    let view = ComplexView::new(Complex { re: -1.5, im: 0.8 },
+                                Complex { re: 0.3, im: -0.6 });
+    let shading = Shading::Smooth { extra: 3 };
+    let sampling = Supersampling::square(3, 42);
+
+    let mut whole = vec![0; bounds.pixel_count()];
+    render_rows(&mut whole, bounds, view, 0, shading, LIMIT, Coloring::Gray, sampling, None);
+
+    // Two tiles of 13 and 17 rows, rendered on their own.
+    let mut upper = vec![0; 13 * bounds.width];
+    render_rows(&mut upper, bounds, view, 0, shading, LIMIT, Coloring::Gray, sampling, None);
+    let mut lower = vec![0; 17 * bounds.width];
+    render_rows(&mut lower, bounds, view, 13, shading, LIMIT, Coloring::Gray, sampling, None);
+    upper.extend(lower);
+    assert!(upper == whole);
+
+    let mut threaded = vec![0; bounds.pixel_count()];
+    render_parallel(&mut threaded, bounds, view, shading, LIMIT, Coloring::Gray, sampling, None,
+                    Threading::new(7));
+    assert!(threaded == whole);
+
+    // A different seed moves the samples, and averaging them differs from
+    // taking one sample per pixel.
+    let mut reseeded = vec![0; bounds.pixel_count()];
+    let other_seed = Supersampling::square(3, 43);
+    render_rows(&mut reseeded, bounds, view, 0, shading, LIMIT, Coloring::Gray, other_seed, None);
+    assert!(reseeded != whole);
+    let mut aliased = vec![0; bounds.pixel_count()];
+    render(&mut aliased, bounds, view, shading);
+    assert!(aliased != whole);
+}
+
+/// Lyapunov shading test
+#[test]
+fn test_render_lyapunov() {
+    // c = -0.5 has an attracting fixed point; c = 1 diverges.
+    let view = ComplexView::new(Complex { re: -0.5, im: 0.0 },
+                                Complex { re: 2.5, im: 0.0 });
+    let mut pixels = vec![0; 2];
+    render(&mut pixels, PixelBounds::new(2, 1), view, Shading::Lyapunov);
+    assert!(pixels[0] > 0 && pixels[0] < 128);
+    assert!(pixels[1] > 128);
+}
+
+/// render_rgba test
+#[test]
+fn test_render_rgba() {
+    let bounds = PixelBounds::new(40, 30);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let none = Supersampling::none();
+
+    let mut gray = vec![0; bounds.pixel_count()];
+    render(&mut gray, bounds, view, Shading::EscapeTime);
+    let rgba = render_rgba(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray, none);
+    assert_eq!(rgba.len(), bounds.pixel_count() * 4);
+    for (pixel, &g) in rgba.chunks(4).zip(&gray) {
+        assert_eq!(pixel, &[g, g, g, 255]);
+    }
+
+    let palette = [[255, 0, 0], [0, 0, 255]];
+    let mut rgb = vec![0; bounds.pixel_count() * 3];
+    render_rgb(&mut rgb, bounds, view, Shading::EscapeTime, &palette, 1);
+    let coloring = Coloring::Palette { colors: &palette, repeat: 1 };
+    let rgba = render_rgba(bounds, view, Shading::EscapeTime, LIMIT, coloring, none);
+    for (pixel, color) in rgba.chunks(4).zip(rgb.chunks(3)) {
+        assert_eq!(&pixel[..3], color);
+        assert_eq!(pixel[3], 255);
+    }
+
+    // Interior alpha clears exactly the members of the set.
+    let coloring = Coloring::Alpha { base: &Coloring::Gray, clear: Region::Interior };
+    let rgba = render_rgba(bounds, view, Shading::EscapeTime, LIMIT, coloring, none);
+    assert_eq!(rgba.len(), bounds.pixel_count() * 4);
+    let membership = crate::field::render_membership(bounds, view, LIMIT);
+    assert!(membership.iter().any(|&member| member));
+    for ((pixel, &g), &member) in rgba.chunks(4).zip(&gray).zip(&membership) {
+        assert_eq!(&pixel[..3], &[g, g, g]);
+        assert_eq!(pixel[3], if member { 0 } else { 255 });
+    }
+}
+
+/// alpha test
+#[test]
+fn test_render_alpha() {
+    let bounds = PixelBounds::new(60, 40);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let none = Supersampling::none();
+
+    let lit = Shading::Lit { azimuth: 1.0, elevation: 0.5 };
+    for &shading in &[Shading::EscapeTime, Shading::Lyapunov, lit] {
+        let mut opaque = vec![0; bounds.pixel_count()];
+        render_rows(&mut opaque, bounds, view, 0, shading, LIMIT, Coloring::Gray, none, None);
+
+        let coloring = Coloring::Alpha { base: &Coloring::Gray, clear: Region::Interior };
+        let mut pixels = vec![0; bounds.pixel_count() * 4];
+        render_rows(&mut pixels, bounds, view, 0, shading, LIMIT, coloring, none, None);
+
+        let mut points = vec![Complex { re: 0.0, im: 0.0 }; bounds.width];
+        let mut interior = 0;
+        for (row, (row_pixels, row_gray)) in pixels.chunks(bounds.width * 4)
+            .zip(opaque.chunks(bounds.width)).enumerate()
+        {
+            row_points(bounds, row, view, &mut points);
+            for ((pixel, &gray), &point) in row_pixels.chunks(4).zip(row_gray).zip(&points) {
+                let is_interior = escape_time(point, LIMIT).is_none();
+                assert_eq!(pixel[3], if is_interior { 0 } else { 255 });
+                assert_eq!(&pixel[..3], &[gray, gray, gray]);
+                interior += is_interior as usize;
+            }
+        }
+        assert!(interior > 0 && interior < bounds.pixel_count());
+
+        let coloring = Coloring::Alpha { base: &Coloring::Gray, clear: Region::Exterior };
+        let mut inverse = vec![0; bounds.pixel_count() * 4];
+        render_rows(&mut inverse, bounds, view, 0, shading, LIMIT, coloring, none, None);
+        for (a, b) in pixels.chunks(4).zip(inverse.chunks(4)) {
+            assert_eq!(a[3], 255 - b[3]);
+        }
+    }
+}
+
+/// auto_tune_threads test
+#[cfg(feature = "threads")]
+#[test]
+fn test_auto_tune_threads() {
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let none = Supersampling::none();
+
+    for &(width, height, max_threads) in &[(400, 300, 6), (400, 300, 1), (3, 2, 8), (0, 0, 4)] {
+        let bounds = PixelBounds::new(width, height);
+        let threads = auto_tune_threads(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                                        none, max_threads);
+        assert!(threads >= 1 && threads <= max_threads);
+    }
+}
+
+/// estimate_render_time test
+#[test]
+fn test_estimate_render_time() {
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let estimate = |width, height| {
+        estimate_render_time(PixelBounds::new(width, height), view, Shading::EscapeTime, LIMIT,
+                             Coloring::Gray, Supersampling::none(), Threading::new(2))
+    };
+
+    // An empty image still probes a pixel, and costs next to nothing.
+    assert!(estimate(0, 0) < Duration::from_secs(1));
+    assert!(estimate(400, 300) > Duration::ZERO);
+    assert_eq!(probe_bounds(PixelBounds::new(400, 300), 1.0 / 16.0), PixelBounds::new(100, 75));
+}
+
+/// Masked render test: only the pixels the mask covers are computed.
+#[test]
+fn test_render_masked() {
+    let bounds = PixelBounds::new(30, 20);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let shading = Shading::EscapeTime;
+
+    let mut full = vec![0; bounds.pixel_count()];
+    assert_eq!(render_rows(&mut full, bounds, view, 0, shading, LIMIT, Coloring::Gray,
+                           Supersampling::none(), None),
+               bounds.pixel_count());
+
+    // Black on the left half, white on the right.
+    let gray: Vec<u8> = (0..bounds.pixel_count())
+        .map(|i| if i % bounds.width < bounds.width / 2 { 0 } else { 255 })
+        .collect();
+    let mask = Mask::from_gray(&gray, bounds);
+
+    for &sampling in &[Supersampling::none(), Supersampling::square(2, 1)] {
+        let mut expected = vec![0; bounds.pixel_count()];
+        render_rows(&mut expected, bounds, view, 0, shading, LIMIT, Coloring::Gray, sampling, None);
+
+        let mut masked = vec![7; bounds.pixel_count()];
+        let computed = render_rows(&mut masked, bounds, view, 0, shading, LIMIT, Coloring::Gray,
+                                   sampling, Some(&mask));
+        assert_eq!(computed, bounds.pixel_count() / 2);
+        for (i, (&pixel, &expected)) in masked.iter().zip(&expected).enumerate() {
+            let covered = i % bounds.width >= bounds.width / 2;
+            assert_eq!(pixel, if covered { expected } else { 0 });
+        }
+    }
+}
+
+/// render_image test
+#[test]
+fn test_render_image() {
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let (shading, none, threading) = (Shading::EscapeTime, Supersampling::none(), Threading::new(2));
+    let image = |bounds, view| {
+        render_image(bounds, view, shading, LIMIT, Coloring::Gray, none, None, threading)
+    };
+
+    let bounds = PixelBounds::new(30, 20);
+    let mut expected = vec![0; bounds.pixel_count()];
+    render(&mut expected, bounds, view, shading);
+    assert_eq!(image(bounds, view).unwrap(), expected);
+
+    for &bounds in &[PixelBounds::new(0, 20), PixelBounds::new(30, 0),
+                     PixelBounds::new(usize::MAX, 2)] {
+        match image(bounds, view) {
+            Err(RenderError::InvalidBounds(reported)) => assert_eq!(reported, bounds),
+            other => panic!("{:?} rendered as {:?}", bounds, other.map(|pixels| pixels.len())),
+        }
+    }
+
+    let flat = ComplexView::new(view.upper_left, Complex { re: 1.0, im: 1.0 });
+    assert!(matches!(image(bounds, flat), Err(RenderError::InvalidView(_))));
+    let infinite = ComplexView::new(Complex { re: f64::NEG_INFINITY, im: 1.0 }, view.lower_right);
+    assert!(matches!(image(bounds, infinite), Err(RenderError::InvalidView(_))));
+
+    // Fits in a usize, but not in memory.
+    assert!(matches!(image(PixelBounds::new(usize::MAX / 2, 1), view),
+                     Err(RenderError::Allocation { .. })));
+}
+
+/// Lit shading test
+#[test]
+fn test_lit_shading() {
+    let lit = |azimuth: f64, point| {
+        Shading::Lit { azimuth: azimuth.to_radians(), elevation: 30f64.to_radians() }
+            .count(point, LIMIT).unwrap()
+    };
+
+    // The boundary just right of the cusp faces right, so it is brightest
+    // (lowest count) with the light there, and darkest with it behind.
+    let boundary = Complex { re: 0.3, im: 0.0 };
+    assert!(lit(0.0, boundary) < lit(90.0, boundary));
+    assert!(lit(90.0, boundary) < lit(180.0, boundary));
+
+    // Inside the set is flat, so only the elevation matters.
+    let inside = Complex { re: -0.5, im: 0.0 };
+    let flat = LIMIT as f64 * (1.0 - 30f64.to_radians().sin());
+    for &azimuth in &[0.0, 90.0, 180.0] {
+        assert!((lit(azimuth, inside) - flat).abs() < 1e-9);
+    }
+}
+
+/// render_verified test
+#[test]
+fn test_render_verified() {
+    let bounds = PixelBounds::new(40, 30);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let colors = [[255, 0, 0], [0, 0, 255]];
+    let coloring = Coloring::Palette { colors: &colors, repeat: 2 };
+    let sampling = Supersampling::square(2, 5);
+
+    for &threading in &[Threading::new(1), Threading::new(4),
+                        Threading { threads: 3, chunk_rows: Some(7) }] {
+        let verified = render_verified(bounds, view, Shading::EscapeTime, LIMIT, coloring, sampling,
+                                       None, threading).unwrap();
+        let plain = render_image(bounds, view, Shading::EscapeTime, LIMIT, coloring, sampling, None,
+                                 threading).unwrap();
+        assert!(verified == plain);
+    }
+
+    assert!(matches!(render_verified(PixelBounds::new(0, 3), view, Shading::EscapeTime, LIMIT,
+                                     Coloring::Gray, sampling, None, Threading::new(2)),
+                     Err(RenderError::InvalidBounds(_))));
+}
+
+/// render_image_with_preview test: the previews fill in from the top, and
+/// the result is what `render_image` gives.
+#[cfg(feature = "threads")]
+#[test]
+fn test_render_image_with_preview() {
+    let bounds = PixelBounds::new(30, 20);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let threading = Threading::new(3);
+    let full = render_image(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                            Supersampling::none(), None, threading).unwrap();
+
+    let mut previews: Vec<Vec<u8>> = Vec::new();
+    let pixels = render_image_with_preview(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                                           Supersampling::none(), None, threading,
+                                           Duration::from_secs(0), |pixels| {
+        previews.push(pixels.to_vec());
+        Ok(())
+    }).unwrap();
+    assert!(pixels == full);
+
+    // Bands of three rows, and no preview of the finished image.
+    assert_eq!(previews.len(), 6);
+    for (i, preview) in previews.iter().enumerate() {
+        let done = (i + 1) * 3 * bounds.width;
+        assert!(preview[.. done] == full[.. done]);
+        assert!(preview[done ..].iter().all(|&byte| byte == 0));
+    }
+
+    // An error from the preview stops the render.
+    let result = render_image_with_preview(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                                           Supersampling::none(), None, threading,
+                                           Duration::from_secs(0),
+                                           |_| Err(RenderError::Allocation { bytes: 0 }));
+    assert!(matches!(result, Err(RenderError::Allocation { .. })));
+}
+
+/// RenderStats test: the JSON holds every measurement as a number.
+#[test]
+fn test_render_stats() {
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let stats = RenderStats {
+        elapsed: Duration::from_millis(250),
+        bounds: PixelBounds::new(30, 20),
+        view,
+        limit: 100,
+        samples: 4,
+        threads: 8,
+        iterations: 12345,
+    };
+    assert_eq!(stats.iterations_per_second(), Some(49380.0));
+    assert_eq!(RenderStats { elapsed: Duration::ZERO, ..stats }.iterations_per_second(), None);
+
+    /// A JSON value, as `parse` reads it.
+    #[derive(Debug, PartialEq)]
+    enum Json {
+        Null,
+        Number(f64),
+        Array(Vec<Json>),
+        Object(Vec<(String, Json)>),
+    }
+
+    impl Json {
+        fn get(&self, key: &str) -> &Json {
+            match self {
+                Json::Object(members) => members.iter().find(|(name, _)| name == key)
+                    .map(|(_, value)| value).expect(key),
+                _ => panic!("looking up `{}` in {:?}", key, self),
+            }
+        }
+
+        fn number(&self) -> f64 {
+            match *self {
+                Json::Number(number) => number,
+                _ => panic!("{:?} isn't a number", self),
+            }
+        }
+    }
+
+    /// Parse the whole of 'text' as one JSON value, by recursive descent
+    /// over the parts of JSON that `to_json` writes: objects with string
+    /// keys, arrays, numbers and null.
+    fn parse(text: &str) -> Result<Json, String> {
+        fn value(text: &mut &str) -> Result<Json, String> {
+            *text = text.trim_start();
+            if let Some(rest) = text.strip_prefix("null") {
+                *text = rest;
+                Ok(Json::Null)
+            } else if let Some(rest) = text.strip_prefix('[') {
+                *text = rest;
+                let mut items = Vec::new();
+                if !close(text, ']') {
+                    loop {
+                        items.push(value(text)?);
+                        if close(text, ']') {
+                            break;
+                        }
+                        expect(text, ',')?;
+                    }
+                }
+                Ok(Json::Array(items))
+            } else if let Some(rest) = text.strip_prefix('{') {
+                *text = rest;
+                let mut members = Vec::new();
+                if !close(text, '}') {
+                    loop {
+                        expect(text, '"')?;
+                        let end = text.find('"').ok_or("unterminated key")?;
+                        let key = text[..end].to_string();
+                        *text = &text[end + 1..];
+                        expect(text, ':')?;
+                        members.push((key, value(text)?));
+                        if close(text, '}') {
+                            break;
+                        }
+                        expect(text, ',')?;
+                    }
+                }
+                Ok(Json::Object(members))
+            } else {
+                let end = text.find(|c: char| !"+-.0123456789eE".contains(c)).unwrap_or(text.len());
+                let number = text[..end].parse().map_err(|_| format!("bad value at `{}`", text))?;
+                *text = &text[end..];
+                Ok(Json::Number(number))
+            }
+        }
+        fn expect(text: &mut &str, token: char) -> Result<(), String> {
+            *text = text.trim_start();
+            *text = text.strip_prefix(token).ok_or_else(|| format!("expected `{}` at `{}`", token, text))?;
+            Ok(())
+        }
+        fn close(text: &mut &str, token: char) -> bool {
+            expect(text, token).is_ok()
+        }
+
+        let mut rest = text;
+        let parsed = value(&mut rest)?;
+        if rest.trim().is_empty() { Ok(parsed) } else { Err(format!("trailing `{}`", rest)) }
+    }
+
+    // The parser itself turns away what isn't JSON.
+    for bad in &["{\"a\": 1,}", "{\"a\": 1", "[1 2]", "{\"a\" 1}", "{} {}"] {
+        assert!(parse(bad).is_err(), "{}", bad);
+    }
+
+    let json = parse(&stats.to_json()).unwrap();
+    assert_eq!(json.get("render_seconds").number(), 0.25);
+    assert_eq!(json.get("pixels").number(), 600.0);
+    assert_eq!(json.get("iterations").number(), 12345.0);
+    assert_eq!(json.get("threads").number(), 8.0);
+    assert_eq!(json.get("iterations_per_second").number(), 49380.0);
+    let parameters = json.get("parameters");
+    assert_eq!(parameters.get("width").number(), 30.0);
+    assert_eq!(parameters.get("height").number(), 20.0);
+    assert_eq!(parameters.get("limit").number(), 100.0);
+    assert_eq!(parameters.get("samples_per_pixel").number(), 4.0);
+    assert_eq!(parameters.get("upper_left"), &Json::Array(vec![Json::Number(-2.0), Json::Number(1.0)]));
+    assert_eq!(parameters.get("lower_right"), &Json::Array(vec![Json::Number(1.0), Json::Number(-1.0)]));
+
+    let json = parse(&RenderStats { elapsed: Duration::ZERO, ..stats }.to_json()).unwrap();
+    assert_eq!(json.get("iterations_per_second"), &Json::Null);
+}
+
+/// take_iterations test: renders count every sample they iterate, on
+/// however many threads.
+#[test]
+fn test_take_iterations() {
+    let bounds = PixelBounds::new(40, 30);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let expected: u64 = render_field(bounds, view, LIMIT).iter()
+        .map(|escape| escape.iterations(LIMIT) as u64)
+        .sum();
+
+    take_iterations();
+    for threads in [1, 4] {
+        let threading = Threading { threads, chunk_rows: Some(3) };
+        render_image(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray, Supersampling::none(),
+                     None, threading).unwrap();
+        assert_eq!(take_iterations(), expected);
+    }
+    assert_eq!(take_iterations(), 0);
+
+    // Each of four samples a pixel counts; other shadings don't.
+    render_image(bounds, view, Shading::EscapeTime, LIMIT, Coloring::Gray,
+                 Supersampling::square(2, DEFAULT_SUPERSAMPLE_SEED), None, Threading::new(2)).unwrap();
+    assert!(take_iterations() > 3 * expected);
+    render_image(bounds, view, Shading::Smooth { extra: 3 }, LIMIT, Coloring::Gray,
+                 Supersampling::none(), None, Threading::new(2)).unwrap();
+    assert_eq!(take_iterations(), 0);
+}
+
+/// InteriorPeriod test: the main cardioid and the period-2 bulb differ.
+#[test]
+fn test_interior_period_coloring() {
+    let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+    let coloring = Coloring::InteriorPeriod { base: &Coloring::Gray, colors: &colors };
+    assert_eq!(coloring.channels(), 3);
+    let color = |re, im| coloring.color(Complex { re, im }, Shading::EscapeTime, 1000);
+    let cardioid = color(-0.2, 0.1);
+    let bulb = color(-1.05, 0.05);
+    assert_ne!(cardioid, bulb);
+    assert_eq!(cardioid, [255, 0, 0, 255]);
+    assert_eq!(bulb, [0, 255, 0, 255]);
+    // Period 4 comes round to the first color again.
+    assert_eq!(color(-1.31, 0.0), [255, 0, 0, 255]);
+    // The exterior is colored as 'base' colors it.
+    let point = Complex { re: 0.5, im: 0.0 };
+    assert_eq!(coloring.color(point, Shading::EscapeTime, 1000),
+               Coloring::Gray.color(point, Shading::EscapeTime, 1000));
+
+    // Through a render, with the alpha made of it.
+    let view = ComplexView::new(Complex { re: -1.2, im: 0.1 }, Complex { re: 0.0, im: -0.1 });
+    let alpha = Coloring::Alpha { base: &coloring, clear: Region::Exterior };
+    let pixels = render_image(PixelBounds::new(2, 1), view, Shading::EscapeTime, 1000, alpha,
+                              Supersampling::none(), None, Threading { threads: 1, chunk_rows: None })
+        .unwrap();
+    assert_eq!(pixels, vec![0, 255, 0, 255, 255, 0, 0, 255]);
+}
+
+/// Threading::check test: overflow is caught right at the boundary.
+#[test]
+fn test_threading_check() {
+    let bounds = PixelBounds::new(1, 10);
+    let half = usize::MAX / 2;
+    assert!(Threading { threads: 2, chunk_rows: Some(half) }.check(bounds, 1).is_ok());
+    assert!(matches!(Threading { threads: 3, chunk_rows: Some(half) }.check(bounds, 1),
+                     Err(RenderError::InvalidThreading(_))));
+    // Every chunk's bytes have to be countable too.
+    let quarter = usize::MAX / 4;
+    assert!(Threading { threads: 1, chunk_rows: Some(quarter) }.check(bounds, 4).is_ok());
+    assert!(Threading { threads: 1, chunk_rows: Some(quarter) }.check(bounds, 5).is_err());
+    assert!(Threading { threads: 1, chunk_rows: Some(quarter) }
+                .check(PixelBounds::new(5, 10), 1).is_err());
+    // Ordinary settings are fine, and so are more threads than rows.
+    assert!(Threading::new(8).check(PixelBounds::new(4000, 3000), 4).is_ok());
+    assert!(Threading::new(64).check(PixelBounds::new(10, 3), 3).is_ok());
+
+    #[cfg(feature = "threads")]
+    {
+        assert_eq!(chunk_top(10, 4, 3), Some(22));
+        assert_eq!(chunk_top(0, half, 2), Some(usize::MAX - 1));
+        assert_eq!(chunk_top(1, half, 2), Some(usize::MAX));
+        assert_eq!(chunk_top(2, half, 2), None);
+        assert_eq!(chunk_top(0, half, 3), None);
+    }
+}
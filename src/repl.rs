@@ -0,0 +1,187 @@
+//! An interactive mode that keeps its settings between commands, so a
+//! view can be explored without starting the program over for every
+//! render.
+
+use num::Complex;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use image::ColorType;
+
+use crate::output::save_image;
+use crate::parse::{format_complex, parse_complex, parse_pair};
+use crate::render::{Coloring, LIMIT, Shading, Supersampling, Threading, render_image};
+use crate::view::{ComplexView, PixelBounds};
+
+/// What `help`, and any command not understood, prints.
+pub const HELP: &str = "\
+commands:
+  view RE,IM WIDTH   center the view on RE,IM, WIDTH wide on the real axis
+  size WxH           render images W by H pixels
+  iterations N       iterate every point at most N times
+  threads N          render on N threads
+  render FILE        render the current view to FILE
+  help               print this
+  quit               leave (so does the end of input)
+";
+
+/// The settings each `render` uses, as the commands so far left them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    /// The point in the middle of the view.
+    pub center: Complex<f64>,
+    /// The width of the view on the real axis. Its height follows from
+    /// the shape of the image.
+    pub width: f64,
+    pub bounds: PixelBounds,
+    pub limit: u32,
+    pub threads: usize,
+}
+
+impl Default for Session {
+    /// The whole set, at a modest size.
+    fn default() -> Session {
+        Session {
+            center: Complex { re: -0.5, im: 0.0 },
+            width: 3.0,
+            bounds: PixelBounds::new(600, 400),
+            limit: LIMIT,
+            threads: 8,
+        }
+    }
+}
+
+impl Session {
+    /// The part of the plane the current settings cover.
+    pub fn view(&self) -> ComplexView {
+        let height = self.width * self.bounds.height as f64 / self.bounds.width as f64;
+        let half = Complex { re: self.width / 2.0, im: height / 2.0 };
+        ComplexView::new(self.center + Complex { re: -half.re, im: half.im },
+                         self.center + Complex { re: half.re, im: -half.im })
+    }
+
+    /// Carry out the command 'line'. Returns the message to print, or an
+    /// error message for a command that can't be carried out, in which
+    /// case the settings are left as they were.
+    pub fn execute(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["view", center, width] => {
+                let center = parse_complex(center)
+                    .ok_or_else(|| format!("error parsing the center `{}`", center))?;
+                let width = width.parse::<f64>().ok()
+                    .filter(|width| width.is_finite() && *width > 0.0)
+                    .ok_or_else(|| format!("error parsing the width `{}`", width))?;
+                self.center = center;
+                self.width = width;
+                Ok(format!("view {} {:e}", format_complex(self.center), self.width))
+            }
+            ["size", size] => {
+                self.bounds = parse_pair(size, 'x')
+                    .filter(|&(width, height)| width > 0 && height > 0)
+                    .map(|(width, height)| PixelBounds::new(width, height))
+                    .ok_or_else(|| format!("error parsing the image dimensions `{}`", size))?;
+                Ok(format!("size {}x{}", self.bounds.width, self.bounds.height))
+            }
+            ["iterations", limit] => {
+                self.limit = limit.parse().ok().filter(|&limit| limit > 0)
+                    .ok_or_else(|| format!("`iterations` must be at least 1, not `{}`", limit))?;
+                Ok(format!("iterations {}", self.limit))
+            }
+            ["threads", threads] => {
+                self.threads = threads.parse().ok().filter(|&threads| threads > 0)
+                    .ok_or_else(|| format!("`threads` must be at least 1, not `{}`", threads))?;
+                Ok(format!("threads {}", self.threads))
+            }
+            ["render", file] => {
+                let pixels = render_image(self.bounds, self.view(), Shading::EscapeTime, self.limit,
+                                          Coloring::Gray, Supersampling::none(), None,
+                                          Threading::new(self.threads))
+                    .map_err(|error| error.to_string())?;
+                save_image(Path::new(file), &pixels, self.bounds, ColorType::Gray(8))
+                    .map_err(|error| error.to_string())?;
+                Ok(format!("wrote {}", file))
+            }
+            _ => Ok(HELP.to_string()),
+        }
+    }
+}
+
+/// Read commands from 'input' one line at a time, carrying each out on one
+/// `Session` and writing what it says to 'output', until `quit` or
+/// the end of the input. Blank lines are skipped, and a command that
+/// fails only reports why.
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
+    let mut session = Session::default();
+    for line in input.lines() {
+        let line = line?;
+        match line.trim() {
+            "" => continue,
+            "quit" | "exit" => break,
+            command => match session.execute(command) {
+                Ok(message) => writeln!(output, "{}", message.trim_end())?,
+                Err(message) => writeln!(output, "error: {}", message)?,
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// run test: a scripted session renders the view it was steered to.
+#[test]
+fn test_run() {
+    use std::{env, fs, process};
+
+    let path = env::temp_dir().join(format!("mandelbrot-repl-test-{}.png", process::id()));
+    let script = format!("size 40x30\n\
+                          view -0.75,0.1 3.0\n\
+                          \n\
+                          iterations 500\n\
+                          iterations lots\n\
+                          bogus\n\
+                          render {}\n\
+                          quit\n\
+                          render {}.never.png\n", path.display(), path.display());
+    let mut transcript = Vec::new();
+    run(script.as_bytes(), &mut transcript).unwrap();
+    let transcript = String::from_utf8(transcript).unwrap();
+
+    assert!(transcript.contains("size 40x30"));
+    assert!(transcript.contains("error: `iterations` must be at least 1, not `lots`"));
+    assert!(transcript.contains("commands:"));
+    assert!(transcript.contains(&format!("wrote {}", path.display())));
+
+    // The file is what those settings render to.
+    let session = Session {
+        center: Complex { re: -0.75, im: 0.1 },
+        bounds: PixelBounds::new(40, 30),
+        limit: 500,
+        ..Session::default()
+    };
+    let expected = render_image(session.bounds, session.view(), Shading::EscapeTime, 500,
+                                Coloring::Gray, Supersampling::none(), None, Threading::new(1))
+        .unwrap();
+    let image = image::open(&path).unwrap();
+    assert_eq!(image.raw_pixels(), expected);
+    fs::remove_file(&path).unwrap();
+
+    // Nothing after `quit` runs.
+    assert!(!Path::new(&format!("{}.never.png", path.display())).exists());
+}
+
+/// Session::view test
+#[test]
+fn test_session_view() {
+    let mut session = Session::default();
+    session.execute("view 1,2 4").unwrap();
+    session.execute("size 200x100").unwrap();
+    assert_eq!(session.view(), ComplexView::new(Complex { re: -1.0, im: 3.0 },
+                                                Complex { re: 3.0, im: 1.0 }));
+
+    // Failed commands change nothing.
+    assert!(session.execute("view 1,2 -4").is_err());
+    assert!(session.execute("size 0x100").is_err());
+    assert_eq!(session.width, 4.0);
+    assert_eq!(session.bounds, PixelBounds::new(200, 100));
+}
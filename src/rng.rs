@@ -0,0 +1,36 @@
+//! Small deterministic pseudo-random numbers.
+//!
+//! Everything that jitters or samples randomly draws from here, so that
+//! renders are reproducible from a seed on every machine.
+
+/// The SplitMix64 finalizer: scrambles 'x' into a well-mixed 64-bit value.
+pub fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Hash 'seed' together with each of 'values' in turn.
+pub fn hash(seed: u64, values: &[u64]) -> u64 {
+    values.iter().fold(mix(seed), |hash, &value| mix(hash ^ value))
+}
+
+/// Map a 64-bit hash to a float uniformly distributed in `[0, 1)`.
+pub fn unit_float(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// hash test
+#[test]
+fn test_hash() {
+    assert_eq!(hash(1, &[2, 3]), hash(1, &[2, 3]));
+    assert!(hash(1, &[2, 3]) != hash(1, &[3, 2]));
+    assert!(hash(1, &[2, 3]) != hash(2, &[2, 3]));
+
+    for i in 0..1000 {
+        let x = unit_float(hash(7, &[i]));
+        assert!((0.0..1.0).contains(&x));
+    }
+    assert_eq!(unit_float(u64::MAX), 1.0 - 1.0 / (1u64 << 53) as f64);
+}
@@ -0,0 +1,95 @@
+//! A built-in check that this build renders correctly, for packagers and
+//! CI, that needs no golden image files.
+
+use num::Complex;
+
+use crate::palette;
+use crate::render::{Coloring, LIMIT, Shading, Supersampling, Threading, render_parallel};
+use crate::view::{ComplexView, PixelBounds};
+
+/// Size of the image the self-test renders.
+const BOUNDS: PixelBounds = PixelBounds { width: 48, height: 32 };
+
+/// The FNV-1a hash of the grayscale and the `rainbow` palette renders of
+/// the self-test view, one after the other, as produced by a correct
+/// build. Only integer escape counts are used, so the expected value does
+/// not depend on the platform's logarithm implementation.
+const EXPECTED_HASH: u64 = 0x71e4_7bc1_e810_79bf;
+
+/// Number of black (interior) pixels in the grayscale self-test render.
+const EXPECTED_INTERIOR: usize = 404;
+
+/// Summary of a self-test render, compared field by field against the
+/// expected values so a failure says a little about what went wrong.
+#[derive(Debug, PartialEq)]
+struct Summary {
+    hash: u64,
+    interior: usize,
+}
+
+/// Render the self-test view through the threaded renderer and
+/// summarize the resulting pixels.
+fn run() -> Summary {
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 },
+                                Complex { re: 1.0, im: -1.0 });
+    let colors = palette::named("rainbow").expect("rainbow palette is built in");
+
+    let mut gray = vec![0; BOUNDS.pixel_count()];
+    let sampling = Supersampling::none();
+    render_parallel(&mut gray, BOUNDS, view, Shading::EscapeTime, LIMIT, Coloring::Gray, sampling,
+                    None, Threading::new(3));
+    let mut rgb = vec![0; BOUNDS.pixel_count() * 3];
+    let coloring = Coloring::Palette { colors: &colors, repeat: 2 };
+    render_parallel(&mut rgb, BOUNDS, view, Shading::EscapeTime, LIMIT, coloring, sampling,
+                    None, Threading::new(3));
+
+    let mut hash = FNV_OFFSET;
+    for &byte in gray.iter().chain(rgb.iter()) {
+        hash = fnv1a(hash, byte);
+    }
+
+    Summary {
+        hash,
+        interior: gray.iter().filter(|&&pixel| pixel == 0).count(),
+    }
+}
+
+/// Render the self-test view and compare it against the expected result.
+/// On a mismatch, the error describes every value that differed.
+pub fn self_test() -> Result<(), String> {
+    let actual = run();
+    let expected = Summary { hash: EXPECTED_HASH, interior: EXPECTED_INTERIOR };
+    if actual == expected {
+        return Ok(());
+    }
+
+    let mut report = String::from("self-test failed:");
+    if actual.hash != expected.hash {
+        report += &format!("\n  pixel hash: expected {:016x}, got {:016x}", expected.hash, actual.hash);
+    }
+    if actual.interior != expected.interior {
+        report += &format!("\n  interior pixels: expected {}, got {}", expected.interior, actual.interior);
+    }
+    Err(report)
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Fold one byte into an FNV-1a hash.
+fn fnv1a(hash: u64, byte: u8) -> u64 {
+    (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+}
+
+/// self_test test
+#[test]
+fn test_self_test() {
+    assert_eq!(self_test(), Ok(()));
+}
+
+/// fnv1a test
+#[test]
+fn test_fnv1a() {
+    // Published FNV-1a test vector for the string "a".
+    assert_eq!(fnv1a(FNV_OFFSET, b'a'), 0xaf63_dc4c_8601_ec8c);
+}
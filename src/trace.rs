@@ -0,0 +1,162 @@
+//! Tracing the boundary of the set into vector outlines, for images that
+//! scale to any size.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::view::PixelBounds;
+
+/// A crossing of the boundary, halfway along the line between the
+/// centers of two neighbouring pixels: horizontal (the pixel at 'x','y'
+/// and the one right of it) or not (it and the one below). The grid is
+/// padded with a ring of outside pixels, so 'x' and 'y' can be -1.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Crossing {
+    horizontal: bool,
+    x: isize,
+    y: isize,
+}
+
+impl Crossing {
+    /// Where the crossing is, in pixels from the image's top left corner.
+    fn position(self) -> (f64, f64) {
+        let (x, y) = (self.x as f64 + 0.5, self.y as f64 + 0.5);
+        if self.horizontal { (x + 0.5, y) } else { (x, y + 0.5) }
+    }
+}
+
+/// Trace the boundary of the pixels 'inside' says are in, an image of
+/// size 'bounds' in row order, by marching squares over the pixel
+/// centers. Returns one closed polygon per outline, in pixels from the
+/// top left corner, its last vertex joined back to its first; holes get
+/// outlines of their own. Pixels that touch only diagonally count as
+/// apart.
+pub fn trace_boundary(inside: &[bool], bounds: PixelBounds) -> Vec<Vec<(f64, f64)>> {
+    let at = |x: isize, y: isize| {
+        x >= 0 && y >= 0 && (x as usize) < bounds.width && (y as usize) < bounds.height
+            && inside[y as usize * bounds.width + x as usize]
+    };
+
+    // The segments of the boundary through every square of four pixel
+    // centers, and which segments meet at each crossing: always two.
+    let mut segments = Vec::new();
+    let mut meeting: HashMap<Crossing, Vec<usize>> = HashMap::new();
+    for y in -1 .. bounds.height as isize {
+        for x in -1 .. bounds.width as isize {
+            let top = Crossing { horizontal: true, x, y };
+            let bottom = Crossing { horizontal: true, x, y: y + 1 };
+            let left = Crossing { horizontal: false, x, y };
+            let right = Crossing { horizontal: false, x: x + 1, y };
+            let case = (at(x, y) as u8) << 3 | (at(x + 1, y) as u8) << 2
+                | (at(x + 1, y + 1) as u8) << 1 | at(x, y + 1) as u8;
+            let square: &[(Crossing, Crossing)] = match case {
+                1 | 14 => &[(left, bottom)],
+                2 | 13 => &[(bottom, right)],
+                3 | 12 => &[(left, right)],
+                4 | 11 => &[(top, right)],
+                6 | 9 => &[(top, bottom)],
+                7 | 8 => &[(left, top)],
+                5 => &[(top, right), (left, bottom)],
+                10 => &[(left, top), (bottom, right)],
+                _ => &[],
+            };
+            for &(a, b) in square {
+                meeting.entry(a).or_default().push(segments.len());
+                meeting.entry(b).or_default().push(segments.len());
+                segments.push((a, b));
+            }
+        }
+    }
+
+    // Follow the segments from crossing to crossing around each outline.
+    let mut used = vec![false; segments.len()];
+    let mut outlines = Vec::new();
+    for first in 0 .. segments.len() {
+        if used[first] {
+            continue;
+        }
+        let (start, mut current) = segments[first];
+        let mut segment = first;
+        let mut outline = vec![start.position()];
+        used[first] = true;
+        while current != start {
+            outline.push(current.position());
+            segment = meeting[&current].iter().copied()
+                .find(|&other| other != segment && !used[other])
+                .expect("every crossing joins two segments");
+            used[segment] = true;
+            let (a, b) = segments[segment];
+            current = if a == current { b } else { a };
+        }
+        outlines.push(outline);
+    }
+
+    outlines
+}
+
+/// An SVG image of size 'bounds' filling 'outlines' in black, as one path
+/// with a subpath per outline. The even-odd rule leaves holes empty.
+pub fn svg(outlines: &[Vec<(f64, f64)>], bounds: PixelBounds) -> String {
+    let mut path = String::new();
+    for outline in outlines {
+        for (i, &(x, y)) in outline.iter().enumerate() {
+            let command = if i == 0 { 'M' } else { 'L' };
+            write!(path, "{}{} {} ", command, x, y).expect("writing to a String can't fail");
+        }
+        path.push_str("Z ");
+    }
+
+    format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+             viewBox=\"0 0 {0} {1}\">\n\
+             <path d=\"{2}\" fill=\"black\" fill-rule=\"evenodd\"/>\n\
+             </svg>\n", bounds.width, bounds.height, path.trim_end())
+}
+
+/// trace_boundary test
+#[test]
+fn test_trace_boundary() {
+    // A single pixel is a diamond through the midpoints to its neighbours.
+    let bounds = PixelBounds::new(3, 3);
+    let mut inside = vec![false; 9];
+    inside[4] = true;
+    let outlines = trace_boundary(&inside, bounds);
+    assert_eq!(outlines.len(), 1);
+    let mut diamond = outlines[0].clone();
+    diamond.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(diamond, vec![(1.0, 1.5), (1.5, 1.0), (1.5, 2.0), (2.0, 1.5)]);
+
+    // A 2x2 block crosses two lines on each side. Being at the edge of
+    // the image doesn't leave the outline open.
+    let bounds = PixelBounds::new(3, 2);
+    let inside = [true, true, false,
+                  true, true, false];
+    let outlines = trace_boundary(&inside, bounds);
+    assert_eq!(outlines.len(), 1);
+    assert_eq!(outlines[0].len(), 8);
+    for window in outlines[0].windows(2).chain(Some(&[outlines[0][7], outlines[0][0]][..])) {
+        let (dx, dy) = (window[1].0 - window[0].0, window[1].1 - window[0].1);
+        assert!(dx.abs() <= 1.0 && dy.abs() <= 1.0, "{:?}", window);
+    }
+
+    // A ring has its outside and its hole; diagonal neighbours don't join.
+    let bounds = PixelBounds::new(5, 5);
+    let mut ring = vec![false; 25];
+    for &i in &[6, 7, 8, 11, 13, 16, 17, 18] {
+        ring[i] = true;
+    }
+    assert_eq!(trace_boundary(&ring, bounds).len(), 2);
+    let diagonal = [true, false, false, true];
+    assert_eq!(trace_boundary(&diagonal, PixelBounds::new(2, 2)).len(), 2);
+    assert!(trace_boundary(&[false; 4], PixelBounds::new(2, 2)).is_empty());
+}
+
+/// svg test
+#[test]
+fn test_svg() {
+    let outlines = vec![vec![(1.0, 1.5), (1.5, 1.0), (2.0, 1.5), (1.5, 2.0)]];
+    let svg = svg(&outlines, PixelBounds::new(3, 3));
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"3\" height=\"3\" \
+                             viewBox=\"0 0 3 3\">"));
+    assert!(svg.contains("d=\"M1 1.5 L1.5 1 L2 1.5 L1.5 2 Z\""));
+    assert!(svg.trim_end().ends_with("</svg>"));
+}
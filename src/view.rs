@@ -0,0 +1,398 @@
+use num::Complex;
+
+use crate::kernel;
+
+/// The size of an image in pixels.
+///
+/// Kept apart from `ComplexView` so a pixel size can't be passed where
+/// a region of the complex plane is expected, or the other way around.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelBounds {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl PixelBounds {
+    pub fn new(width: usize, height: usize) -> PixelBounds {
+        PixelBounds { width, height }
+    }
+
+    /// Number of pixels in an image of this size.
+    pub fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+/// How the corners of a `ComplexView`, and the positions between them,
+/// are taken onto the complex plane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Projection {
+    /// The view is a flat window onto the plane: positions are points.
+    Plane,
+    /// The view is a map of the Riemann sphere, with 0 at the origin and
+    /// infinity all around the unit circle. A position at distance r from
+    /// the origin stands for the point of the sphere at polar angle π r
+    /// from 0, which inverse stereographic projection takes back to the
+    /// plane at |c| = tan(π r / 2), in the position's direction. The view
+    /// from -1+1i to 1-1i fits the whole plane in a disk. Positions on or
+    /// past the unit circle are infinity, and outside the set.
+    Stereographic,
+}
+
+/// The modulus `Projection::Stereographic` gives infinity and the points
+/// next to it: far enough out to escape at once, and near enough that
+/// squaring it a few more times for smooth coloring doesn't overflow.
+const PROJECTED_INFINITY: f64 = 1e8;
+
+impl Projection {
+    /// The point of the complex plane that position 'position' of a
+    /// view stands for.
+    pub fn point(self, position: Complex<f64>) -> Complex<f64> {
+        match self {
+            Projection::Plane => position,
+            Projection::Stereographic => {
+                let r = position.norm();
+                if r == 0.0 {
+                    return position;
+                }
+                let modulus = if r < 1.0 {
+                    (std::f64::consts::FRAC_PI_2 * r).tan().min(PROJECTED_INFINITY)
+                } else {
+                    PROJECTED_INFINITY
+                };
+                position * (modulus / r)
+            }
+        }
+    }
+}
+
+/// The rectangle of the complex plane covered by an image, given by its
+/// upper left and lower right corners. Under a 'projection' other than
+/// `Projection::Plane` the corners are positions it maps, rather than
+/// points of the plane themselves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComplexView {
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    pub projection: Projection,
+}
+
+impl ComplexView {
+    pub fn new(upper_left: Complex<f64>, lower_right: Complex<f64>) -> ComplexView {
+        ComplexView { upper_left, lower_right, projection: Projection::Plane }
+    }
+
+    /// This view, seen through 'projection' instead.
+    pub fn with_projection(self, projection: Projection) -> ComplexView {
+        ComplexView { projection, ..self }
+    }
+
+    /// The part of this view covered by the 'height' rows starting at
+    /// row 'top' of an image of size 'bounds'. This is what a band of
+    /// the image rendered on its own needs to be given.
+    pub fn rows(&self, bounds: PixelBounds, top: usize, height: usize) -> ComplexView {
+        ComplexView {
+            upper_left: position(bounds, (0.0, top as f64), *self),
+            lower_right: position(bounds, (bounds.width as f64, (top + height) as f64), *self),
+            projection: self.projection,
+        }
+    }
+}
+
+/// Given the row and column of a pixel in the output image, return the
+/// corresponding point on the complex plane.
+///
+/// 'bounds' gives the size of the image and 'pixel' a (column, row) pair
+/// indicating a particular pixel in it. 'view' is the area of the
+/// complex plane the image covers.
+pub fn pixel_to_point(bounds: PixelBounds,
+            pixel: (usize, usize),
+            view: ComplexView) -> Complex<f64> {
+            subpixel_to_point(bounds, (pixel.0 as f64, pixel.1 as f64), view)
+}
+
+/// Every pixel of an image of size 'bounds' covering 'view', as a
+/// (column, row) pair, with its point as `pixel_to_point` gives it: row
+/// by row from the top, left to right along each. A building block for
+/// renderers of one's own that need the same mapping as this crate's.
+/// The points are worked out lazily as the iterator is advanced, with
+/// nothing allocated.
+///
+/// ```
+/// use mandelbrot::view::{ComplexView, PixelBounds, pixels};
+/// use num::Complex;
+///
+/// let view = ComplexView::new(Complex::new(-1.0, 1.0), Complex::new(1.0, -1.0));
+/// let points: Vec<_> = pixels(PixelBounds::new(2, 2), view).collect();
+/// assert_eq!(points, vec![((0, 0), Complex::new(-1.0, 1.0)),
+///                         ((1, 0), Complex::new(0.0, 1.0)),
+///                         ((0, 1), Complex::new(-1.0, 0.0)),
+///                         ((1, 1), Complex::new(0.0, 0.0))]);
+/// ```
+pub fn pixels(bounds: PixelBounds, view: ComplexView)
+    -> impl Iterator<Item = ((usize, usize), Complex<f64>)> {
+    (0 .. bounds.height)
+        .flat_map(move |row| (0 .. bounds.width).map(move |column| (column, row)))
+        .map(move |pixel| (pixel, pixel_to_point(bounds, pixel, view)))
+}
+
+/// Like `pixel_to_point`, but for a position anywhere inside a pixel:
+/// `(column + 0.5, row + 0.5)` is the center of the pixel at
+/// `(column, row)`.
+pub fn subpixel_to_point(bounds: PixelBounds,
+            pixel: (f64, f64),
+            view: ComplexView) -> Complex<f64> {
+            view.projection.point(position(bounds, pixel, view))
+}
+
+/// Where 'pixel' is between the corners of 'view', before its projection
+/// takes it onto the plane.
+fn position(bounds: PixelBounds,
+            pixel: (f64, f64),
+            view: ComplexView) -> Complex<f64> {
+            kernel::subpixel_to_point((bounds.width, bounds.height), pixel, view.upper_left,
+                                      view.lower_right)
+}
+
+/// The view an explorer moves on to after clicking 'pixel' in an image
+/// of size 'bounds' covering 'view': centered on that pixel's point, as
+/// `pixel_to_point` gives it, and 'zoom' times narrower (so a 'zoom'
+/// above one zooms in, and below one out). The new view keeps the old
+/// one's projection, and is recentered before it, on the map of the
+/// sphere rather than on the plane.
+pub fn recenter(view: ComplexView, bounds: PixelBounds, pixel: (usize, usize), zoom: f64)
+    -> ComplexView {
+    let center = position(bounds, (pixel.0 as f64, pixel.1 as f64), view);
+    let half = (view.lower_right - view.upper_left) / (2.0 * zoom);
+    ComplexView { upper_left: center - half, lower_right: center + half, ..view }
+}
+
+/// The whole multiple of the width and of the height of a pixel of an
+/// image of size 'bounds' covering 'view' nearest the view's center: a
+/// point on the pixel grid, at most half a pixel from the center.
+pub fn grid_center(view: ComplexView, bounds: PixelBounds) -> Complex<f64> {
+    let step = Complex {
+        re: (view.lower_right.re - view.upper_left.re) / bounds.width as f64,
+        im: (view.upper_left.im - view.lower_right.im) / bounds.height as f64,
+    };
+    let center = (view.upper_left + view.lower_right) / 2.0;
+    Complex {
+        re: (center.re / step.re).round() * step.re,
+        im: (center.im / step.im).round() * step.im,
+    }
+}
+
+/// 'view' moved to be centered on its `grid_center`, with its size
+/// unchanged. The move is at most half a pixel each way, so the view
+/// shows the same thing. Frames of an animation zooming about a snapped
+/// view's center zoom about a point on the grid, instead of one off it
+/// by the last bits of however the center was worked out.
+pub fn snap_to_grid(view: ComplexView, bounds: PixelBounds) -> ComplexView {
+    let center = grid_center(view, bounds);
+    let half = (view.lower_right - view.upper_left) / 2.0;
+    ComplexView { upper_left: center - half, lower_right: center + half, ..view }
+}
+
+/// Fill 'out' with the points `pixel_to_point` gives for the pixels of
+/// row 'row', left to right. 'out' must be `bounds.width` long.
+///
+/// Rather than dividing for every pixel, this works out the step between
+/// neighbouring pixels once and adds it up along the row, which is
+/// quicker on the hot path of rendering. Repeated addition rounds a
+/// little differently from `pixel_to_point`, so the points agree only to
+/// within floating-point error (tiny compared to a pixel). Projected
+/// views aren't evenly spaced, so their points come from
+/// `pixel_to_point` itself.
+pub fn row_points(bounds: PixelBounds, row: usize, view: ComplexView, out: &mut [Complex<f64>]) {
+    assert!(out.len() == bounds.width);
+    if view.projection != Projection::Plane {
+        for (column, slot) in out.iter_mut().enumerate() {
+            *slot = pixel_to_point(bounds, (column, row), view);
+        }
+        return;
+    }
+
+    let mut point = pixel_to_point(bounds, (0, row), view);
+    let step = column_step(bounds, view);
+    for slot in out.iter_mut() {
+        *slot = point;
+        point.re += step;
+    }
+}
+
+/// How far apart on the real axis `row_points` puts neighbouring pixels
+/// of a plane view. Anything that adds it up the way `row_points` does,
+/// starting from column 0, gets exactly the same points.
+pub fn column_step(bounds: PixelBounds, view: ComplexView) -> f64 {
+    (view.lower_right.re - view.upper_left.re) / bounds.width as f64
+}
+
+/// How many units in the last place of the view's largest coordinate a
+/// pixel must span for neighbouring pixels to get distinct, evenly spaced
+/// points. Below this, rounding makes pixels repeat and the image blocky.
+const MIN_ULPS_PER_PIXEL: f64 = 4.0;
+
+/// Whether an image of size 'bounds' covering 'view' is too deep a zoom
+/// for f64: whether the step from one pixel to the next, across or down,
+/// is so small next to the coordinates themselves that f64 can no longer
+/// tell neighbouring pixels apart reliably.
+pub fn exceeds_f64_resolution(bounds: PixelBounds, view: ComplexView) -> bool {
+    let (upper_left, lower_right) = (view.upper_left, view.lower_right);
+    let largest = [upper_left.re, upper_left.im, lower_right.re, lower_right.im].iter()
+        .fold(f64::MIN_POSITIVE, |largest, coordinate| largest.max(coordinate.abs()));
+    let resolution = largest * f64::EPSILON * MIN_ULPS_PER_PIXEL;
+
+    let step_x = (lower_right.re - upper_left.re).abs() / bounds.width.max(1) as f64;
+    let step_y = (upper_left.im - lower_right.im).abs() / bounds.height.max(1) as f64;
+    step_x < resolution || step_y < resolution
+}
+
+/// pixel_to_point test
+#[test]
+fn test_pixel_to_point() {
+    assert_eq!(pixel_to_point(PixelBounds::new(100, 100), (25, 75),
+                    ComplexView::new(Complex { re: -1.0, im: 1.0 },
+                                     Complex { re: 1.0, im: -1.0 })),
+                    Complex { re: -0.5, im: -0.5 });
+}
+
+/// recenter test
+#[test]
+fn test_recenter() {
+    let bounds = PixelBounds::new(300, 200);
+    let view = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+
+    // Clicking the center without zooming changes nothing.
+    assert_eq!(recenter(view, bounds, (150, 100), 1.0), view);
+
+    // Zooming in by 4 on the pixel at the origin.
+    let zoomed = recenter(view, bounds, (200, 100), 4.0);
+    assert_eq!(zoomed, ComplexView::new(Complex { re: -0.375, im: 0.25 },
+                                        Complex { re: 0.375, im: -0.25 }));
+    let center = pixel_to_point(PixelBounds::new(2, 2), (1, 1), zoomed);
+    assert_eq!(center, pixel_to_point(bounds, (200, 100), view));
+
+    let projected = view.with_projection(Projection::Stereographic);
+    assert_eq!(recenter(projected, bounds, (150, 100), 2.0).projection, Projection::Stereographic);
+}
+
+/// grid_center and snap_to_grid test: a center a hair off the pixel grid
+/// lands exactly on it, without the view moving or changing size
+/// noticeably.
+#[test]
+fn test_snap_to_grid() {
+    let bounds = PixelBounds::new(400, 300);
+    // Pixels 0.01 wide and high, centered 1e-12 off (-0.75, 0.1).
+    let drift = Complex { re: 1e-12, im: -1e-12 };
+    let view = ComplexView::new(Complex { re: -2.75, im: 1.6 } + drift,
+                                Complex { re: 1.25, im: -1.4 } + drift);
+    let step = Complex { re: 4.0 / 400.0, im: 3.0 / 300.0 };
+    let center = grid_center(view, bounds);
+    assert_eq!(center, Complex { re: -75.0 * step.re, im: 10.0 * step.im });
+
+    // The snapped view's corners are worked out from that center, and
+    // its middle is back on it to within rounding.
+    let snapped = snap_to_grid(view, bounds);
+    assert!(((snapped.upper_left + snapped.lower_right) / 2.0 - center).norm() < 1e-15);
+    assert_eq!(grid_center(snapped, bounds), center);
+    assert!((snapped.upper_left - view.upper_left).norm() < 1e-11);
+    assert!((snapped.lower_right - view.lower_right).norm() < 1e-11);
+
+    // A far-off center moves by no more than half a pixel.
+    let off = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0033, im: -1.0 });
+    let moved = snap_to_grid(off, bounds);
+    assert!((moved.upper_left.re - off.upper_left.re).abs() <= (1.0033 + 2.0) / 800.0);
+    assert!((moved.upper_left.im - off.upper_left.im).abs() <= 2.0 / 600.0);
+}
+
+/// ComplexView::rows test
+#[test]
+fn test_view_rows() {
+    let bounds = PixelBounds::new(100, 100);
+    let view = ComplexView::new(Complex { re: -1.0, im: 1.0 },
+                                Complex { re: 1.0, im: -1.0 });
+    assert_eq!(view.rows(bounds, 0, 100), view);
+    assert_eq!(view.rows(bounds, 25, 50),
+               ComplexView::new(Complex { re: -1.0, im: 0.5 },
+                                Complex { re: 1.0, im: -0.5 }));
+}
+
+/// subpixel_to_point test
+#[test]
+fn test_subpixel_to_point() {
+    let bounds = PixelBounds::new(100, 100);
+    let view = ComplexView::new(Complex { re: -1.0, im: 1.0 },
+                                Complex { re: 1.0, im: -1.0 });
+    assert_eq!(subpixel_to_point(bounds, (25.0, 75.0), view),
+               pixel_to_point(bounds, (25, 75), view));
+    assert_eq!(subpixel_to_point(bounds, (12.5, 87.5), view),
+               Complex { re: -0.75, im: -0.75 });
+}
+
+/// row_points test
+#[test]
+fn test_row_points() {
+    let bounds = PixelBounds::new(997, 13);
+    let view = ComplexView::new(Complex { re: -1.7499, im: 0.0311 },
+                                Complex { re: -1.7411, im: 0.0223 });
+    let pixel_width = (view.lower_right.re - view.upper_left.re) / bounds.width as f64;
+
+    let mut points = vec![Complex { re: 0.0, im: 0.0 }; bounds.width];
+    for row in 0..bounds.height {
+        row_points(bounds, row, view, &mut points);
+        for (column, point) in points.iter().enumerate() {
+            let expected = pixel_to_point(bounds, (column, row), view);
+            assert_eq!(point.im, expected.im);
+            assert!((point.re - expected.re).abs() < pixel_width * 1e-6);
+        }
+    }
+}
+
+/// Stereographic projection test
+#[test]
+fn test_stereographic_projection() {
+    let bounds = PixelBounds::new(101, 101);
+    let view = ComplexView::new(Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+        .with_projection(Projection::Stereographic);
+
+    // The center pixel straddles the origin, so its point is within a
+    // pixel's worth of it.
+    let center = subpixel_to_point(bounds, (50.5, 50.5), view);
+    assert!(center.norm() < 1e-12, "center maps to {}", center);
+    let next = pixel_to_point(bounds, (51, 50), view);
+    assert!(next.norm() < 0.03 && next.re > 0.0 && next.im > 0.0);
+
+    // Halfway out is the unit circle, with directions kept.
+    let half = Projection::Stereographic.point(Complex { re: 0.0, im: -0.5 });
+    assert!((half - Complex { re: 0.0, im: -1.0 }).norm() < 1e-12);
+
+    // The corners are past infinity, which is finite and escapes at once.
+    let corner = pixel_to_point(bounds, (0, 0), view);
+    assert!((corner.norm() - PROJECTED_INFINITY).abs() < 1.0);
+    assert_eq!(crate::escape::escape_time(corner, 255), Some(0));
+
+    // Bands of a projected view still make up the whole.
+    let band = view.rows(bounds, 40, 10);
+    assert_eq!(band.projection, Projection::Stereographic);
+    let in_band = pixel_to_point(PixelBounds::new(101, 10), (70, 3), band);
+    assert!((in_band - pixel_to_point(bounds, (70, 43), view)).norm() < 1e-12);
+    let mut points = vec![Complex { re: 0.0, im: 0.0 }; bounds.width];
+    row_points(bounds, 43, view, &mut points);
+    assert_eq!(points[70], pixel_to_point(bounds, (70, 43), view));
+}
+
+/// exceeds_f64_resolution test
+#[test]
+fn test_exceeds_f64_resolution() {
+    let bounds = PixelBounds::new(1000, 750);
+    let whole = ComplexView::new(Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert!(!exceeds_f64_resolution(bounds, whole));
+
+    // Around -0.75 an f64 step is about 1.1e-16, so a view 1e-11 wide
+    // still has room for 1000 pixels but one 1e-14 wide doesn't.
+    let center = Complex { re: -0.75, im: 0.1 };
+    let deep = |size: f64| ComplexView::new(center + Complex { re: -size, im: size } / 2.0,
+                                            center + Complex { re: size, im: -size } / 2.0);
+    assert!(!exceeds_f64_resolution(bounds, deep(1e-11)));
+    assert!(exceeds_f64_resolution(bounds, deep(1e-14)));
+}